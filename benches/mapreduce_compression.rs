@@ -0,0 +1,86 @@
+//! Sends a representative map output from a local worker to the manager with each
+//! compression level. Run with `cargo bench --bench mapreduce_compression`.
+//!
+//! The worker runs on localhost, so the numbers are the cost of compressing and
+//! decompressing the output rather than the time it saves on a slow network.
+
+use std::{
+    net::{SocketAddr, TcpListener},
+    sync::OnceLock,
+    time::Duration,
+};
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use cuely::mapreduce::{Manager, Map, StatelessWorker, Worker};
+use serde::{Deserialize, Serialize};
+
+/// Term counts per url, like the output of an indexing job.
+type Output = Vec<(String, Vec<(String, u64)>)>;
+
+fn output() -> &'static Output {
+    static OUTPUT: OnceLock<Output> = OnceLock::new();
+
+    OUTPUT.get_or_init(|| {
+        (0..5_000u64)
+            .map(|i| {
+                let url = format!("https://www.example{}.com/articles/{}", i % 50, i);
+                let terms = (0..20)
+                    .map(|j| (format!("term{}", (i * j) % 500), j))
+                    .collect();
+
+                (url, terms)
+            })
+            .collect()
+    })
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct OutputJob;
+
+impl Map<StatelessWorker, Output> for OutputJob {
+    fn map(self, _worker: &StatelessWorker) -> Output {
+        output().clone()
+    }
+}
+
+fn spawn_worker() -> SocketAddr {
+    let addr = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap();
+
+    std::thread::spawn(move || {
+        StatelessWorker::default()
+            .run::<OutputJob, Output>(addr)
+            .unwrap();
+    });
+    std::thread::sleep(Duration::from_millis(100)); // give the worker time to bind
+
+    addr
+}
+
+fn compression(c: &mut Criterion) {
+    let worker = spawn_worker();
+    let payload = bincode::serialize(output()).unwrap();
+
+    let mut group = c.benchmark_group("compression");
+    group.throughput(Throughput::Bytes(payload.len() as u64));
+
+    for level in [None, Some(1), Some(6), Some(9)] {
+        let manager = match level {
+            Some(level) => Manager::new(&[worker]).with_compression(level),
+            None => Manager::new(&[worker]),
+        };
+
+        group.bench_with_input(
+            BenchmarkId::from_parameter(format!("{:?}", level)),
+            &manager,
+            |b, manager| b.iter(|| manager.map::<StatelessWorker, OutputJob, Output>(OutputJob)),
+        );
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, compression);
+criterion_main!(benches);
@@ -0,0 +1,62 @@
+use std::{
+    collections::HashSet,
+    net::SocketAddr,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use cuely::mapreduce::{Map, Reduce, StatefulWorker, Worker};
+use serde::{Deserialize, Serialize};
+use tracing::{info, Level};
+use tracing_subscriber::FmtSubscriber;
+
+/// State that is expensive to create and therefore only loaded once per worker.
+struct State {
+    even_ids: HashSet<usize>,
+    num_tasks: AtomicUsize,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Job {
+    id: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Count(usize);
+
+impl Map<StatefulWorker<State>, Count> for Job {
+    fn map(self, worker: &StatefulWorker<State>) -> Count {
+        let state = worker.state();
+        let num_tasks = state.num_tasks.fetch_add(1, Ordering::SeqCst) + 1;
+        info!("executed {} tasks on this worker", num_tasks);
+
+        Count(state.even_ids.contains(&self.id) as usize)
+    }
+}
+
+impl Reduce<Count> for Count {
+    fn reduce(self, element: Self) -> Self {
+        Count(self.0 + element.0)
+    }
+}
+
+fn main() {
+    let subscriber = FmtSubscriber::builder()
+        .with_max_level(Level::DEBUG)
+        .finish();
+    tracing::subscriber::set_global_default(subscriber).unwrap();
+
+    let args: Vec<_> = std::env::args().collect();
+
+    let worker = StatefulWorker::new(|| {
+        std::thread::sleep(std::time::Duration::from_secs(2)); // simulate some expensive setup
+        State {
+            even_ids: (0..1_000usize).filter(|id| id.is_multiple_of(2)).collect(),
+            num_tasks: AtomicUsize::new(0),
+        }
+    })
+    .initialized();
+
+    worker
+        .run::<Job, Count>(args[1].parse::<SocketAddr>().unwrap())
+        .expect("failed to run worker");
+}
@@ -53,7 +53,7 @@ fn iterate_children(path: &str) -> Result<Vec<DirEntry>> {
     Ok(res)
 }
 
-pub fn recreate_folder(entry: &DirEntry) -> Result<()> {
+pub fn recreate_folder(entry: &DirEntry) -> std::io::Result<()> {
     match entry {
         DirEntry::Folder { name, entries } => {
             fs::create_dir(name)?;
@@ -64,7 +64,7 @@ pub fn recreate_folder(entry: &DirEntry) -> Result<()> {
 
             Ok(())
         }
-        DirEntry::File { name, content } => Ok(fs::write(name, content)?),
+        DirEntry::File { name, content } => fs::write(name, content),
     }
 }
 
@@ -74,3 +74,31 @@ pub fn scan_folder(path: String) -> Result<DirEntry> {
         name: path,
     })
 }
+
+fn relocate_entry(entry: DirEntry, old_root: &str, new_root: &str) -> DirEntry {
+    let rename = |name: String| format!("{}{}", new_root, &name[old_root.len()..]);
+
+    match entry {
+        DirEntry::Folder { name, entries } => DirEntry::Folder {
+            name: rename(name),
+            entries: entries
+                .into_iter()
+                .map(|entry| relocate_entry(entry, old_root, new_root))
+                .collect(),
+        },
+        DirEntry::File { name, content } => DirEntry::File {
+            name: rename(name),
+            content,
+        },
+    }
+}
+
+/// Moves the entry (and all its children) to `path`, so it can be recreated
+/// somewhere else than where it was scanned.
+pub fn relocate(entry: DirEntry, path: &str) -> DirEntry {
+    let root = match &entry {
+        DirEntry::Folder { name, entries: _ } | DirEntry::File { name, content: _ } => name.clone(),
+    };
+
+    relocate_entry(entry, &root, path)
+}
@@ -20,7 +20,7 @@ use serde::Serialize;
 
 use crate::{
     image_store::{Image, ImageStore},
-    webpage::Url,
+    webpage::{DownloadOptions, Url, DEFAULT_DOWNLOAD_TIMEOUT},
 };
 
 #[derive(Clone, Debug)]
@@ -44,8 +44,12 @@ where
             }
 
             if let Some(image) = url
-                .download_bytes(self.timeout.unwrap_or_else(|| Duration::from_secs(20)))
+                .download_bytes(&DownloadOptions {
+                    timeout: self.timeout.unwrap_or(DEFAULT_DOWNLOAD_TIMEOUT),
+                    ..Default::default()
+                })
                 .await
+                .ok()
                 .and_then(|bytes| Image::from_bytes(bytes).ok())
                 .map(|image| DownloadedImage {
                     image,
@@ -0,0 +1,78 @@
+// Cuely is an open source web search engine.
+// Copyright (C) 2022 Cuely ApS
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::{collections::BTreeMap, sync::RwLock};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::kv::Kv;
+
+/// Store that keeps everything in memory, which is useful for fast tests.
+/// Nothing is persisted, so the content is lost when the store is dropped.
+/// Keys are ordered by their serialized bytes, like in RocksDB.
+#[allow(unused)]
+#[derive(Default)]
+pub struct MemoryStore {
+    map: RwLock<BTreeMap<Vec<u8>, Vec<u8>>>,
+}
+
+impl MemoryStore {
+    pub fn open<K, V>() -> Box<dyn Kv<K, V> + Send + Sync>
+    where
+        K: Serialize + DeserializeOwned + 'static,
+        V: Serialize + DeserializeOwned + 'static,
+    {
+        Box::new(MemoryStore::default())
+    }
+}
+
+impl<K, V> Kv<K, V> for MemoryStore
+where
+    K: Serialize + DeserializeOwned + 'static,
+    V: Serialize + DeserializeOwned + 'static,
+{
+    fn get_raw(&self, key: &[u8]) -> Option<Vec<u8>> {
+        self.map.read().unwrap().get(key).cloned()
+    }
+
+    fn insert_raw(&self, key: Vec<u8>, value: Vec<u8>) {
+        self.map.write().unwrap().insert(key, value);
+    }
+
+    fn remove_raw(&self, key: &[u8]) {
+        self.map.write().unwrap().remove(key);
+    }
+
+    fn flush(&self) {}
+
+    fn iter<'a>(&'a self) -> Box<dyn Iterator<Item = (K, V)> + 'a> {
+        // the entries are copied so the lock isn't held while iterating
+        let entries: Vec<_> = self
+            .map
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+
+        Box::new(entries.into_iter().map(|(key_bytes, value_bytes)| {
+            (
+                bincode::deserialize(&key_bytes).expect("Failed to deserialize key"),
+                bincode::deserialize(&value_bytes).expect("Failed to deserialize value"),
+            )
+        }))
+    }
+}
@@ -16,6 +16,7 @@
 
 use serde::{de::DeserializeOwned, Serialize};
 
+pub mod memory_store;
 pub mod rocksdb_store;
 
 pub trait Kv<K, V>
@@ -26,9 +27,13 @@ where
 {
     fn get_raw(&self, key: &[u8]) -> Option<Vec<u8>>;
     fn insert_raw(&self, key: Vec<u8>, value: Vec<u8>);
+    fn remove_raw(&self, key: &[u8]);
     fn flush(&self);
     fn iter<'a>(&'a self) -> Box<dyn Iterator<Item = (K, V)> + 'a>;
 
+    /// Restores the normal settings of a store opened for bulk loading.
+    fn finish_bulk_load(&self) {}
+
     fn get(&self, key: &K) -> Option<V> {
         let key_bytes = bincode::serialize(key).expect("failed to serialize key");
 
@@ -42,4 +47,10 @@ where
 
         self.insert_raw(key_bytes, val_bytes);
     }
+
+    fn remove(&self, key: &K) {
+        let key_bytes = bincode::serialize(key).expect("failed to serialize key");
+
+        self.remove_raw(&key_bytes);
+    }
 }
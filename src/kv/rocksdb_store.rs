@@ -14,10 +14,15 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-use std::{fs, marker::PhantomData};
+use std::{
+    fs,
+    marker::PhantomData,
+    sync::atomic::{AtomicBool, Ordering},
+};
 
 use rocksdb::{
-    DBIteratorWithThreadMode, DBWithThreadMode, IteratorMode, Options, SingleThreaded, DB,
+    BlockBasedOptions, Cache, DBCompressionType, DBIteratorWithThreadMode, DBWithThreadMode,
+    IteratorMode, Options, SingleThreaded, WriteOptions, DB,
 };
 use serde::{de::DeserializeOwned, Serialize};
 
@@ -25,6 +30,62 @@ use crate::kv::Kv;
 
 pub struct RocksDbStore {}
 
+/// Options for the RocksDB stores. Settings that are `None` use the defaults of RocksDB.
+#[derive(Debug, Clone, Default)]
+pub struct RocksDbTuning {
+    /// Size in bytes of the cache for uncompressed blocks read from disk.
+    pub block_cache_size: Option<usize>,
+    /// Size in bytes of the memtable that is filled before it is written to disk.
+    pub write_buffer_size: Option<usize>,
+    pub compression: Option<DBCompressionType>,
+    /// Disables the write-ahead log and automatic compactions, which speeds up large imports.
+    /// Data that hasn't been flushed is lost if the process crashes. The normal settings are
+    /// restored, and the store is compacted, by `Kv::finish_bulk_load`.
+    pub bulk_load: bool,
+}
+
+impl RocksDbTuning {
+    fn options(&self) -> Options {
+        let mut options = Options::default();
+        options.create_if_missing(true);
+
+        if let Some(size) = self.block_cache_size {
+            let cache = Cache::new_lru_cache(size).expect("failed to create block cache");
+            let mut block_options = BlockBasedOptions::default();
+            block_options.set_block_cache(&cache);
+            options.set_block_based_table_factory(&block_options);
+        }
+
+        if let Some(size) = self.write_buffer_size {
+            options.set_write_buffer_size(size);
+        }
+
+        if let Some(compression) = self.compression {
+            options.set_compression_type(compression);
+        }
+
+        if self.bulk_load {
+            options.set_disable_auto_compactions(true);
+        }
+
+        options
+    }
+}
+
+/// Database opened with a `RocksDbTuning`.
+pub struct TunedDb {
+    db: DB,
+    bulk_load: AtomicBool,
+}
+
+impl TunedDb {
+    fn write_options(&self) -> WriteOptions {
+        let mut options = WriteOptions::default();
+        options.disable_wal(self.bulk_load.load(Ordering::SeqCst));
+        options
+    }
+}
+
 impl RocksDbStore {
     pub fn open<K, V, P>(path: P) -> Box<dyn Kv<K, V> + Send + Sync>
     where
@@ -54,6 +115,63 @@ impl RocksDbStore {
 
         Box::new(DB::open_for_read_only(&options, path, false).expect("unable to open rocks db"))
     }
+
+    pub fn open_tuned<K, V, P>(path: P, tuning: &RocksDbTuning) -> Box<dyn Kv<K, V> + Send + Sync>
+    where
+        P: AsRef<std::path::Path>,
+        K: Serialize + DeserializeOwned + 'static,
+        V: Serialize + DeserializeOwned + 'static,
+    {
+        if !path.as_ref().exists() {
+            fs::create_dir_all(path.as_ref()).expect("faild to create dir");
+        }
+
+        Box::new(TunedDb {
+            db: DB::open(&tuning.options(), path).expect("unable to open rocks db"),
+            bulk_load: AtomicBool::new(tuning.bulk_load),
+        })
+    }
+}
+
+impl<K, V> Kv<K, V> for TunedDb
+where
+    K: Serialize + DeserializeOwned + 'static,
+    V: Serialize + DeserializeOwned + 'static,
+{
+    fn get_raw(&self, key: &[u8]) -> Option<Vec<u8>> {
+        self.db.get(key).expect("failed to retrieve key")
+    }
+
+    fn insert_raw(&self, key: Vec<u8>, value: Vec<u8>) {
+        self.db
+            .put_opt(key, value, &self.write_options())
+            .expect("failed to insert value");
+    }
+
+    fn remove_raw(&self, key: &[u8]) {
+        self.db
+            .delete_opt(key, &self.write_options())
+            .expect("failed to remove key");
+    }
+
+    fn flush(&self) {
+        self.db.flush().expect("failed to flush");
+    }
+
+    fn iter<'a>(&'a self) -> Box<dyn Iterator<Item = (K, V)> + 'a> {
+        Kv::<K, V>::iter(&self.db)
+    }
+
+    fn finish_bulk_load(&self) {
+        if self.bulk_load.swap(false, Ordering::SeqCst) {
+            // the memtable isn't protected by the write-ahead log while bulk loading
+            self.db.flush().expect("failed to flush");
+            self.db
+                .set_options(&[("disable_auto_compactions", "false")])
+                .expect("failed to enable compactions");
+            self.db.compact_range(None::<&[u8]>, None::<&[u8]>);
+        }
+    }
 }
 
 impl<K, V> Kv<K, V> for rocksdb::DB
@@ -69,6 +187,10 @@ where
         self.put(key, value).expect("failed to insert value");
     }
 
+    fn remove_raw(&self, key: &[u8]) {
+        self.delete(key).expect("failed to remove key");
+    }
+
     fn flush(&self) {
         self.flush().expect("failed to flush");
     }
@@ -54,6 +54,8 @@ mod warc;
 mod webgraph;
 mod webpage;
 
+pub use webgraph::{Edge, NodeID};
+
 #[derive(Debug, Deserialize, Clone)]
 pub struct IndexingMasterConfig {
     limit_warc_files: Option<usize>,
@@ -185,6 +187,15 @@ pub enum Error {
 
     #[error("Unknown region")]
     UnknownRegion,
+
+    #[error("The webgraph is opened read-only")]
+    ReadOnlyWebgraph,
+
+    #[error("Unsupported version {0} of the node id mapping")]
+    UnsupportedIdMappingVersion(u32),
+
+    #[error("Failed to open frozen webgraph")]
+    FrozenWebgraph(#[from] crate::webgraph::FrozenError),
 }
 
 pub(crate) type Result<T> = std::result::Result<T, Error>;
@@ -0,0 +1,209 @@
+use std::{net::SocketAddr, sync::Arc};
+
+use rustls::ServerConfig;
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    sync::Notify,
+};
+use tokio_rustls::TlsAcceptor;
+
+use crate::mapreduce::manager::{BUF_SIZE, END_OF_MESSAGE};
+
+use super::{
+    encode_error_frame, encode_frame, tls::HANDSHAKE_TIMEOUT, worker::decode_request,
+    worker::State, Error, ErrorReply, Map, Result, Task, Worker,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use tracing::{debug, info, warn};
+
+/// Serves the jobs for `W` on a tokio runtime. Unlike `Worker::run`, connections are
+/// handled concurrently and the jobs are executed on the blocking thread pool, so a
+/// single worker process can use all cores when the manager allows several tasks per worker.
+pub struct AsyncWorker<W> {
+    worker: Arc<W>,
+}
+
+impl<W> AsyncWorker<W>
+where
+    W: Worker + Send + Sync + 'static,
+{
+    pub fn new(worker: W) -> Self {
+        Self {
+            worker: Arc::new(worker),
+        }
+    }
+
+    pub async fn run<I, O>(&self, addr: SocketAddr) -> Result<()>
+    where
+        I: Map<W, O> + 'static,
+        O: Serialize + DeserializeOwned + Send + 'static,
+    {
+        let listener = TcpListener::bind(addr).await?;
+        info!("worker listening on: {:}", addr);
+
+        self.serve::<I, O>(listener).await
+    }
+
+    /// Same as `run`, but the connections are encrypted with TLS, so the manager has to be
+    /// configured with `Manager::with_tls`. Each handshake runs on the task of its connection
+    /// and has to complete within `HANDSHAKE_TIMEOUT`.
+    pub async fn run_tls<I, O>(&self, addr: SocketAddr, config: Arc<ServerConfig>) -> Result<()>
+    where
+        I: Map<W, O> + 'static,
+        O: Serialize + DeserializeOwned + Send + 'static,
+    {
+        let listener = TcpListener::bind(addr).await?;
+        info!("worker listening on: {:}", addr);
+
+        self.serve_tls::<I, O>(listener, config).await
+    }
+
+    /// Handles the connections of `listener` until the manager signals that all jobs are finished.
+    pub async fn serve<I, O>(&self, listener: TcpListener) -> Result<()>
+    where
+        I: Map<W, O> + 'static,
+        O: Serialize + DeserializeOwned + Send + 'static,
+    {
+        self.serve_with::<I, O>(listener, None).await
+    }
+
+    /// Same as `serve`, but the connections are encrypted with TLS.
+    pub async fn serve_tls<I, O>(
+        &self,
+        listener: TcpListener,
+        config: Arc<ServerConfig>,
+    ) -> Result<()>
+    where
+        I: Map<W, O> + 'static,
+        O: Serialize + DeserializeOwned + Send + 'static,
+    {
+        self.serve_with::<I, O>(listener, Some(TlsAcceptor::from(config)))
+            .await
+    }
+
+    async fn serve_with<I, O>(&self, listener: TcpListener, tls: Option<TlsAcceptor>) -> Result<()>
+    where
+        I: Map<W, O> + 'static,
+        O: Serialize + DeserializeOwned + Send + 'static,
+    {
+        let shutdown = Arc::new(Notify::new());
+
+        loop {
+            tokio::select! {
+                res = listener.accept() => {
+                    let (socket, _) = res?;
+                    debug!("received connection");
+
+                    let worker = Arc::clone(&self.worker);
+                    let shutdown = Arc::clone(&shutdown);
+                    let tls = tls.clone();
+
+                    tokio::spawn(async move {
+                        match accept_connection::<W, I, O>(worker, socket, tls).await {
+                            Ok(State::Finished) => shutdown.notify_one(),
+                            Ok(State::Continue) => {}
+                            Err(err) => debug!("failed to handle connection: {:?}", err),
+                        }
+                    });
+                }
+                _ = shutdown.notified() => break,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Completes the TLS handshake of the connection if `tls` is set, and then handles it.
+async fn accept_connection<W, I, O>(
+    worker: Arc<W>,
+    socket: TcpStream,
+    tls: Option<TlsAcceptor>,
+) -> Result<State>
+where
+    W: Worker + Send + Sync + 'static,
+    I: Map<W, O> + 'static,
+    O: Serialize + DeserializeOwned + Send + 'static,
+{
+    match tls {
+        Some(acceptor) => {
+            let stream = tokio::time::timeout(HANDSHAKE_TIMEOUT, acceptor.accept(socket))
+                .await
+                .map_err(|_| Error::Tls("handshake timed out".to_string()))?
+                .map_err(|err| Error::Tls(err.to_string()))?;
+
+            handle_connection::<W, I, O, _>(worker, stream).await
+        }
+        None => handle_connection::<W, I, O, _>(worker, socket).await,
+    }
+}
+
+async fn handle_connection<W, I, O, S>(worker: Arc<W>, mut socket: S) -> Result<State>
+where
+    W: Worker + Send + Sync + 'static,
+    I: Map<W, O> + 'static,
+    O: Serialize + DeserializeOwned + Send + 'static,
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let mut buf = [0; BUF_SIZE];
+    let mut bytes = Vec::new();
+    while !bytes.ends_with(&END_OF_MESSAGE) {
+        let size = socket.read(&mut buf).await?;
+        debug!("read {:?} bytes", size);
+
+        if size == 0 {
+            return Err(Error::NoResponse);
+        }
+
+        bytes.extend_from_slice(&buf[..size]);
+    }
+
+    let (task, format, compression) =
+        match decode_request::<I>(&bytes[..bytes.len() - END_OF_MESSAGE.len()]) {
+            Ok(request) => request,
+            Err(Error::ProtocolMismatch { manager, worker }) => {
+                warn!(
+                    "rejected request from manager with protocol version {} (worker uses {})",
+                    manager, worker
+                );
+                let reply = ErrorReply::ProtocolMismatch { manager, worker };
+                socket.write_all(&encode_error_frame(&reply)?).await?;
+                socket.write_all(&END_OF_MESSAGE).await?;
+
+                return Ok(State::Continue);
+            }
+            Err(err) => return Err(err),
+        };
+
+    match task {
+        Task::Job(job) => {
+            debug!("received job");
+            let res = match tokio::task::spawn_blocking(move || job.map(&worker)).await {
+                Ok(res) => res,
+                Err(err) => {
+                    warn!("job failed: {}", err);
+                    let reply = ErrorReply::JobFailed(err.to_string());
+                    socket.write_all(&encode_error_frame(&reply)?).await?;
+                    socket.write_all(&END_OF_MESSAGE).await?;
+
+                    return Ok(State::Continue);
+                }
+            };
+            let bytes = encode_frame(format.serialize(&res)?, compression)?;
+            debug!("serialized result into {} bytes", bytes.len());
+            socket.write_all(&bytes).await?;
+            socket.write_all(&END_OF_MESSAGE).await?;
+        }
+        Task::AllFinished => {
+            debug!("shutting down");
+            return Ok(State::Finished);
+        }
+        Task::Ping => {
+            debug!("received ping");
+            socket.write_all(&END_OF_MESSAGE).await?;
+        }
+    }
+
+    Ok(State::Continue)
+}
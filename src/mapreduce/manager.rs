@@ -1,40 +1,142 @@
-use super::{Error, Result, Worker};
+use super::tls::{self, ClientStream};
+use super::{decode_frame, encode_frame, Error, Result, SerFormat, Worker};
 use super::{Map, Reduce};
 use crate::exponential_backoff::ExponentialBackoff;
-use crate::mapreduce::Task;
-use indicatif::{ParallelProgressIterator, ProgressBar, ProgressStyle};
+use crate::mapreduce::{Task, PROTOCOL_VERSION};
+use indicatif::{ProgressBar, ProgressStyle};
 use rayon::iter::ParallelBridge;
 use rayon::prelude::*;
+use rustls::ClientConfig;
 use serde::de::DeserializeOwned;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use std::any::Any;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fs;
 use std::io::{Read, Write};
 use std::net::ToSocketAddrs;
 use std::net::{SocketAddr, TcpStream};
 use std::ops::Deref;
-use std::sync::atomic::{AtomicU32, Ordering};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver};
 use std::sync::{Arc, Mutex};
-use std::time::Duration;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
 use tracing::{debug, warn};
 
-#[derive(Debug)]
 struct RemoteWorker {
     addr: SocketAddr,
+    /// Encrypts the connections to the worker, see `Manager::with_tls`.
+    tls: Option<Arc<ClientConfig>>,
+}
+
+impl std::fmt::Debug for RemoteWorker {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RemoteWorker")
+            .field("addr", &self.addr)
+            .field("tls", &self.tls.is_some())
+            .finish()
+    }
+}
+
+/// Connection to a worker, which is encrypted if the worker has a TLS config.
+enum Connection {
+    Plain(TcpStream),
+    Tls(Box<ClientStream>),
+}
+
+impl Connection {
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> std::io::Result<()> {
+        match self {
+            Connection::Plain(stream) => stream.set_read_timeout(timeout),
+            Connection::Tls(stream) => stream.sock.set_read_timeout(timeout),
+        }
+    }
+}
+
+impl Read for Connection {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            Connection::Plain(stream) => stream.read(buf),
+            Connection::Tls(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for Connection {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Connection::Plain(stream) => stream.write(buf),
+            Connection::Tls(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Connection::Plain(stream) => stream.flush(),
+            Connection::Tls(stream) => stream.flush(),
+        }
+    }
 }
 pub const BUF_SIZE: usize = 4096;
 
 pub const END_OF_MESSAGE: [u8; BUF_SIZE] = [42; BUF_SIZE];
 
+const PING_TIMEOUT: Duration = Duration::from_secs(1);
+const HEALTH_PROBE_INTERVAL: Duration = Duration::from_secs(10);
+const DEFAULT_CHECKPOINT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// A task is duplicated once it has run this many times longer than the median task.
+const SPECULATION_FACTOR: u32 = 3;
+/// Tasks are never duplicated before they have run for this long.
+const MIN_SPECULATION_DELAY: Duration = Duration::from_secs(1);
+/// Number of finished tasks needed before the median task duration is trusted.
+const SPECULATION_MIN_SAMPLES: usize = 5;
+/// Number of recent task durations the median is computed from.
+const SPECULATION_WINDOW: usize = 1_000;
+const SPECULATION_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Serializes the task into the message that is sent to a worker.
+fn encode_task<T: Serialize>(
+    task: &Task<T>,
+    format: SerFormat,
+    compression: Option<u32>,
+) -> Result<Vec<u8>> {
+    let frame = encode_frame(format.serialize(task)?, compression)?;
+
+    let mut bytes = Vec::with_capacity(5 + frame.len() + END_OF_MESSAGE.len());
+    bytes.extend_from_slice(&PROTOCOL_VERSION.to_le_bytes());
+    bytes.push(format.tag());
+    bytes.extend_from_slice(&frame);
+    bytes.extend_from_slice(&END_OF_MESSAGE);
+
+    Ok(bytes)
+}
+
+fn write_task<T: Serialize, S: Write>(
+    stream: &mut S,
+    task: &Task<T>,
+    format: SerFormat,
+    compression: Option<u32>,
+) -> Result<()> {
+    let bytes = encode_task(task, format, compression)?;
+    debug!("sending {:?} bytes", bytes.len());
+    stream.write_all(&bytes)?;
+
+    Ok(())
+}
+
 impl RemoteWorker {
     fn retry_strategy() -> impl Iterator<Item = Duration> {
         ExponentialBackoff::from_millis(10).take(5)
     }
 
-    fn connect(&self) -> Result<TcpStream> {
+    fn connect(&self) -> Result<Connection> {
         debug!("connecting to {:}", self.addr);
         for dur in RemoteWorker::retry_strategy() {
             if let Ok(stream) = TcpStream::connect(&self.addr) {
                 debug!("connected");
-                return Ok(stream);
+                return self.secure(stream);
             }
 
             std::thread::sleep(dur);
@@ -43,42 +145,151 @@ impl RemoteWorker {
         Err(Error::NoResponse)
     }
 
-    fn perform<W, I, O>(&self, job: &I) -> Result<O>
+    /// Performs the TLS handshake if the worker has a TLS config.
+    fn secure(&self, stream: TcpStream) -> Result<Connection> {
+        match &self.tls {
+            Some(config) => Ok(Connection::Tls(Box::new(tls::connect(
+                stream,
+                Arc::clone(config),
+            )?))),
+            None => Ok(Connection::Plain(stream)),
+        }
+    }
+
+    fn try_ping(&self, format: SerFormat) -> Result<()> {
+        let stream = TcpStream::connect_timeout(&self.addr, PING_TIMEOUT)?;
+        stream.set_read_timeout(Some(PING_TIMEOUT))?;
+        let mut stream = self.secure(stream)?;
+        write_task(&mut stream, &Task::<()>::Ping, format, None)?;
+
+        let mut buf = [0; BUF_SIZE];
+        let mut bytes = Vec::new();
+        while !bytes.ends_with(&END_OF_MESSAGE) {
+            let size = stream.read(&mut buf)?;
+            if size == 0 {
+                return Err(Error::NoResponse);
+            }
+            bytes.extend_from_slice(&buf[..size]);
+        }
+
+        // the worker responds with an error frame if it can't handle our requests
+        let reply = &bytes[..bytes.len() - END_OF_MESSAGE.len()];
+        if !reply.is_empty() {
+            decode_frame(reply)?;
+        }
+
+        Ok(())
+    }
+
+    /// Checks that the worker is alive and able to respond to a tiny message.
+    fn ping(&self, format: SerFormat) -> bool {
+        match self.try_ping(format) {
+            Ok(()) => true,
+            Err(err) => {
+                debug!("worker {:} did not respond to ping: {:?}", self.addr, err);
+                false
+            }
+        }
+    }
+
+    /// Sends the job to the worker and waits for the result. If `timeout` is set,
+    /// the entire response must be received within `timeout`.
+    fn perform<W, I, O>(
+        &self,
+        job: &I,
+        timeout: Option<Duration>,
+        format: SerFormat,
+        compression: Option<u32>,
+    ) -> Result<O>
     where
         W: Worker,
         I: Map<W, O> + Send,
         O: Serialize + DeserializeOwned + Send,
+    {
+        let message = encode_task(&Task::Job(job), format, compression)?;
+        self.perform_encoded(&message, timeout, format, None)
+    }
+
+    /// Same as `perform`, but for a job that has already been encoded by `encode_task`.
+    /// If `abandoned` is set while waiting for the result, `Error::Cancelled` is returned.
+    fn perform_encoded<O>(
+        &self,
+        message: &[u8],
+        timeout: Option<Duration>,
+        format: SerFormat,
+        abandoned: Option<&AtomicBool>,
+    ) -> Result<O>
+    where
+        O: DeserializeOwned,
     {
         let mut stream = self.connect()?;
-        let serialized_job = bincode::serialize(&Task::Job(job))?;
-        debug!("sending {:?} bytes", serialized_job.len());
-        stream.write_all(&serialized_job)?;
-        stream.write_all(&END_OF_MESSAGE)?;
+        debug!("sending {:?} bytes", message.len());
+        stream.write_all(message)?;
+
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
 
         let mut buf = [0; BUF_SIZE];
         let mut bytes = Vec::new();
         loop {
-            if let Ok(size) = stream.read(&mut buf) {
-                debug!("read {:?} bytes", size);
-                if size == 0 && bytes.is_empty() {
-                    return Err(Error::NoResponse);
+            let remaining =
+                deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()));
+
+            if remaining == Some(Duration::ZERO) {
+                return Err(Error::TaskTimeout);
+            }
+
+            if let Some(abandoned) = abandoned {
+                if abandoned.load(Ordering::SeqCst) {
+                    return Err(Error::Cancelled);
                 }
-                bytes.extend_from_slice(&buf[..size]);
+            }
 
-                if bytes.len() >= END_OF_MESSAGE.len()
-                    && bytes[bytes.len() - END_OF_MESSAGE.len()..] == END_OF_MESSAGE
+            let read_timeout = [remaining, abandoned.map(|_| SPECULATION_POLL_INTERVAL)]
+                .into_iter()
+                .flatten()
+                .min();
+            if read_timeout.is_some() {
+                stream.set_read_timeout(read_timeout)?;
+            }
+
+            match stream.read(&mut buf) {
+                Ok(size) => {
+                    debug!("read {:?} bytes", size);
+                    // the worker closed the connection before the response was complete
+                    if size == 0 {
+                        return Err(Error::NoResponse);
+                    }
+                    bytes.extend_from_slice(&buf[..size]);
+
+                    if bytes.len() >= END_OF_MESSAGE.len()
+                        && bytes[bytes.len() - END_OF_MESSAGE.len()..] == END_OF_MESSAGE
+                    {
+                        break;
+                    }
+                }
+                Err(err)
+                    if matches!(
+                        err.kind(),
+                        std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+                    ) =>
                 {
-                    break;
+                    // when polling for `abandoned`, the deadline is checked at the top of the loop
+                    if abandoned.is_none() {
+                        return Err(Error::TaskTimeout);
+                    }
                 }
+                Err(err) if err.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(err) => return Err(err.into()),
             }
         }
         bytes = bytes[..bytes.len() - END_OF_MESSAGE.len()].to_vec();
         debug!("finished reading {:?} bytes", bytes.len());
 
-        Ok(bincode::deserialize(&bytes)?)
+        let (payload, _) = decode_frame(&bytes)?;
+        format.deserialize(&payload)
     }
 
-    fn stop<W, I, O>(&self) -> Result<()>
+    fn stop<W, I, O>(&self, format: SerFormat) -> Result<()>
     where
         W: Worker,
         I: Map<W, O> + Send,
@@ -86,17 +297,14 @@ impl RemoteWorker {
     {
         debug!("closing worker {:}", self.addr);
         let mut stream = self.connect()?;
-        let serialized_job = bincode::serialize(&Task::<I>::AllFinished)?;
-        debug!("sending {:?} bytes", serialized_job.len());
-        stream.write_all(&serialized_job)?;
-        stream.write_all(&END_OF_MESSAGE)?;
-        Ok(())
+        write_task(&mut stream, &Task::<I>::AllFinished, format, None)
     }
 }
 
 struct WorkerGuard<'a> {
     from_pool: &'a WorkerPool,
     worker: Arc<RemoteWorker>,
+    succeeded: bool,
 }
 
 impl<'a> WorkerGuard<'a> {
@@ -104,11 +312,12 @@ impl<'a> WorkerGuard<'a> {
         Self {
             worker,
             from_pool: pool,
+            succeeded: false,
         }
     }
 
-    fn success(self) {
-        self.from_pool.insert(Arc::clone(&self.worker));
+    fn success(mut self) {
+        self.succeeded = true;
     }
 }
 
@@ -122,14 +331,24 @@ impl<'a> Deref for WorkerGuard<'a> {
 
 impl<'a> Drop for WorkerGuard<'a> {
     fn drop(&mut self) {
+        self.from_pool.finish(&self.worker, self.succeeded);
         self.from_pool.put_back();
     }
 }
 
 struct WorkerPool {
     all_workers: Vec<Arc<RemoteWorker>>,
-    ready_workers: Mutex<Vec<Arc<RemoteWorker>>>,
+    /// Workers that are believed to be alive.
+    live_workers: Mutex<Vec<Arc<RemoteWorker>>>,
+    /// Number of in-flight tasks of each worker. Kept while a worker is dead, as its other
+    /// tasks may still be running. Always locked after `live_workers`.
+    in_flight: Mutex<HashMap<SocketAddr, usize>>,
+    /// Workers that failed, together with the last time they were probed.
+    dead_workers: Mutex<Vec<(Arc<RemoteWorker>, Instant)>>,
     running_workers: AtomicU32,
+    tasks_per_worker: usize,
+    format: SerFormat,
+    compression: Option<u32>,
 }
 
 impl WorkerPool {
@@ -144,35 +363,187 @@ impl WorkerPool {
                     panic!("failed to transform {:?} into a socket address", addr)
                 })
             })
-            .map(|addr| Arc::new(RemoteWorker { addr }))
+            .map(|addr| Arc::new(RemoteWorker { addr, tls: None }))
             .collect();
 
         Self {
-            ready_workers: Mutex::new(all_workers.clone()),
+            live_workers: Mutex::new(all_workers.clone()),
+            in_flight: Mutex::new(all_workers.iter().map(|worker| (worker.addr, 0)).collect()),
+            dead_workers: Mutex::new(Vec::new()),
             all_workers,
             running_workers: AtomicU32::new(0),
+            tasks_per_worker: 1,
+            format: SerFormat::default(),
+            compression: None,
         }
     }
 
+    /// Encrypts the connections to all workers. Only used while the manager is configured,
+    /// so every worker is still live and idle.
+    fn set_tls(&mut self, config: Arc<ClientConfig>) {
+        self.all_workers = self
+            .all_workers
+            .iter()
+            .map(|worker| {
+                Arc::new(RemoteWorker {
+                    addr: worker.addr,
+                    tls: Some(Arc::clone(&config)),
+                })
+            })
+            .collect();
+
+        *self.live_workers.get_mut().unwrap() = self.all_workers.clone();
+    }
+
     fn put_back(&self) {
         self.running_workers.fetch_sub(1, Ordering::SeqCst);
     }
 
     fn insert(&self, worker: Arc<RemoteWorker>) {
-        self.ready_workers.lock().unwrap().push(worker);
+        self.live_workers.lock().unwrap().push(worker);
     }
 
-    fn get_worker(&self) -> Result<Option<WorkerGuard<'_>>> {
-        let mut ready_workers = self.ready_workers.lock().unwrap();
-        if ready_workers.len() as u32 + self.running_workers.load(Ordering::SeqCst) == 0 {
-            return Err(Error::NoAvailableWorker);
+    fn mark_dead(&self, worker: Arc<RemoteWorker>) {
+        self.dead_workers
+            .lock()
+            .unwrap()
+            .push((worker, Instant::now()));
+    }
+
+    /// Releases a task slot of the worker. If the task failed, the worker is moved
+    /// to the dead workers (unless another of its tasks already failed).
+    fn finish(&self, worker: &Arc<RemoteWorker>, succeeded: bool) {
+        *self
+            .in_flight
+            .lock()
+            .unwrap()
+            .get_mut(&worker.addr)
+            .unwrap() -= 1;
+
+        if succeeded {
+            return;
         }
 
-        if let Some(worker) = ready_workers.pop() {
-            self.running_workers.fetch_add(1, Ordering::SeqCst);
-            Ok(Some(WorkerGuard::new(self, worker)))
-        } else {
-            Ok(None)
+        let mut live_workers = self.live_workers.lock().unwrap();
+
+        if let Some(pos) = live_workers
+            .iter()
+            .position(|live_worker| Arc::ptr_eq(live_worker, worker))
+        {
+            live_workers.remove(pos);
+            drop(live_workers);
+            self.mark_dead(Arc::clone(worker));
+        }
+    }
+
+    /// Moves the dead workers that respond to a ping back into the pool.
+    /// Each dead worker is probed at most once every `HEALTH_PROBE_INTERVAL`.
+    fn probe_dead_workers(&self) {
+        let due: Vec<_> = {
+            let mut dead_workers = self.dead_workers.lock().unwrap();
+            let now = Instant::now();
+
+            let (due, waiting): (Vec<_>, Vec<_>) = dead_workers
+                .drain(..)
+                .partition(|(_, last_probe)| now - *last_probe >= HEALTH_PROBE_INTERVAL);
+            *dead_workers = waiting;
+
+            // the workers being probed count as running, so the pool isn't considered empty
+            self.running_workers
+                .fetch_add(due.len() as u32, Ordering::SeqCst);
+
+            due
+        };
+
+        for (worker, _) in due {
+            if worker.ping(self.format) {
+                debug!("worker {:} is alive again", worker.addr);
+                self.insert(worker);
+            } else {
+                self.mark_dead(worker);
+            }
+
+            self.put_back();
+        }
+    }
+
+    /// Returns a live worker other than `exclude` without any in-flight tasks,
+    /// as long as it responds to a ping.
+    fn get_idle_worker(&self, exclude: &Arc<RemoteWorker>) -> Option<WorkerGuard<'_>> {
+        loop {
+            let worker = {
+                let live_workers = self.live_workers.lock().unwrap();
+                let mut in_flight = self.in_flight.lock().unwrap();
+                let worker = live_workers
+                    .iter()
+                    .find(|worker| in_flight[&worker.addr] == 0 && !Arc::ptr_eq(worker, exclude))?;
+
+                *in_flight.get_mut(&worker.addr).unwrap() += 1;
+                self.running_workers.fetch_add(1, Ordering::SeqCst);
+                Arc::clone(worker)
+            };
+
+            let guard = WorkerGuard::new(self, worker);
+
+            if guard.ping(self.format) {
+                return Some(guard);
+            }
+        }
+    }
+
+    /// Returns the live worker with the fewest in-flight tasks, as long as it has
+    /// fewer than `tasks_per_worker` tasks and responds to a ping. Workers that
+    /// don't respond are marked as dead and skipped until they respond to a later probe.
+    ///
+    /// If `preferred` is set, the worker at that index (modulo the number of workers) is
+    /// returned instead, unless it is dead.
+    fn get_worker(&self, preferred: Option<usize>) -> Result<Option<WorkerGuard<'_>>> {
+        self.probe_dead_workers();
+
+        loop {
+            let worker = {
+                let live_workers = self.live_workers.lock().unwrap();
+                if live_workers.len() as u32 + self.running_workers.load(Ordering::SeqCst) == 0 {
+                    return Err(Error::NoAvailableWorker);
+                }
+                let mut in_flight = self.in_flight.lock().unwrap();
+
+                let preferred = preferred.and_then(|idx| {
+                    let worker = &self.all_workers[idx % self.all_workers.len()];
+                    live_workers
+                        .iter()
+                        .position(|live_worker| Arc::ptr_eq(live_worker, worker))
+                });
+
+                let pos = match preferred {
+                    Some(pos) if in_flight[&live_workers[pos].addr] < self.tasks_per_worker => {
+                        Some(pos)
+                    }
+                    Some(_) => None,
+                    None => live_workers
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, worker)| in_flight[&worker.addr] < self.tasks_per_worker)
+                        .min_by_key(|(_, worker)| in_flight[&worker.addr])
+                        .map(|(pos, _)| pos),
+                };
+
+                match pos {
+                    Some(pos) => {
+                        let worker = &live_workers[pos];
+                        *in_flight.get_mut(&worker.addr).unwrap() += 1;
+                        self.running_workers.fetch_add(1, Ordering::SeqCst);
+                        Arc::clone(worker)
+                    }
+                    None => return Ok(None),
+                }
+            };
+
+            let guard = WorkerGuard::new(self, worker);
+
+            if guard.ping(self.format) {
+                return Ok(Some(guard));
+            }
         }
     }
 
@@ -184,7 +555,7 @@ impl WorkerPool {
     {
         let mut failing_workers = Vec::new();
         for worker in &self.all_workers {
-            if worker.stop::<W, I, O>().is_err() {
+            if !worker.ping(self.format) || worker.stop::<W, I, O>(self.format).is_err() {
                 failing_workers.push(worker);
             }
         }
@@ -198,8 +569,169 @@ impl WorkerPool {
     }
 }
 
+/// Statistics collected while running a job.
+#[derive(Debug, Default, Clone)]
+pub struct JobStats {
+    pub tasks_completed: usize,
+    /// Number of tasks that could not be executed, even after retrying.
+    pub tasks_failed: usize,
+    pub retries: usize,
+    pub per_worker_task_counts: HashMap<SocketAddr, usize>,
+    /// Time each worker spent on tasks, including sending the job and receiving the result.
+    pub per_worker_busy_time: HashMap<SocketAddr, Duration>,
+    pub wall_time: Duration,
+    /// Indices of the inputs that were dropped under `FailPolicy::BestEffort`.
+    pub dropped_tasks: Vec<usize>,
+    /// Number of tasks that were duplicated by `Manager::with_speculation`.
+    pub speculative_tasks: usize,
+}
+
+/// Decides what happens when a task still fails after it has been retried.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FailPolicy {
+    /// The entire job fails.
+    #[default]
+    AllOrNothing,
+    /// The failed task is dropped and the result is reduced over the remaining tasks.
+    /// The job fails if more than `max_failures` tasks are dropped.
+    BestEffort { max_failures: usize },
+}
+
+/// Progress of a job run by `Manager::run_resumable`.
+#[derive(Serialize, Deserialize)]
+struct Checkpoint<O> {
+    /// Indices of the inputs whose results are reduced into `acc`.
+    completed: HashSet<usize>,
+    acc: Option<O>,
+}
+
+impl<O> Default for Checkpoint<O> {
+    fn default() -> Self {
+        Self {
+            completed: HashSet::new(),
+            acc: None,
+        }
+    }
+}
+
+impl<O> Checkpoint<O>
+where
+    O: Serialize + DeserializeOwned,
+{
+    fn load(path: &Path) -> Result<Option<Self>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        Ok(Some(bincode::deserialize(&fs::read(path)?)?))
+    }
+
+    /// Writes the checkpoint to a temporary file first, so a crash while saving
+    /// doesn't corrupt the previous checkpoint.
+    fn save(&self, path: &Path) -> Result<()> {
+        let tmp_path = path.with_extension("tmp");
+        fs::write(&tmp_path, bincode::serialize(self)?)?;
+        fs::rename(tmp_path, path)?;
+
+        Ok(())
+    }
+}
+
+/// Called with the number of finished tasks and the total number of tasks, if known.
+type ProgressCallback = Box<dyn Fn(u64, Option<u64>) + Send + Sync>;
+
+/// Reports finished tasks to the progress bar and the progress callback of a manager.
+struct Progress<'a> {
+    bar: Option<ProgressBar>,
+    callback: Option<&'a ProgressCallback>,
+    finished: AtomicU64,
+    total: Option<u64>,
+}
+
+impl<'a> Progress<'a> {
+    /// `finished` is the number of tasks that were finished before the job was started.
+    fn new(manager: &'a Manager, total: Option<u64>, finished: u64) -> Self {
+        let bar = if manager.show_progress {
+            Some(Progress::bar(total, finished))
+        } else {
+            None
+        };
+
+        Self {
+            bar,
+            callback: manager.progress_callback.as_ref(),
+            finished: AtomicU64::new(finished),
+            total,
+        }
+    }
+
+    /// A progress bar if the total number of tasks is known, and a spinner otherwise.
+    fn bar(total: Option<u64>, finished: u64) -> ProgressBar {
+        let pb = match total {
+            Some(total) => {
+                let pb = ProgressBar::new(total);
+                pb.set_style(
+                    ProgressStyle::default_bar()
+                        .template(
+                            "{spinner:.green} [{elapsed_precise}] [{wide_bar}] {pos:>7}/{len:7} ({eta})",
+                        )
+                        .progress_chars("#>-"),
+                );
+                pb
+            }
+            None => {
+                let pb = ProgressBar::new_spinner();
+                pb.set_style(
+                    ProgressStyle::default_spinner()
+                        .template("{spinner:.green} [{elapsed_precise}] {pos} tasks finished"),
+                );
+                pb.enable_steady_tick(100);
+                pb
+            }
+        };
+        pb.set_position(finished);
+
+        pb
+    }
+
+    fn inc(&self) {
+        let finished = self.finished.fetch_add(1, Ordering::SeqCst) + 1;
+
+        if let Some(bar) = &self.bar {
+            bar.inc(1);
+        }
+
+        if let Some(callback) = self.callback {
+            callback(finished, self.total);
+        }
+    }
+
+    fn finish(&self) {
+        if let Some(bar) = &self.bar {
+            bar.finish();
+        }
+    }
+}
+
+/// Maps a job to the index of the worker it should be sent to. Returns `None` for
+/// jobs of another type than the partitioner was created for.
+type Partitioner = Box<dyn Fn(&dyn Any) -> Option<usize> + Send + Sync>;
+
 pub struct Manager {
     pool: WorkerPool,
+    partitioner: Option<Partitioner>,
+    max_retries: Option<usize>,
+    task_timeout: Option<Duration>,
+    cancel: Option<Arc<AtomicBool>>,
+    fail_policy: FailPolicy,
+    stats: Mutex<JobStats>,
+    checkpoint: Option<PathBuf>,
+    checkpoint_interval: Duration,
+    speculation: bool,
+    /// Durations of the most recently completed tasks.
+    task_durations: Mutex<VecDeque<Duration>>,
+    show_progress: bool,
+    progress_callback: Option<ProgressCallback>,
 }
 
 impl Manager {
@@ -209,19 +741,181 @@ impl Manager {
     {
         Self {
             pool: WorkerPool::new(workers),
+            partitioner: None,
+            max_retries: None,
+            task_timeout: None,
+            cancel: None,
+            fail_policy: FailPolicy::default(),
+            stats: Mutex::new(JobStats::default()),
+            checkpoint: None,
+            checkpoint_interval: DEFAULT_CHECKPOINT_INTERVAL,
+            speculation: false,
+            task_durations: Mutex::new(VecDeque::new()),
+            show_progress: true,
+            progress_callback: None,
         }
     }
 
-    fn try_map<W, I, O>(&self, job: &I) -> Result<O>
+    /// Gives up on a job once it has been retried `max_retries` times.
+    /// Every retry is sent to a different worker, as failing workers are taken out
+    /// of the pool until they respond to a health probe. By default, jobs are
+    /// retried until no workers are left.
+    pub fn with_retries(mut self, max_retries: usize) -> Self {
+        self.max_retries = Some(max_retries);
+        self
+    }
+
+    /// Fails the job with `Error::TaskTimeout` if the worker hasn't responded within `timeout`.
+    /// The worker is then taken out of the pool and the job is rescheduled like any other failure.
+    pub fn with_task_timeout(mut self, timeout: Duration) -> Self {
+        self.task_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets the format used to serialize jobs and results. Defaults to bincode.
+    pub fn with_format(mut self, format: SerFormat) -> Self {
+        self.pool.format = format;
+        self
+    }
+
+    /// Encrypts the connections to the workers with TLS. The workers have to be started
+    /// with `Worker::run_tls` or `AsyncWorker::run_tls`, using a certificate that is valid
+    /// for their ip address. Handshake failures count as failures of the worker, see `Error::Tls`.
+    pub fn with_tls(mut self, config: Arc<ClientConfig>) -> Self {
+        self.pool.set_tls(config);
+        self
+    }
+
+    /// Compresses jobs and results with the given zlib compression level (0-9).
+    /// The workers respond with the same compression level. Whether it pays off depends
+    /// on the payloads and the network. For 2.5 MB of term counts per url, the payload of
+    /// the `mapreduce_compression` benchmark, the default level 6 shrinks the payload 8.7
+    /// times, compresses at about 100 MB/s and decompresses at about 500 MB/s. That's
+    /// 45 MB/s of payload instead of 12.5 MB/s on a 100 Mbit/s network, but 75 MB/s
+    /// instead of 125 MB/s on a 1 Gbit/s network.
+    pub fn with_compression(mut self, level: u32) -> Self {
+        self.pool.compression = Some(level);
+        self
+    }
+
+    /// Allows up to `tasks_per_worker` jobs to be in flight on each worker at the same time.
+    /// New jobs are sent to the worker with the fewest jobs in flight. Defaults to 1.
+    pub fn with_tasks_per_worker(mut self, tasks_per_worker: usize) -> Self {
+        self.pool.tasks_per_worker = tasks_per_worker.max(1);
+        self
+    }
+
+    /// Stops the job once `cancel` is set. No new jobs are dispatched after that,
+    /// the jobs already sent to the workers are allowed to finish, and `run` returns `None`.
+    pub fn with_cancel_token(mut self, cancel: Arc<AtomicBool>) -> Self {
+        self.cancel = Some(cancel);
+        self
+    }
+
+    /// Sets what happens to `run` when a task fails permanently.
+    /// Defaults to `FailPolicy::AllOrNothing`.
+    pub fn with_fail_tolerance(mut self, fail_policy: FailPolicy) -> Self {
+        self.fail_policy = fail_policy;
+        self
+    }
+
+    /// Stores the progress of `run_resumable` in `path`, so the job can be resumed after
+    /// a crash by running it again with the same inputs and checkpoint. If a checkpoint
+    /// already exists at `path`, the inputs it contains are skipped.
+    pub fn resume_from<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.checkpoint = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Sets how often the checkpoint is written. Defaults to once a minute.
+    pub fn with_checkpoint_interval(mut self, interval: Duration) -> Self {
+        self.checkpoint_interval = interval;
+        self
+    }
+
+    /// Sends each job to the worker at index `partitioner(job) % workers.len()` in the list
+    /// the manager was created with, so the same input always ends up on the same worker.
+    /// The job waits for the worker if it is busy, and is only scheduled dynamically
+    /// if the worker is dead.
+    pub fn with_partitioner<I, F>(mut self, partitioner: F) -> Self
+    where
+        I: 'static,
+        F: Fn(&I) -> usize + Send + Sync + 'static,
+    {
+        self.partitioner = Some(Box::new(move |job| {
+            job.downcast_ref::<I>().map(&partitioner)
+        }));
+        self
+    }
+
+    /// Sends a duplicate of a task to an idle worker if the task has been running
+    /// `SPECULATION_FACTOR` times longer than the median task. The first result is used,
+    /// and the other attempt is abandoned. This uses extra capacity, so it is disabled by default.
+    pub fn with_speculation(mut self, speculation: bool) -> Self {
+        self.speculation = speculation;
+        self
+    }
+
+    /// Shows a progress bar on stderr while the jobs are running, or a spinner if the
+    /// number of jobs isn't known up front. Enabled by default.
+    pub fn with_progress(mut self, show_progress: bool) -> Self {
+        self.show_progress = show_progress;
+        self
+    }
+
+    /// Calls `callback` with the number of finished tasks and the total number of tasks
+    /// (if the input iterator has an upper bound) every time a task finishes. Tasks that
+    /// are dropped by the `FailPolicy` count as finished.
+    pub fn with_progress_callback<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(u64, Option<u64>) + Send + Sync + 'static,
+    {
+        self.progress_callback = Some(Box::new(callback));
+        self
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancel
+            .as_ref()
+            .map(|cancel| cancel.load(Ordering::SeqCst))
+            .unwrap_or(false)
+    }
+
+    fn map_once<W, I, O>(&self, job: &I) -> Result<O>
     where
         W: Worker,
-        I: Map<W, O> + Send,
+        I: Map<W, O> + Send + 'static,
         O: Serialize + DeserializeOwned + Send,
     {
+        let preferred = self
+            .partitioner
+            .as_ref()
+            .and_then(|partitioner| partitioner(job));
+
         loop {
-            match self.pool.get_worker()? {
+            if self.is_cancelled() {
+                return Err(Error::Cancelled);
+            }
+
+            let speculation_delay = self.speculation_delay();
+
+            match self.pool.get_worker(preferred)? {
                 Some(worker) => {
-                    let res = worker.perform(job)?;
+                    if let Some(delay) = speculation_delay {
+                        let message =
+                            encode_task(&Task::Job(job), self.pool.format, self.pool.compression)?;
+
+                        return self.perform_speculatively(&message, worker, delay);
+                    }
+
+                    let start = Instant::now();
+                    let res = worker.perform(
+                        job,
+                        self.task_timeout,
+                        self.pool.format,
+                        self.pool.compression,
+                    )?;
+                    self.record_completed(worker.addr, start.elapsed());
                     worker.success();
 
                     return Ok(res);
@@ -231,98 +925,298 @@ impl Manager {
         }
     }
 
-    /// Execute job on one of the remote machines. If the remote machine fails for some reason,
-    /// the job should be allocated to another machine.
-    pub fn map<W, I, O>(&self, job: I) -> O
+    /// How long a task may run before it is duplicated, or `None` if speculation is disabled
+    /// or too few tasks have finished to tell whether a task is slow.
+    fn speculation_delay(&self) -> Option<Duration> {
+        if !self.speculation {
+            return None;
+        }
+
+        let mut durations: Vec<_> = self
+            .task_durations
+            .lock()
+            .unwrap()
+            .iter()
+            .copied()
+            .collect();
+        if durations.len() < SPECULATION_MIN_SAMPLES {
+            return None;
+        }
+
+        durations.sort_unstable();
+        let median = durations[durations.len() / 2];
+
+        Some((median * SPECULATION_FACTOR).max(MIN_SPECULATION_DELAY))
+    }
+
+    /// Performs the encoded job on `primary`, and on an idle worker as well if no result
+    /// has arrived within `delay`. Returns the first result.
+    fn perform_speculatively<O>(
+        &self,
+        message: &[u8],
+        primary: WorkerGuard<'_>,
+        delay: Duration,
+    ) -> Result<O>
     where
-        W: Worker,
-        I: Map<W, O> + Send,
-        O: Serialize + DeserializeOwned + Send,
+        O: DeserializeOwned + Send,
     {
-        loop {
-            match self.try_map(&job) {
-                Ok(res) => return res,
-                Err(Error::NoAvailableWorker) => panic!("{}", Error::NoAvailableWorker),
-                Err(err) => {
-                    warn!("Worker failed - rescheduling job");
-                    debug!("{:?}", err);
+        let speculate_at = Instant::now() + delay;
+        let abandoned = AtomicBool::new(false);
+        let (sender, receiver) = mpsc::channel();
+
+        std::thread::scope(|s| {
+            let primary_worker = Arc::clone(&*primary);
+            let spawn_attempt = |worker| {
+                let sender = sender.clone();
+                let abandoned = &abandoned;
+
+                s.spawn(move || {
+                    let _ = sender.send(self.attempt::<O>(worker, message, abandoned));
+                });
+            };
+
+            spawn_attempt(primary);
+            let mut running = 1;
+            let mut speculated = false;
+
+            loop {
+                let timeout = if speculated {
+                    None
+                } else {
+                    Some(
+                        speculate_at
+                            .saturating_duration_since(Instant::now())
+                            .max(SPECULATION_POLL_INTERVAL),
+                    )
+                };
+
+                let res = match timeout {
+                    Some(timeout) => receiver.recv_timeout(timeout),
+                    None => receiver
+                        .recv()
+                        .map_err(|_| mpsc::RecvTimeoutError::Disconnected),
+                };
+
+                match res {
+                    Ok(Ok(Some(res))) => return Ok(res),
+                    Ok(res) => {
+                        running -= 1;
+
+                        // the first result is returned right away, so the last attempt failed
+                        if running == 0 {
+                            return Err(res.err().unwrap_or(Error::NoResponse));
+                        }
+                    }
+                    Err(mpsc::RecvTimeoutError::Timeout) => {
+                        if Instant::now() < speculate_at {
+                            continue;
+                        }
+
+                        if let Some(backup) = self.pool.get_idle_worker(&primary_worker) {
+                            debug!(
+                                "task on {:} is slow - sending a duplicate to {:}",
+                                primary_worker.addr, backup.addr
+                            );
+                            self.stats.lock().unwrap().speculative_tasks += 1;
+
+                            spawn_attempt(backup);
+                            running += 1;
+                            speculated = true;
+                        }
+                    }
+                    Err(mpsc::RecvTimeoutError::Disconnected) => return Err(Error::NoResponse),
                 }
             }
-        }
+        })
     }
 
-    fn reduce<O1, O2>(acc: Option<O2>, elem: O1) -> O2
+    /// One of the attempts of `perform_speculatively`. Returns `None` if another
+    /// attempt finished first.
+    fn attempt<O>(
+        &self,
+        worker: WorkerGuard<'_>,
+        message: &[u8],
+        abandoned: &AtomicBool,
+    ) -> Result<Option<O>>
     where
-        O1: Serialize + DeserializeOwned + Send,
-        O2: From<O1> + Reduce<O1> + Send,
+        O: DeserializeOwned,
     {
-        match acc {
-            Some(acc) => acc.reduce(elem),
-            None => elem.into(),
+        let start = Instant::now();
+
+        match worker.perform_encoded(
+            message,
+            self.task_timeout,
+            self.pool.format,
+            Some(abandoned),
+        ) {
+            Ok(res) => {
+                // the worker did its job even if the other attempt won, so it is not marked as dead
+                if !abandoned.swap(true, Ordering::SeqCst) {
+                    self.record_completed(worker.addr, start.elapsed());
+                    worker.success();
+                    Ok(Some(res))
+                } else {
+                    worker.success();
+                    Ok(None)
+                }
+            }
+            Err(Error::Cancelled) => {
+                worker.success();
+                Ok(None)
+            }
+            Err(err) => Err(err),
         }
     }
 
-    fn reduce_end<O>(acc: Option<O>, elem: O) -> O
-    where
-        O: Reduce<O> + Send,
-    {
-        match acc {
-            Some(acc) => acc.reduce(elem),
-            None => elem,
+    fn record_completed(&self, worker: SocketAddr, busy_time: Duration) {
+        let mut stats = self.stats.lock().unwrap();
+
+        stats.tasks_completed += 1;
+        *stats.per_worker_task_counts.entry(worker).or_default() += 1;
+        *stats.per_worker_busy_time.entry(worker).or_default() += busy_time;
+
+        let mut durations = self.task_durations.lock().unwrap();
+        durations.push_back(busy_time);
+        if durations.len() > SPECULATION_WINDOW {
+            durations.pop_front();
         }
     }
 
-    #[allow(clippy::trait_duplication_in_bounds)]
-    fn get_results<W, I, O1, O2>(&self, jobs: impl Iterator<Item = I> + Send) -> Option<O2>
+    /// Execute job on one of the remote machines. If the remote machine fails for some reason,
+    /// the job is allocated to another machine until the job has been retried `max_retries` times.
+    pub fn try_map<W, I, O>(&self, job: &I) -> Result<O>
     where
         W: Worker,
-        I: Map<W, O1> + Send,
-        O1: Serialize + DeserializeOwned + Send,
-        O2: From<O1> + Reduce<O1> + Send + Reduce<O2>,
+        I: Map<W, O> + Send + 'static,
+        O: Serialize + DeserializeOwned + Send,
     {
-        let acc: Arc<Mutex<Option<O2>>> = Arc::new(Mutex::new(None));
+        let mut attempts = 0;
 
-        let size = jobs.size_hint();
+        loop {
+            match self.map_once(job) {
+                Ok(res) => return Ok(res),
+                Err(Error::NoAvailableWorker) => {
+                    self.stats.lock().unwrap().tasks_failed += 1;
+                    return Err(Error::NoAvailableWorker);
+                }
+                Err(Error::Cancelled) => return Err(Error::Cancelled),
+                Err(err @ Error::ProtocolMismatch { .. }) => {
+                    // the other workers are most likely built from the same version
+                    self.stats.lock().unwrap().tasks_failed += 1;
+                    return Err(err);
+                }
+                Err(err) => {
+                    attempts += 1;
 
-        match size.1 {
-            Some(size) => {
-                let pb = ProgressBar::new(size as u64);
-                pb.set_style(
-                    ProgressStyle::default_bar()
-                        .template(
-                            "{spinner:.green} [{elapsed_precise}] [{wide_bar}] {pos:>7}/{len:7} ({eta})",
-                        )
-                        .progress_chars("#>-"),
-                );
-                jobs.par_bridge()
-                    .map(|job| self.map::<W, I, O1>(job))
-                    .progress_with(pb)
-                    .fold(
-                        || None,
-                        |acc: Option<O2>, elem| Some(Manager::reduce(acc, elem)),
-                    )
-                    .for_each(|res| {
-                        if let Some(res) = res {
-                            let mut lock = acc.lock().unwrap();
-                            *lock = Some(Manager::reduce_end(lock.take(), res));
+                    if let Some(max_retries) = self.max_retries {
+                        if attempts > max_retries {
+                            warn!("Job failed {} times - giving up", attempts);
+                            self.stats.lock().unwrap().tasks_failed += 1;
+                            return Err(err);
                         }
-                    });
-            }
-            None => {
-                jobs.par_bridge()
-                    .map(|job| self.map::<W, I, O1>(job))
-                    .fold(
-                        || None,
-                        |acc: Option<O2>, elem| Some(Manager::reduce(acc, elem)),
-                    )
-                    .for_each(|res| {
-                        if let Some(res) = res {
-                            let mut lock = acc.lock().unwrap();
-                            *lock = Some(Manager::reduce_end(lock.take(), res));
-                        }
-                    });
+                    }
+
+                    self.stats.lock().unwrap().retries += 1;
+
+                    warn!("Worker failed - rescheduling job (attempt {})", attempts);
+                    debug!("{:?}", err);
+                }
             }
         }
+    }
+
+    /// Same as `try_map`, but panics if the job could not be executed.
+    pub fn map<W, I, O>(&self, job: I) -> O
+    where
+        W: Worker,
+        I: Map<W, O> + Send + 'static,
+        O: Serialize + DeserializeOwned + Send,
+    {
+        match self.try_map(&job) {
+            Ok(res) => res,
+            Err(err) => panic!("{}", err),
+        }
+    }
+
+    /// Same as `map`, but returns `None` if the job was cancelled or dropped by the `FailPolicy`.
+    /// `idx` is the index of the job in the input.
+    fn map_job<W, I, O>(&self, idx: usize, job: I) -> Option<O>
+    where
+        W: Worker,
+        I: Map<W, O> + Send + 'static,
+        O: Serialize + DeserializeOwned + Send,
+    {
+        match self.try_map(&job) {
+            Ok(res) => Some(res),
+            Err(Error::Cancelled) => None,
+            Err(err) => match self.fail_policy {
+                FailPolicy::AllOrNothing => panic!("{}", err),
+                FailPolicy::BestEffort { max_failures } => {
+                    let mut stats = self.stats.lock().unwrap();
+                    stats.dropped_tasks.push(idx);
+
+                    if stats.dropped_tasks.len() > max_failures {
+                        panic!("more than {} tasks failed: {}", max_failures, err);
+                    }
+
+                    warn!("dropping task {} after it failed: {}", idx, err);
+                    None
+                }
+            },
+        }
+    }
+
+    fn reduce<O1, O2>(acc: Option<O2>, elem: O1) -> O2
+    where
+        O1: Serialize + DeserializeOwned + Send,
+        O2: From<O1> + Reduce<O1> + Send,
+    {
+        match acc {
+            Some(acc) => acc.reduce(elem),
+            None => elem.into(),
+        }
+    }
+
+    fn reduce_end<O>(acc: Option<O>, elem: O) -> O
+    where
+        O: Reduce<O> + Send,
+    {
+        match acc {
+            Some(acc) => acc.reduce(elem),
+            None => elem,
+        }
+    }
+
+    #[allow(clippy::trait_duplication_in_bounds)]
+    fn get_results<W, I, O1, O2>(&self, jobs: impl Iterator<Item = I> + Send) -> Option<O2>
+    where
+        W: Worker,
+        I: Map<W, O1> + Send + 'static,
+        O1: Serialize + DeserializeOwned + Send,
+        O2: From<O1> + Reduce<O1> + Send + Reduce<O2>,
+    {
+        let acc: Arc<Mutex<Option<O2>>> = Arc::new(Mutex::new(None));
+        let jobs = jobs.take_while(|_| !self.is_cancelled()).enumerate();
+        let progress = Progress::new(self, jobs.size_hint().1.map(|total| total as u64), 0);
+
+        jobs.par_bridge()
+            .filter_map(|(idx, job)| {
+                let res = self.map_job::<W, I, O1>(idx, job);
+                progress.inc();
+                res
+            })
+            .fold(
+                || None,
+                |acc: Option<O2>, elem| Some(Manager::reduce(acc, elem)),
+            )
+            .for_each(|res| {
+                if let Some(res) = res {
+                    let mut lock = acc.lock().unwrap();
+                    *lock = Some(Manager::reduce_end(lock.take(), res));
+                }
+            });
+
+        progress.finish();
 
         let x = acc.lock().unwrap().take();
         x
@@ -332,13 +1226,1226 @@ impl Manager {
     pub fn run<W, I, O1, O2>(self, jobs: impl Iterator<Item = I> + Send) -> Option<O2>
     where
         W: Worker,
-        I: Map<W, O1> + Send,
+        I: Map<W, O1> + Send + 'static,
+        O1: Serialize + DeserializeOwned + Send,
+        O2: From<O1> + Reduce<O1> + Send + Reduce<O2>,
+    {
+        self.run_with_stats(jobs).0
+    }
+
+    /// Same as `run`, but also returns statistics about the executed tasks.
+    #[allow(clippy::trait_duplication_in_bounds)]
+    pub fn run_with_stats<W, I, O1, O2>(
+        self,
+        jobs: impl Iterator<Item = I> + Send,
+    ) -> (Option<O2>, JobStats)
+    where
+        W: Worker,
+        I: Map<W, O1> + Send + 'static,
         O1: Serialize + DeserializeOwned + Send,
         O2: From<O1> + Reduce<O1> + Send + Reduce<O2>,
     {
+        assert!(
+            self.checkpoint.is_none(),
+            "jobs with a checkpoint must be executed with `run_resumable`"
+        );
+
+        let start = Instant::now();
+
         let result = self.get_results(jobs);
         self.pool.stop_workers::<W, I, O1>();
 
-        result
+        let mut stats = std::mem::take(&mut *self.stats.lock().unwrap());
+        stats.wall_time = start.elapsed();
+
+        if self.is_cancelled() {
+            return (None, stats);
+        }
+
+        (result, stats)
+    }
+
+    /// Same as `run`, but periodically stores the indices of the completed inputs and
+    /// their reduced result in the checkpoint set by `resume_from`. Inputs that are
+    /// already in the checkpoint are skipped, and their result is reduced with the rest.
+    /// The inputs must be yielded in the same order every time the job is run.
+    #[allow(clippy::trait_duplication_in_bounds)]
+    pub fn run_resumable<W, I, O1, O2>(self, jobs: impl Iterator<Item = I> + Send) -> Option<O2>
+    where
+        W: Worker,
+        I: Map<W, O1> + Send + 'static,
+        O1: Serialize + DeserializeOwned + Send,
+        O2: From<O1> + Reduce<O1> + Send + Serialize + DeserializeOwned,
+    {
+        let path = self
+            .checkpoint
+            .clone()
+            .expect("a checkpoint must be set with `resume_from`");
+
+        let checkpoint: Checkpoint<O2> = Checkpoint::load(&path)
+            .unwrap_or_else(|err| panic!("failed to load checkpoint {:?}: {}", path, err))
+            .unwrap_or_default();
+        let skip = checkpoint.completed.clone();
+        let state = Mutex::new((checkpoint, Instant::now()));
+        let progress = Progress::new(
+            &self,
+            jobs.size_hint().1.map(|total| total as u64),
+            skip.len() as u64,
+        );
+
+        jobs.take_while(|_| !self.is_cancelled())
+            .enumerate()
+            .filter(|(idx, _)| !skip.contains(idx))
+            .par_bridge()
+            .for_each(|(idx, job)| {
+                let res = self.map_job::<W, I, O1>(idx, job);
+                progress.inc();
+
+                if let Some(res) = res {
+                    let mut state = state.lock().unwrap();
+                    let (checkpoint, last_save) = &mut *state;
+
+                    checkpoint.acc = Some(Manager::reduce(checkpoint.acc.take(), res));
+                    checkpoint.completed.insert(idx);
+
+                    if last_save.elapsed() >= self.checkpoint_interval {
+                        if let Err(err) = checkpoint.save(&path) {
+                            warn!("failed to save checkpoint: {}", err);
+                        }
+                        *last_save = Instant::now();
+                    }
+                }
+            });
+
+        progress.finish();
+
+        let (checkpoint, _) = state.into_inner().unwrap();
+        checkpoint
+            .save(&path)
+            .unwrap_or_else(|err| panic!("failed to save checkpoint {:?}: {}", path, err));
+
+        self.pool.stop_workers::<W, I, O1>();
+
+        if self.is_cancelled() {
+            return None;
+        }
+
+        checkpoint.acc
+    }
+
+    /// Executes the jobs on the workers and yields each result as soon as it's
+    /// received, so the results arrive in completion order rather than input order.
+    /// Failing jobs are rescheduled like in `run`, and jobs that are cancelled or
+    /// dropped by the `FailPolicy` yield no result. The workers are stopped once
+    /// all jobs have finished.
+    pub fn run_streaming<W, I, O>(
+        self,
+        jobs: impl Iterator<Item = I> + Send + 'static,
+    ) -> impl Iterator<Item = O>
+    where
+        W: Worker + 'static,
+        I: Map<W, O> + Send + 'static,
+        O: Serialize + DeserializeOwned + Send + 'static,
+    {
+        let (sender, receiver) = mpsc::channel();
+
+        let handle = std::thread::spawn(move || {
+            let jobs = jobs.take_while(|_| !self.is_cancelled()).enumerate();
+            let progress = Progress::new(&self, jobs.size_hint().1.map(|total| total as u64), 0);
+
+            jobs.par_bridge()
+                .for_each_with(sender, |sender, (idx, job)| {
+                    let res = self.map_job::<W, I, O>(idx, job);
+                    progress.inc();
+
+                    if let Some(res) = res {
+                        sender.send(res).ok();
+                    }
+                });
+
+            progress.finish();
+
+            self.pool.stop_workers::<W, I, O>();
+        });
+
+        StreamingResults {
+            receiver,
+            handle: Some(handle),
+        }
+    }
+}
+
+struct StreamingResults<O> {
+    receiver: Receiver<O>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl<O> Iterator for StreamingResults<O> {
+    type Item = O;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.receiver.recv() {
+            Ok(res) => Some(res),
+            Err(_) => {
+                // all jobs are done, so propagate the panic of a failed job (if any)
+                if let Some(handle) = self.handle.take() {
+                    if let Err(err) = handle.join() {
+                        std::panic::resume_unwind(err);
+                    }
+                }
+
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+
+    use serde::Deserialize;
+
+    use super::*;
+    use crate::mapreduce::worker::{decode_request, State};
+    use crate::mapreduce::{encode_error_frame, ErrorReply};
+    use crate::mapreduce::{AsyncWorker, StatefulWorker, StatelessWorker};
+
+    #[derive(Serialize, Deserialize, Debug)]
+    struct MockJob {
+        contents: Vec<usize>,
+    }
+
+    #[derive(Serialize, Deserialize, Debug)]
+    struct Count(usize);
+
+    impl Map<StatelessWorker, Count> for MockJob {
+        fn map(self, _worker: &StatelessWorker) -> Count {
+            Count(self.contents.into_iter().filter(|d| *d == 0).count())
+        }
+    }
+
+    impl Reduce<Count> for Count {
+        fn reduce(self, element: Count) -> Self {
+            Count(self.0 + element.0)
+        }
+    }
+
+    fn spawn_worker() -> (SocketAddr, JoinHandle<()>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let handle = std::thread::spawn(move || {
+            let worker = StatelessWorker::default();
+
+            for stream in listener.incoming() {
+                match worker.run_stream::<StatelessWorker, MockJob, Count, _>(stream.unwrap()) {
+                    Ok(State::Finished) => break,
+                    Ok(State::Continue) | Err(_) => {}
+                }
+            }
+        });
+
+        (addr, handle)
+    }
+
+    /// Reads the request and answers it if it's a ping.
+    fn read_request(stream: &mut TcpStream) -> Option<Task<MockJob>> {
+        let mut buf = [0; BUF_SIZE];
+        let mut bytes = Vec::new();
+
+        while !bytes.ends_with(&END_OF_MESSAGE) {
+            match stream.read(&mut buf) {
+                Ok(0) | Err(_) => return None,
+                Ok(size) => bytes.extend_from_slice(&buf[..size]),
+            }
+        }
+
+        let (task, _, _) =
+            decode_request::<MockJob>(&bytes[..bytes.len() - END_OF_MESSAGE.len()]).ok()?;
+
+        if let Task::Ping = task {
+            stream.write_all(&END_OF_MESSAGE).unwrap();
+        }
+
+        Some(task)
+    }
+
+    /// Answers pings, but closes the connection without responding to any job.
+    fn spawn_failing_worker() -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                read_request(&mut stream.unwrap());
+            }
+        });
+
+        addr
+    }
+
+    /// Answers pings, but closes the connection halfway through the response to a job.
+    fn spawn_truncating_worker() -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+
+                if let Some(Task::Job(_)) = read_request(&mut stream) {
+                    let frame = encode_frame(bincode::serialize(&Count(1)).unwrap(), None).unwrap();
+                    stream.write_all(&frame[..frame.len() / 2]).unwrap();
+                }
+            }
+        });
+
+        addr
+    }
+
+    /// Answers pings, but never responds to a job.
+    fn spawn_slow_worker() -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        std::thread::spawn(move || {
+            let mut streams = Vec::new();
+
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+
+                if let Some(Task::Job(_)) = read_request(&mut stream) {
+                    streams.push(stream);
+                }
+            }
+        });
+
+        addr
+    }
+
+    #[test]
+    fn failed_job_retried_on_other_worker() {
+        let (good, handle) = spawn_worker();
+        let bad = spawn_failing_worker();
+
+        // the first worker is picked first, so the job initially goes to the failing worker
+        let manager = Manager::new(&[bad, good]).with_retries(1);
+
+        let res: Option<Count> = manager.run::<StatelessWorker, MockJob, Count, Count>(
+            vec![MockJob {
+                contents: vec![1, 0, 0, 2, 0],
+            }]
+            .into_iter(),
+        );
+
+        assert_eq!(res.unwrap().0, 3);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn gives_up_after_max_retries() {
+        let manager =
+            Manager::new(&[spawn_failing_worker(), spawn_failing_worker()]).with_retries(0);
+
+        let res =
+            manager.try_map::<StatelessWorker, MockJob, Count>(&MockJob { contents: vec![0] });
+
+        assert!(matches!(res, Err(Error::NoResponse)));
+    }
+
+    #[test]
+    fn truncated_response() {
+        let manager = Manager::new(&[spawn_truncating_worker()]).with_retries(0);
+
+        let res =
+            manager.try_map::<StatelessWorker, MockJob, Count>(&MockJob { contents: vec![0] });
+
+        assert!(matches!(res, Err(Error::NoResponse)));
+    }
+
+    /// Answers pings, but rejects jobs like a worker built with another protocol version.
+    fn spawn_outdated_worker(num_jobs: Arc<AtomicU32>) -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+
+                if let Some(Task::Job(_)) = read_request(&mut stream) {
+                    num_jobs.fetch_add(1, Ordering::SeqCst);
+
+                    let reply = ErrorReply::ProtocolMismatch {
+                        manager: PROTOCOL_VERSION,
+                        worker: PROTOCOL_VERSION + 1,
+                    };
+                    stream
+                        .write_all(&encode_error_frame(&reply).unwrap())
+                        .unwrap();
+                    stream.write_all(&END_OF_MESSAGE).unwrap();
+                }
+            }
+        });
+
+        addr
+    }
+
+    #[test]
+    fn protocol_mismatch_not_retried() {
+        let num_jobs = Arc::new(AtomicU32::new(0));
+        let manager = Manager::new(&[
+            spawn_outdated_worker(Arc::clone(&num_jobs)),
+            spawn_outdated_worker(Arc::clone(&num_jobs)),
+        ]);
+
+        let res =
+            manager.try_map::<StatelessWorker, MockJob, Count>(&MockJob { contents: vec![0] });
+
+        match res {
+            Err(Error::ProtocolMismatch { manager, worker }) => {
+                assert_eq!(manager, PROTOCOL_VERSION);
+                assert_eq!(worker, PROTOCOL_VERSION + 1);
+            }
+            _ => panic!("expected a protocol mismatch"),
+        }
+        assert_eq!(num_jobs.load(Ordering::SeqCst), 1);
+        assert_eq!(manager.stats.lock().unwrap().retries, 0);
+        assert_eq!(manager.stats.lock().unwrap().tasks_failed, 1);
+    }
+
+    #[test]
+    fn task_timeout() {
+        let manager = Manager::new(&[spawn_slow_worker()])
+            .with_retries(0)
+            .with_task_timeout(Duration::from_millis(100));
+
+        let res =
+            manager.try_map::<StatelessWorker, MockJob, Count>(&MockJob { contents: vec![0] });
+
+        assert!(matches!(res, Err(Error::TaskTimeout)));
+    }
+
+    /// Job that sleeps for the duration in the state of the worker before responding.
+    /// The id is used to pin jobs to a worker.
+    #[derive(Serialize, Deserialize, Debug)]
+    struct SleepJob(usize);
+
+    impl Map<StatefulWorker<Duration>, Count> for SleepJob {
+        fn map(self, worker: &StatefulWorker<Duration>) -> Count {
+            std::thread::sleep(*worker.state());
+            Count(1)
+        }
+    }
+
+    /// Runs `Worker::run` for `SleepJob`s with the given delay.
+    fn spawn_sleeping_worker(delay: Duration) -> (SocketAddr, JoinHandle<()>) {
+        let addr = unused_addr();
+
+        let handle = std::thread::spawn(move || {
+            StatefulWorker::new(move || delay)
+                .run::<SleepJob, Count>(addr)
+                .unwrap();
+        });
+
+        (addr, handle)
+    }
+
+    #[test]
+    fn worker_survives_task_timeout() {
+        let (worker, handle) = spawn_sleeping_worker(Duration::from_millis(300));
+
+        let res = Manager::new(&[worker])
+            .with_retries(0)
+            .with_task_timeout(Duration::from_millis(100))
+            .try_map::<StatefulWorker<Duration>, SleepJob, Count>(&SleepJob(0));
+        assert!(matches!(res, Err(Error::TaskTimeout)));
+
+        // the worker can't deliver the result of the timed out job, but keeps serving
+        std::thread::sleep(Duration::from_millis(300));
+
+        let res: Option<Count> = Manager::new(&[worker])
+            .run::<StatefulWorker<Duration>, SleepJob, Count, Count>(vec![SleepJob(0)].into_iter());
+
+        assert_eq!(res.unwrap().0, 1);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn timed_out_job_rescheduled() {
+        let (good, handle) = spawn_worker();
+        let slow = spawn_slow_worker();
+
+        let manager = Manager::new(&[slow, good]).with_task_timeout(Duration::from_millis(100));
+
+        let res: Option<Count> = manager.run::<StatelessWorker, MockJob, Count, Count>(
+            vec![MockJob {
+                contents: vec![0, 0],
+            }]
+            .into_iter(),
+        );
+
+        assert_eq!(res.unwrap().0, 2);
+        handle.join().unwrap();
+    }
+
+    /// Handles each connection in its own thread and keeps track of the maximum
+    /// number of jobs that were processed at the same time.
+    fn spawn_concurrent_worker(max_in_flight: Arc<AtomicU32>) -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let in_flight = Arc::new(AtomicU32::new(0));
+
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let in_flight = Arc::clone(&in_flight);
+                let max_in_flight = Arc::clone(&max_in_flight);
+
+                std::thread::spawn(move || {
+                    let mut stream = stream.unwrap();
+
+                    if let Some(Task::Job(job)) = read_request(&mut stream) {
+                        let current = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                        max_in_flight.fetch_max(current, Ordering::SeqCst);
+
+                        std::thread::sleep(Duration::from_millis(200));
+                        let res = job.map(&StatelessWorker::default());
+
+                        in_flight.fetch_sub(1, Ordering::SeqCst);
+
+                        let bytes = encode_frame(bincode::serialize(&res).unwrap(), None).unwrap();
+                        stream.write_all(&bytes).unwrap();
+                        stream.write_all(&END_OF_MESSAGE).unwrap();
+                    }
+                });
+            }
+        });
+
+        addr
+    }
+
+    fn max_concurrent_tasks(tasks_per_worker: usize) -> u32 {
+        let max_in_flight = Arc::new(AtomicU32::new(0));
+        let worker = spawn_concurrent_worker(Arc::clone(&max_in_flight));
+        let manager = Arc::new(Manager::new(&[worker]).with_tasks_per_worker(tasks_per_worker));
+
+        let handles: Vec<_> = (0..2)
+            .map(|_| {
+                let manager = Arc::clone(&manager);
+                std::thread::spawn(move || {
+                    manager
+                        .try_map::<StatelessWorker, MockJob, Count>(&MockJob { contents: vec![0] })
+                        .unwrap()
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            assert_eq!(handle.join().unwrap().0, 1);
+        }
+
+        max_in_flight.load(Ordering::SeqCst)
+    }
+
+    #[test]
+    fn tasks_per_worker() {
+        assert_eq!(max_concurrent_tasks(1), 1);
+        assert_eq!(max_concurrent_tasks(2), 2);
+    }
+
+    #[test]
+    fn failed_task_keeps_other_in_flight_tasks() {
+        let (worker, handle) = spawn_worker();
+        let mut pool = WorkerPool::new(&[worker]);
+        pool.tasks_per_worker = 2;
+
+        let first = pool.get_worker(None).unwrap().unwrap();
+        let second = pool.get_worker(None).unwrap().unwrap();
+
+        // the second task fails while the first one is still in flight
+        drop(second);
+        assert!(pool.live_workers.lock().unwrap().is_empty());
+
+        pool.dead_workers.lock().unwrap()[0].1 -= HEALTH_PROBE_INTERVAL;
+        pool.probe_dead_workers();
+        assert_eq!(pool.live_workers.lock().unwrap().len(), 1);
+
+        // the first task still occupies one of the two slots
+        let third = pool.get_worker(None).unwrap().unwrap();
+        assert!(pool.get_worker(None).unwrap().is_none());
+
+        first.success();
+        third.success();
+        assert_eq!(pool.in_flight.lock().unwrap()[&worker], 0);
+
+        pool.stop_workers::<StatelessWorker, MockJob, Count>();
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn job_stats() {
+        let workers = [
+            spawn_concurrent_worker(Arc::new(AtomicU32::new(0))),
+            spawn_concurrent_worker(Arc::new(AtomicU32::new(0))),
+        ];
+        let manager = Manager::new(&workers);
+
+        let jobs = (0..10).map(|_| MockJob { contents: vec![0] });
+
+        // two threads, so both workers are busy at the same time
+        let (res, stats) = rayon::ThreadPoolBuilder::new()
+            .num_threads(2)
+            .build()
+            .unwrap()
+            .install(|| manager.run_with_stats::<StatelessWorker, MockJob, Count, Count>(jobs));
+
+        assert_eq!(res.unwrap().0, 10);
+        assert_eq!(stats.tasks_completed, 10);
+        assert_eq!(stats.tasks_failed, 0);
+        assert_eq!(stats.retries, 0);
+
+        assert_eq!(stats.per_worker_task_counts.len(), 2);
+        assert_eq!(stats.per_worker_task_counts.values().sum::<usize>(), 10);
+        for worker in &workers {
+            assert!(stats.per_worker_task_counts[worker] > 0);
+            assert!(stats.per_worker_busy_time[worker] > Duration::ZERO);
+        }
+        assert!(stats.wall_time > Duration::ZERO);
+    }
+
+    /// Answers pings, and responds to each job once a message is sent on `release`.
+    fn spawn_held_worker(release: Receiver<()>) -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+
+                if let Some(Task::Job(job)) = read_request(&mut stream) {
+                    release.recv().unwrap();
+
+                    let res = job.map(&StatelessWorker::default());
+                    let bytes = encode_frame(bincode::serialize(&res).unwrap(), None).unwrap();
+                    stream.write_all(&bytes).unwrap();
+                    stream.write_all(&END_OF_MESSAGE).unwrap();
+                }
+            }
+        });
+
+        addr
+    }
+
+    /// Answers pings and jobs, and sends a message on `served` after each job.
+    fn spawn_reporting_worker(served: mpsc::Sender<()>) -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+
+                if let Some(Task::Job(job)) = read_request(&mut stream) {
+                    let res = job.map(&StatelessWorker::default());
+                    let bytes = encode_frame(bincode::serialize(&res).unwrap(), None).unwrap();
+                    stream.write_all(&bytes).unwrap();
+                    stream.write_all(&END_OF_MESSAGE).unwrap();
+
+                    served.send(()).unwrap();
+                }
+            }
+        });
+
+        addr
+    }
+
+    #[test]
+    fn least_loaded_worker() {
+        let (release, held) = mpsc::channel();
+        let (served, reported) = mpsc::channel();
+        let slow = spawn_held_worker(held);
+        let fast = spawn_reporting_worker(served);
+        let manager = Manager::new(&[slow, fast]);
+
+        let run = std::thread::spawn(move || {
+            let jobs = (0..20).map(|_| MockJob { contents: vec![0] });
+
+            rayon::ThreadPoolBuilder::new()
+                .num_threads(2)
+                .build()
+                .unwrap()
+                .install(|| manager.run_with_stats::<StatelessWorker, MockJob, Count, Count>(jobs))
+        });
+
+        // the first job goes to the slow worker, which holds it until every other job
+        // has been served by the fast worker
+        for _ in 0..19 {
+            reported.recv().unwrap();
+        }
+        release.send(()).unwrap();
+
+        let (res, stats) = run.join().unwrap();
+        assert_eq!(res.unwrap().0, 20);
+        assert_eq!(stats.per_worker_task_counts.get(&slow), Some(&1));
+        assert_eq!(stats.per_worker_task_counts.get(&fast), Some(&19));
+    }
+
+    #[test]
+    fn speculation() {
+        let (fast, handle) = spawn_worker();
+        let stuck = spawn_slow_worker();
+
+        // the last job is pinned to the stuck worker, the others give the manager a median
+        let manager = Manager::new(&[fast, stuck])
+            .with_speculation(true)
+            .with_partitioner(|job: &MockJob| job.contents.len() - 1);
+
+        let jobs = [1, 1, 1, 1, 1, 1, 2].map(|len| MockJob {
+            contents: vec![0; len],
+        });
+
+        let (res, stats) = rayon::ThreadPoolBuilder::new()
+            .num_threads(1)
+            .build()
+            .unwrap()
+            .install(|| {
+                manager.run_with_stats::<StatelessWorker, MockJob, Count, Count>(jobs.into_iter())
+            });
+
+        assert_eq!(res.unwrap().0, 8);
+        assert_eq!(stats.speculative_tasks, 1);
+        assert_eq!(stats.tasks_completed, 7);
+        assert_eq!(stats.per_worker_task_counts.get(&fast), Some(&7));
+        assert_eq!(stats.per_worker_task_counts.get(&stuck), None);
+        assert_eq!(stats.retries, 0);
+
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn abandoned_worker_keeps_serving() {
+        let (fast, fast_handle) = spawn_sleeping_worker(Duration::ZERO);
+        let (slow, slow_handle) = spawn_sleeping_worker(Duration::from_millis(1500));
+
+        let manager = Manager::new(&[fast, slow])
+            .with_speculation(true)
+            .with_partitioner(|job: &SleepJob| job.0);
+
+        // enough fast tasks for a median, then a task that is duplicated on the fast worker
+        for id in [0, 0, 0, 0, 0, 1] {
+            let res = manager
+                .try_map::<StatefulWorker<Duration>, SleepJob, Count>(&SleepJob(id))
+                .unwrap();
+            assert_eq!(res.0, 1);
+        }
+        assert_eq!(manager.stats.lock().unwrap().speculative_tasks, 1);
+
+        // the slow worker fails to deliver the abandoned result, but still serves the next task
+        let res: Option<Count> = Manager::new(&[slow])
+            .run::<StatefulWorker<Duration>, SleepJob, Count, Count>(vec![SleepJob(0)].into_iter());
+        assert_eq!(res.unwrap().0, 1);
+        slow_handle.join().unwrap();
+
+        let res: Option<Count> = Manager::new(&[fast])
+            .run::<StatefulWorker<Duration>, SleepJob, Count, Count>(vec![SleepJob(0)].into_iter());
+        assert_eq!(res.unwrap().0, 1);
+        fast_handle.join().unwrap();
+    }
+
+    #[test]
+    fn progress_callback() {
+        let (worker, handle) = spawn_worker();
+        let calls = Arc::new(Mutex::new(Vec::new()));
+
+        let manager = Manager::new(&[worker])
+            .with_progress(false)
+            .with_progress_callback({
+                let calls = Arc::clone(&calls);
+                move |finished, total| calls.lock().unwrap().push((finished, total))
+            });
+
+        let jobs: Vec<_> = (0..10).map(|_| MockJob { contents: vec![0] }).collect();
+        let res = manager.run::<StatelessWorker, MockJob, Count, Count>(jobs.into_iter());
+        assert_eq!(res.unwrap().0, 10);
+        handle.join().unwrap();
+
+        let mut calls = calls.lock().unwrap().clone();
+        calls.sort_unstable();
+        assert_eq!(calls, (1..=10).map(|i| (i, Some(10))).collect::<Vec<_>>());
+
+        // without an upper bound on the number of jobs, the total is unknown
+        let (worker, handle) = spawn_worker();
+        let totals = Arc::new(Mutex::new(Vec::new()));
+
+        let manager = Manager::new(&[worker]).with_progress_callback({
+            let totals = Arc::clone(&totals);
+            move |_, total| totals.lock().unwrap().push(total)
+        });
+
+        let mut remaining = 3;
+        let jobs = std::iter::from_fn(move || {
+            remaining -= 1;
+            (remaining >= 0).then(|| MockJob { contents: vec![0] })
+        });
+        let res = manager.run::<StatelessWorker, MockJob, Count, Count>(jobs);
+        assert_eq!(res.unwrap().0, 3);
+        handle.join().unwrap();
+
+        assert_eq!(*totals.lock().unwrap(), vec![None; 3]);
+    }
+
+    #[test]
+    fn partitioner() {
+        let workers = [
+            spawn_concurrent_worker(Arc::new(AtomicU32::new(0))),
+            spawn_concurrent_worker(Arc::new(AtomicU32::new(0))),
+            spawn_concurrent_worker(Arc::new(AtomicU32::new(0))),
+        ];
+        let manager = Manager::new(&workers).with_partitioner(|job: &MockJob| job.contents.len());
+
+        // jobs with 1 and 4 elements go to the second worker, the job with 2 elements to the third
+        let jobs = [1, 4, 2, 4].map(|len| MockJob {
+            contents: vec![0; len],
+        });
+
+        let (res, stats) =
+            manager.run_with_stats::<StatelessWorker, MockJob, Count, Count>(jobs.into_iter());
+
+        assert_eq!(res.unwrap().0, 11);
+        assert_eq!(stats.per_worker_task_counts.get(&workers[0]), None);
+        assert_eq!(stats.per_worker_task_counts.get(&workers[1]), Some(&3));
+        assert_eq!(stats.per_worker_task_counts.get(&workers[2]), Some(&1));
+    }
+
+    /// Runs an `AsyncWorker` for `I` on its own runtime. The connections are encrypted
+    /// if `tls` is set.
+    fn spawn_async_worker<I>(tls: Option<Arc<rustls::ServerConfig>>) -> (SocketAddr, JoinHandle<()>)
+    where
+        I: Map<StatelessWorker, Count> + 'static,
+    {
+        let (sender, receiver) = mpsc::channel();
+
+        let handle = std::thread::spawn(move || {
+            tokio::runtime::Runtime::new().unwrap().block_on(async {
+                let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+                sender.send(listener.local_addr().unwrap()).unwrap();
+
+                let worker = AsyncWorker::new(StatelessWorker::default());
+                let res = match tls {
+                    Some(config) => worker.serve_tls::<I, Count>(listener, config).await,
+                    None => worker.serve::<I, Count>(listener).await,
+                };
+                res.unwrap();
+            });
+        });
+
+        (receiver.recv().unwrap(), handle)
+    }
+
+    #[test]
+    fn async_worker() {
+        let (worker, handle) = spawn_async_worker::<MockJob>(None);
+        let manager = Manager::new(&[worker]).with_tasks_per_worker(4);
+
+        let res: Option<Count> =
+            manager.run::<StatelessWorker, MockJob, Count, Count>((0..10).map(|_| MockJob {
+                contents: vec![0, 1, 0],
+            }));
+
+        assert_eq!(res.unwrap().0, 20);
+        handle.join().unwrap();
+    }
+
+    /// Panics on the worker if the flag is set.
+    #[derive(Serialize, Deserialize, Debug)]
+    struct PanicJob(bool);
+
+    impl Map<StatelessWorker, Count> for PanicJob {
+        fn map(self, _worker: &StatelessWorker) -> Count {
+            assert!(!self.0, "job panicked");
+            Count(1)
+        }
+    }
+
+    #[test]
+    fn async_worker_survives_panic() {
+        let (worker, handle) = spawn_async_worker::<PanicJob>(None);
+
+        let res = Manager::new(&[worker])
+            .with_retries(0)
+            .try_map::<StatelessWorker, PanicJob, Count>(&PanicJob(true));
+        assert!(matches!(res, Err(Error::JobFailed(_))));
+
+        let res: Option<Count> = Manager::new(&[worker])
+            .run::<StatelessWorker, PanicJob, Count, Count>(vec![PanicJob(false)].into_iter());
+
+        assert_eq!(res.unwrap().0, 1);
+        handle.join().unwrap();
+    }
+
+    /// Answers pings and jobs, but closes the connection on jobs without contents.
+    fn spawn_picky_worker() -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+
+                if let Some(Task::Job(job)) = read_request(&mut stream) {
+                    if job.contents.is_empty() {
+                        continue;
+                    }
+
+                    let res = job.map(&StatelessWorker::default());
+                    let bytes = encode_frame(bincode::serialize(&res).unwrap(), None).unwrap();
+                    stream.write_all(&bytes).unwrap();
+                    stream.write_all(&END_OF_MESSAGE).unwrap();
+                }
+            }
+        });
+
+        addr
+    }
+
+    #[test]
+    fn best_effort() {
+        let manager = Manager::new(&[spawn_picky_worker()])
+            .with_retries(1)
+            .with_fail_tolerance(FailPolicy::BestEffort { max_failures: 1 });
+
+        let jobs = vec![
+            MockJob {
+                contents: vec![0, 0],
+            },
+            MockJob { contents: vec![0] },
+            MockJob { contents: vec![] },
+        ];
+
+        // a single thread, so the failing job is executed last and doesn't take
+        // the worker out of the pool for the other jobs
+        let (res, stats) = rayon::ThreadPoolBuilder::new()
+            .num_threads(1)
+            .build()
+            .unwrap()
+            .install(|| {
+                manager.run_with_stats::<StatelessWorker, MockJob, Count, Count>(jobs.into_iter())
+            });
+
+        assert_eq!(res.unwrap().0, 3);
+        assert_eq!(stats.tasks_completed, 2);
+        assert_eq!(stats.tasks_failed, 1);
+        assert_eq!(stats.dropped_tasks, vec![2]);
+    }
+
+    #[test]
+    fn cancel() {
+        let (worker, handle) = spawn_worker();
+        let cancel = Arc::new(AtomicBool::new(false));
+        let manager = Manager::new(&[worker]).with_cancel_token(Arc::clone(&cancel));
+
+        // never ends unless the job is cancelled
+        let jobs = (0..).map(move |i| {
+            if i == 5 {
+                cancel.store(true, Ordering::SeqCst);
+            }
+
+            MockJob { contents: vec![0] }
+        });
+
+        let res: Option<Count> = manager.run::<StatelessWorker, MockJob, Count, Count>(jobs);
+
+        assert!(res.is_none());
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn resume_from_checkpoint() {
+        let path = crate::gen_temp_path().join("checkpoint");
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+
+        let jobs = || {
+            (0..10).map(|i| MockJob {
+                contents: vec![0; i],
+            })
+        };
+
+        // the job crashes after the first five inputs
+        let (worker, handle) = spawn_worker();
+        let cancel = Arc::new(AtomicBool::new(false));
+        let manager = Manager::new(&[worker])
+            .resume_from(&path)
+            .with_checkpoint_interval(Duration::ZERO)
+            .with_cancel_token(Arc::clone(&cancel));
+
+        // a single thread, so each input is done before the next is taken from the iterator
+        let res: Option<Count> = rayon::ThreadPoolBuilder::new()
+            .num_threads(1)
+            .build()
+            .unwrap()
+            .install(|| {
+                manager.run_resumable::<StatelessWorker, MockJob, Count, Count>(
+                    jobs().enumerate().map(move |(i, job)| {
+                        if i == 5 {
+                            cancel.store(true, Ordering::SeqCst);
+                        }
+                        job
+                    }),
+                )
+            });
+        handle.join().unwrap();
+
+        assert!(res.is_none());
+
+        let checkpoint: Checkpoint<Count> = Checkpoint::load(&path).unwrap().unwrap();
+        assert_eq!(checkpoint.completed, (0..5).collect::<HashSet<_>>());
+        assert_eq!(checkpoint.acc.unwrap().0, 10);
+
+        let (worker, handle) = spawn_worker();
+        let res: Option<Count> = Manager::new(&[worker])
+            .resume_from(&path)
+            .run_resumable::<StatelessWorker, MockJob, Count, Count>(jobs());
+        handle.join().unwrap();
+
+        assert_eq!(res.unwrap().0, 45);
+    }
+
+    fn unused_addr() -> SocketAddr {
+        TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+    }
+
+    /// Configurations using the self-signed certificate for 127.0.0.1 in `testcases/tls`.
+    fn tls_configs() -> (Arc<rustls::ServerConfig>, Arc<ClientConfig>) {
+        let certs: Vec<_> =
+            rustls_pemfile::certs(&mut &include_bytes!("../../testcases/tls/cert.pem")[..])
+                .unwrap()
+                .into_iter()
+                .map(rustls::Certificate)
+                .collect();
+        let key = rustls_pemfile::pkcs8_private_keys(
+            &mut &include_bytes!("../../testcases/tls/key.pem")[..],
+        )
+        .unwrap()
+        .remove(0);
+
+        let server = rustls::ServerConfig::builder()
+            .with_safe_defaults()
+            .with_no_client_auth()
+            .with_single_cert(certs.clone(), rustls::PrivateKey(key))
+            .unwrap();
+
+        let mut roots = rustls::RootCertStore::empty();
+        roots.add(&certs[0]).unwrap();
+        let client = ClientConfig::builder()
+            .with_safe_defaults()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+
+        (Arc::new(server), Arc::new(client))
+    }
+
+    #[test]
+    fn tls() {
+        let (server_config, client_config) = tls_configs();
+
+        let addr = unused_addr();
+        let handle = std::thread::spawn(move || {
+            StatelessWorker::default()
+                .run_tls::<MockJob, Count>(addr, server_config)
+                .unwrap();
+        });
+
+        // the worker drops connections that fail the handshake, like the ones below that
+        // wait for it to listen and the ones of a manager without TLS
+        while TcpStream::connect(addr).is_err() {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+
+        let res = Manager::new(&[addr])
+            .with_retries(0)
+            .try_map::<StatelessWorker, MockJob, Count>(&MockJob { contents: vec![0] });
+        assert!(res.is_err());
+
+        let res: Option<Count> = Manager::new(&[addr])
+            .with_tls(client_config)
+            .run::<StatelessWorker, MockJob, Count, Count>(
+                vec![MockJob {
+                    contents: vec![0, 1, 0],
+                }]
+                .into_iter(),
+            );
+
+        assert_eq!(res.unwrap().0, 2);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn async_worker_tls() {
+        let (server_config, client_config) = tls_configs();
+        let (worker, handle) = spawn_async_worker::<MockJob>(Some(server_config));
+
+        let manager = Manager::new(&[worker])
+            .with_tls(client_config)
+            .with_tasks_per_worker(4);
+
+        let res: Option<Count> =
+            manager.run::<StatelessWorker, MockJob, Count, Count>((0..10).map(|_| MockJob {
+                contents: vec![0, 1, 0],
+            }));
+
+        assert_eq!(res.unwrap().0, 20);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn dead_worker_skipped() {
+        let (alive, handle) = spawn_worker();
+
+        let manager = Manager::new(&[unused_addr(), alive]).with_retries(0);
+
+        let res: Option<Count> = manager.run::<StatelessWorker, MockJob, Count, Count>(
+            vec![
+                MockJob {
+                    contents: vec![0, 1],
+                },
+                MockJob {
+                    contents: vec![0, 0, 0],
+                },
+            ]
+            .into_iter(),
+        );
+
+        assert_eq!(res.unwrap().0, 4);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn all_workers_dead() {
+        let manager = Manager::new(&[unused_addr(), unused_addr()]);
+
+        let start = Instant::now();
+        let res =
+            manager.try_map::<StatelessWorker, MockJob, Count>(&MockJob { contents: vec![0] });
+
+        assert!(matches!(res, Err(Error::NoAvailableWorker)));
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn streaming_results() {
+        let (worker, handle) = spawn_worker();
+        let manager = Manager::new(&[worker]);
+
+        let jobs: Vec<_> = (0..5)
+            .map(|i| MockJob {
+                contents: vec![0; i],
+            })
+            .collect();
+
+        let mut res: Vec<_> = manager
+            .run_streaming::<StatelessWorker, MockJob, Count>(jobs.into_iter())
+            .map(|count| count.0)
+            .collect();
+        res.sort_unstable();
+
+        assert_eq!(res, vec![0, 1, 2, 3, 4]);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn streaming_best_effort() {
+        let manager = Manager::new(&[spawn_picky_worker()])
+            .with_retries(0)
+            .with_fail_tolerance(FailPolicy::BestEffort { max_failures: 1 });
+
+        // the failing job is dropped instead of panicking
+        let res: Vec<_> = manager
+            .run_streaming::<StatelessWorker, MockJob, Count>(
+                vec![MockJob { contents: vec![] }].into_iter(),
+            )
+            .collect();
+
+        assert!(res.is_empty());
+    }
+
+    #[test]
+    fn streaming_cancel() {
+        let (worker, handle) = spawn_worker();
+        let cancel = Arc::new(AtomicBool::new(false));
+        let manager = Manager::new(&[worker]).with_cancel_token(Arc::clone(&cancel));
+
+        // never ends unless the job is cancelled
+        let jobs = (0..).map(move |i| {
+            if i == 5 {
+                cancel.store(true, Ordering::SeqCst);
+            }
+
+            MockJob { contents: vec![0] }
+        });
+
+        let res = manager
+            .run_streaming::<StatelessWorker, MockJob, Count>(jobs)
+            .count();
+
+        assert!(res <= 5);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn json_format() {
+        let (worker, handle) = spawn_worker();
+        let manager = Manager::new(&[worker]).with_format(SerFormat::Json);
+
+        let res: Option<Count> = manager.run::<StatelessWorker, MockJob, Count, Count>(
+            vec![
+                MockJob {
+                    contents: vec![0, 1, 0],
+                },
+                MockJob {
+                    contents: vec![2, 0],
+                },
+            ]
+            .into_iter(),
+        );
+
+        assert_eq!(res.unwrap().0, 3);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn compression() {
+        let jobs = || {
+            (0..10).map(|i| MockJob {
+                contents: (0..1000).map(|j| (i + j) % 3).collect(),
+            })
+        };
+
+        let (worker, handle) = spawn_worker();
+        let uncompressed: Option<Count> =
+            Manager::new(&[worker]).run::<StatelessWorker, MockJob, Count, Count>(jobs());
+        handle.join().unwrap();
+
+        let (worker, handle) = spawn_worker();
+        let compressed: Option<Count> = Manager::new(&[worker])
+            .with_compression(6)
+            .run::<StatelessWorker, MockJob, Count, Count>(jobs());
+        handle.join().unwrap();
+
+        assert_eq!(uncompressed.unwrap().0, compressed.unwrap().0);
+    }
+
+    #[test]
+    fn frame_roundtrip() {
+        let payload: Vec<u8> = (0..10_000).map(|i| (i % 7) as u8).collect();
+
+        let frame = encode_frame(payload.clone(), None).unwrap();
+        assert_eq!(decode_frame(&frame).unwrap(), (payload.clone(), None));
+
+        let frame = encode_frame(payload.clone(), Some(6)).unwrap();
+        assert!(frame.len() < payload.len());
+        assert_eq!(decode_frame(&frame).unwrap(), (payload, Some(6)));
     }
 }
@@ -14,14 +14,20 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
+use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression};
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::io::{Read, Write};
 use std::net::SocketAddr;
 
+mod async_worker;
 mod manager;
+mod tls;
 mod worker;
 
-pub use manager::Manager;
+pub use async_worker::AsyncWorker;
+pub use manager::{FailPolicy, JobStats, Manager};
 use thiserror::Error;
+pub use worker::StatefulWorker;
 pub use worker::StatelessWorker;
 pub use worker::Worker;
 
@@ -35,11 +41,35 @@ pub enum Error {
     #[error("error while serializing/deserializing to/from bytes")]
     Serialization(#[from] bincode::Error),
 
+    #[error("error while serializing/deserializing to/from json")]
+    Json(#[from] serde_json::Error),
+
+    #[error("unknown serialization format")]
+    UnknownFormat(u8),
+
     #[error("could not get a working worker")]
     NoAvailableWorker,
 
     #[error("did not get a reponse")]
     NoResponse,
+
+    #[error("worker did not respond within the task timeout")]
+    TaskTimeout,
+
+    #[error("the job was cancelled")]
+    Cancelled,
+
+    #[error(
+        "the manager uses protocol version {manager}, but the worker uses version {worker}. \
+        Make sure the manager and the workers are built from the same version"
+    )]
+    ProtocolMismatch { manager: u32, worker: u32 },
+
+    #[error("the job failed on the worker: {0}")]
+    JobFailed(String),
+
+    #[error("TLS handshake failed: {0}")]
+    Tls(String),
 }
 
 pub trait Map<W, T>
@@ -61,7 +91,7 @@ pub trait MapReduce<W, I, O1, O2>
 where
     Self: Sized + Iterator<Item = I> + Send,
     W: Worker,
-    I: Map<W, O1>,
+    I: Map<W, O1> + 'static,
     O1: Serialize + DeserializeOwned + Send,
     O2: From<O1> + Reduce<O1> + Send + Reduce<O2>,
 {
@@ -76,14 +106,133 @@ impl<W, I, O1, O2, T> MapReduce<W, I, O1, O2> for T
 where
     W: Worker,
     T: Iterator<Item = I> + Sized + Send,
-    I: Map<W, O1>,
+    I: Map<W, O1> + 'static,
     O1: Serialize + DeserializeOwned + Send,
     O2: From<O1> + Reduce<O1> + Send + Reduce<O2>,
 {
 }
 
+/// Serialization format of the jobs and results sent between the manager and the workers.
+/// Each message from the manager is prefixed with its format, and the worker responds in
+/// the same format, so workers don't need to be configured.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SerFormat {
+    #[default]
+    Bincode,
+    Json,
+}
+
+impl SerFormat {
+    fn tag(&self) -> u8 {
+        match self {
+            SerFormat::Bincode => 0,
+            SerFormat::Json => 1,
+        }
+    }
+
+    fn from_tag(tag: u8) -> Result<Self> {
+        match tag {
+            0 => Ok(SerFormat::Bincode),
+            1 => Ok(SerFormat::Json),
+            _ => Err(Error::UnknownFormat(tag)),
+        }
+    }
+
+    fn serialize<T: Serialize>(&self, value: &T) -> Result<Vec<u8>> {
+        match self {
+            SerFormat::Bincode => Ok(bincode::serialize(value)?),
+            SerFormat::Json => Ok(serde_json::to_vec(value)?),
+        }
+    }
+
+    fn deserialize<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T> {
+        match self {
+            SerFormat::Bincode => Ok(bincode::deserialize(bytes)?),
+            SerFormat::Json => Ok(serde_json::from_slice(bytes)?),
+        }
+    }
+}
+
+/// Prefixes the payload with a one-byte header and compresses it if `compression` is set.
+/// The header is 0 for uncompressed frames and the compression level plus one otherwise,
+/// so the receiver can respond with the same level.
+///
+/// Compression trades CPU time on both ends for less data on the wire, so it only pays off
+/// for large payloads on a slow network.
+fn encode_frame(payload: Vec<u8>, compression: Option<u32>) -> Result<Vec<u8>> {
+    match compression {
+        None => {
+            let mut frame = Vec::with_capacity(payload.len() + 1);
+            frame.push(0);
+            frame.extend(payload);
+            Ok(frame)
+        }
+        Some(level) => {
+            let level = level.min(9);
+            let mut encoder = ZlibEncoder::new(vec![level as u8 + 1], Compression::new(level));
+            encoder.write_all(&payload)?;
+            Ok(encoder.finish()?)
+        }
+    }
+}
+
+/// Header of the frame a worker responds with instead of a result when it can't handle
+/// the request. Compression levels are at most 9, so `encode_frame` never uses it.
+const ERROR_FRAME: u8 = u8::MAX;
+
+/// Payload of an error frame. It is always serialized with bincode, so the manager can
+/// read it regardless of the format and protocol version of the request.
+#[derive(Serialize, Deserialize, Debug)]
+enum ErrorReply {
+    ProtocolMismatch {
+        manager: u32,
+        worker: u32,
+    },
+    /// The job panicked while it was executed.
+    JobFailed(String),
+}
+
+impl From<ErrorReply> for Error {
+    fn from(reply: ErrorReply) -> Self {
+        match reply {
+            ErrorReply::ProtocolMismatch { manager, worker } => {
+                Error::ProtocolMismatch { manager, worker }
+            }
+            ErrorReply::JobFailed(msg) => Error::JobFailed(msg),
+        }
+    }
+}
+
+fn encode_error_frame(reply: &ErrorReply) -> Result<Vec<u8>> {
+    let mut frame = vec![ERROR_FRAME];
+    frame.extend(bincode::serialize(reply)?);
+    Ok(frame)
+}
+
+/// Returns the payload of a frame created by `encode_frame` together with the
+/// compression level it was sent with. Error frames are returned as the error they contain.
+fn decode_frame(frame: &[u8]) -> Result<(Vec<u8>, Option<u32>)> {
+    match frame.split_first() {
+        Some((&ERROR_FRAME, reply)) => Err(bincode::deserialize::<ErrorReply>(reply)?.into()),
+        Some((0, payload)) => Ok((payload.to_vec(), None)),
+        Some((header, compressed)) => {
+            let mut payload = Vec::new();
+            ZlibDecoder::new(compressed).read_to_end(&mut payload)?;
+            Ok((payload, Some(u32::from(*header) - 1)))
+        }
+        None => Err(Error::NoResponse),
+    }
+}
+
+/// Version of the messages sent between the manager and the workers. Every request starts
+/// with the version, so a worker can reject requests it might not be able to decode.
+/// Must be incremented whenever the format of the messages (including `Task`) changes.
+const PROTOCOL_VERSION: u32 = 2;
+
 #[derive(Serialize, Deserialize, Debug)]
 enum Task<T> {
     Job(T),
     AllFinished,
+    /// Health check. The worker responds with an empty message.
+    Ping,
 }
@@ -0,0 +1,50 @@
+use std::{net::TcpStream, sync::Arc, time::Duration};
+
+use rustls::{
+    ClientConfig, ClientConnection, ConnectionCommon, ServerConfig, ServerConnection, ServerName,
+    StreamOwned,
+};
+
+use super::{Error, Result};
+
+/// Time a peer gets to complete the TLS handshake. Connections that stall during the
+/// handshake are dropped afterwards, so they can't block a worker.
+pub(super) const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub(super) type ServerStream = StreamOwned<ServerConnection, TcpStream>;
+pub(super) type ClientStream = StreamOwned<ClientConnection, TcpStream>;
+
+/// Performs the server side of the handshake on an accepted connection.
+pub(super) fn accept(mut socket: TcpStream, config: Arc<ServerConfig>) -> Result<ServerStream> {
+    let mut conn = ServerConnection::new(config).map_err(|err| Error::Tls(err.to_string()))?;
+    handshake(&mut conn, &mut socket)?;
+
+    Ok(StreamOwned::new(conn, socket))
+}
+
+/// Performs the client side of the handshake. The certificate of the peer has to be
+/// valid for its ip address.
+pub(super) fn connect(mut socket: TcpStream, config: Arc<ClientConfig>) -> Result<ClientStream> {
+    let name = ServerName::IpAddress(socket.peer_addr()?.ip());
+    let mut conn =
+        ClientConnection::new(config, name).map_err(|err| Error::Tls(err.to_string()))?;
+    handshake(&mut conn, &mut socket)?;
+
+    Ok(StreamOwned::new(conn, socket))
+}
+
+/// Completes the handshake within `HANDSHAKE_TIMEOUT`. The read timeout of the socket
+/// is restored afterwards.
+fn handshake<D>(conn: &mut ConnectionCommon<D>, socket: &mut TcpStream) -> Result<()> {
+    let timeout = socket.read_timeout()?;
+    socket.set_read_timeout(Some(HANDSHAKE_TIMEOUT))?;
+
+    while conn.is_handshaking() {
+        conn.complete_io(socket)
+            .map_err(|err| Error::Tls(err.to_string()))?;
+    }
+
+    socket.set_read_timeout(timeout)?;
+
+    Ok(())
+}
@@ -1,17 +1,83 @@
 use std::{
     io::{Read, Write},
-    net::{SocketAddr, TcpListener},
+    net::{SocketAddr, TcpListener, TcpStream},
+    sync::Arc,
 };
 
 use crate::mapreduce::manager::{BUF_SIZE, END_OF_MESSAGE};
 
-use super::{Error, Map, Result, Task};
+use super::{
+    decode_frame, encode_error_frame, encode_frame, tls, Error, ErrorReply, Map, Result, SerFormat,
+    Task, PROTOCOL_VERSION,
+};
+use once_cell::sync::OnceCell;
+use rustls::ServerConfig;
 use serde::{de::DeserializeOwned, Serialize};
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 
 #[derive(Default)]
 pub struct StatelessWorker {}
 
+/// Worker holding state that is expensive to create, like a loaded model or a database
+/// connection. The state is created by `init` when it is first needed and then shared by
+/// all jobs the worker executes.
+pub struct StatefulWorker<S> {
+    init: Box<dyn Fn() -> S + Send + Sync>,
+    state: OnceCell<S>,
+}
+
+impl<S> StatefulWorker<S> {
+    pub fn new<F>(init: F) -> Self
+    where
+        F: Fn() -> S + Send + Sync + 'static,
+    {
+        Self {
+            init: Box::new(init),
+            state: OnceCell::new(),
+        }
+    }
+
+    /// Creates the state right away instead of when the first job arrives.
+    pub fn initialized(self) -> Self {
+        self.state();
+        self
+    }
+
+    pub fn state(&self) -> &S {
+        self.state.get_or_init(|| (self.init)())
+    }
+}
+
+/// Decodes a request from the manager (without the end of message marker) into the task,
+/// the format it was serialized with and the compression level the response should use.
+/// Requests from a manager with another protocol version are rejected before the task is decoded.
+pub(super) fn decode_request<I>(bytes: &[u8]) -> Result<(Task<I>, SerFormat, Option<u32>)>
+where
+    I: DeserializeOwned,
+{
+    if bytes.len() < 4 {
+        return Err(Error::NoResponse);
+    }
+
+    let (version, bytes) = bytes.split_at(4);
+    let version = u32::from_le_bytes(version.try_into().unwrap());
+
+    if version != PROTOCOL_VERSION {
+        return Err(Error::ProtocolMismatch {
+            manager: version,
+            worker: PROTOCOL_VERSION,
+        });
+    }
+
+    let (format, frame) = match bytes.split_first() {
+        Some((tag, frame)) => (SerFormat::from_tag(*tag)?, frame),
+        None => return Err(Error::NoResponse),
+    };
+    let (payload, compression) = decode_frame(frame)?;
+
+    Ok((format.deserialize(&payload)?, format, compression))
+}
+
 pub enum State {
     Continue,
     Finished,
@@ -28,29 +94,46 @@ pub trait Worker {
         let mut buf = [0; BUF_SIZE];
         let mut bytes = Vec::new();
         loop {
-            if let Ok(size) = stream.read(&mut buf) {
-                debug!("read {:?} bytes", size);
-                if size == 0 && bytes.is_empty() {
-                    return Err(Error::NoResponse);
-                }
-
-                bytes.extend_from_slice(&buf[..size]);
-
-                if bytes.len() >= END_OF_MESSAGE.len()
-                    && bytes[bytes.len() - END_OF_MESSAGE.len()..] == END_OF_MESSAGE
-                {
-                    break;
-                }
+            let size = stream.read(&mut buf)?;
+            debug!("read {:?} bytes", size);
+
+            // the manager closed the connection before the request was complete
+            if size == 0 {
+                return Err(Error::NoResponse);
+            }
+
+            bytes.extend_from_slice(&buf[..size]);
+
+            if bytes.len() >= END_OF_MESSAGE.len()
+                && bytes[bytes.len() - END_OF_MESSAGE.len()..] == END_OF_MESSAGE
+            {
+                break;
             }
         }
 
         bytes = bytes[..bytes.len() - END_OF_MESSAGE.len()].to_vec();
 
-        match bincode::deserialize::<Task<I>>(&bytes)? {
+        let (task, format, compression) = match decode_request::<I>(&bytes) {
+            Ok(request) => request,
+            Err(Error::ProtocolMismatch { manager, worker }) => {
+                warn!(
+                    "rejected request from manager with protocol version {} (worker uses {})",
+                    manager, worker
+                );
+                let reply = ErrorReply::ProtocolMismatch { manager, worker };
+                stream.write_all(&encode_error_frame(&reply)?)?;
+                stream.write_all(&END_OF_MESSAGE)?;
+
+                return Ok(State::Continue);
+            }
+            Err(err) => return Err(err),
+        };
+
+        match task {
             Task::Job(job) => {
                 debug!("received job");
                 let res = job.map(self);
-                let bytes = bincode::serialize(&res)?;
+                let bytes = encode_frame(format.serialize(&res)?, compression)?;
                 debug!("serialized result into {} bytes", bytes.len());
                 stream.write_all(&bytes)?;
                 stream.write_all(&END_OF_MESSAGE)?;
@@ -59,37 +142,95 @@ pub trait Worker {
                 debug!("shutting down");
                 return Ok(State::Finished);
             }
+            Task::Ping => {
+                debug!("received ping");
+                stream.write_all(&END_OF_MESSAGE)?;
+            }
         };
 
         Ok(State::Continue)
     }
 
+    /// Handles the connections on `addr` one at a time until the manager signals that all
+    /// jobs are finished. A failing connection (e.g. one the manager gave up on because of
+    /// a timeout) is logged, and the worker continues with the next connection.
     fn run<I, O>(&self, addr: SocketAddr) -> Result<()>
     where
         Self: Sized,
         I: Map<Self, O>,
         O: Serialize + DeserializeOwned + Send,
     {
-        let listener = TcpListener::bind(addr)?;
-        info!("worker listening on: {:}", addr);
+        serve::<Self, I, O, _, _>(self, addr, Ok)
+    }
+
+    /// Same as `run`, but the connections are encrypted with TLS, so the manager has to be
+    /// configured with `Manager::with_tls`. Connections are handled one at a time, so each
+    /// handshake has to complete within `tls::HANDSHAKE_TIMEOUT`. Connections that fail
+    /// the handshake are logged and dropped.
+    fn run_tls<I, O>(&self, addr: SocketAddr, config: Arc<ServerConfig>) -> Result<()>
+    where
+        Self: Sized,
+        I: Map<Self, O>,
+        O: Serialize + DeserializeOwned + Send,
+    {
+        serve::<Self, I, O, _, _>(self, addr, |socket| {
+            tls::accept(socket, Arc::clone(&config))
+        })
+    }
+}
 
-        loop {
-            let (socket, _) = listener.accept()?;
-            debug!("received connection");
-            match self.run_stream::<Self, I, O, _>(socket)? {
-                State::Finished => break,
-                State::Continue => {}
+/// Accepts the connections on `addr` for `Worker::run`. `wrap` turns each accepted socket
+/// into the stream the request is read from and the response is written to.
+fn serve<W, I, O, S, F>(worker: &W, addr: SocketAddr, wrap: F) -> Result<()>
+where
+    W: Worker,
+    I: Map<W, O>,
+    O: Serialize + DeserializeOwned + Send,
+    S: Read + Write + Unpin,
+    F: Fn(TcpStream) -> Result<S>,
+{
+    let listener = TcpListener::bind(addr)?;
+    info!("worker listening on: {:}", addr);
+
+    loop {
+        let socket = match listener.accept() {
+            Ok((socket, _)) => socket,
+            Err(err) => {
+                warn!("failed to accept connection: {:?}", err);
+                continue;
             }
-        }
+        };
+        debug!("received connection");
+
+        let stream = match wrap(socket) {
+            Ok(stream) => stream,
+            Err(err) => {
+                warn!("failed to set up connection: {:?}", err);
+                continue;
+            }
+        };
 
-        Ok(())
+        match worker.run_stream::<W, I, O, _>(stream) {
+            Ok(State::Finished) => break,
+            Ok(State::Continue) => {}
+            Err(err) => warn!("failed to handle connection: {:?}", err),
+        }
     }
+
+    Ok(())
 }
 
 impl Worker for StatelessWorker {}
 
+impl<S> Worker for StatefulWorker<S> {}
+
 #[cfg(test)]
 mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
     use serde::Deserialize;
 
     use super::*;
@@ -152,7 +293,15 @@ mod tests {
     #[test]
     fn execute() {
         let contents = vec![1, 2, 0, 1, 0, 1, 0];
-        let job = bincode::serialize(&Task::Job(MockJob { contents })).unwrap();
+        let mut job = PROTOCOL_VERSION.to_le_bytes().to_vec();
+        job.push(SerFormat::Bincode.tag());
+        job.extend(
+            encode_frame(
+                bincode::serialize(&Task::Job(MockJob { contents })).unwrap(),
+                None,
+            )
+            .unwrap(),
+        );
 
         let mut stream = MockTcpStream::new(job);
         StatelessWorker::default()
@@ -160,8 +309,107 @@ mod tests {
             .expect("worker failed");
 
         let result_bytes = &stream.result[..stream.result.len() - END_OF_MESSAGE.len()];
-        let res: Count = bincode::deserialize(result_bytes).unwrap();
+        let (payload, _) = decode_frame(result_bytes).unwrap();
+        let res: Count = bincode::deserialize(&payload).unwrap();
 
         assert_eq!(res.0, 3);
     }
+
+    #[test]
+    fn execute_json() {
+        let contents = vec![0, 2, 0];
+        let mut job = PROTOCOL_VERSION.to_le_bytes().to_vec();
+        job.push(SerFormat::Json.tag());
+        job.extend(
+            encode_frame(
+                serde_json::to_vec(&Task::Job(MockJob { contents })).unwrap(),
+                Some(3),
+            )
+            .unwrap(),
+        );
+
+        let mut stream = MockTcpStream::new(job);
+        StatelessWorker::default()
+            .run_stream::<StatelessWorker, MockJob, _, _>(&mut stream)
+            .expect("worker failed");
+
+        let result_bytes = &stream.result[..stream.result.len() - END_OF_MESSAGE.len()];
+        let (payload, compression) = decode_frame(result_bytes).unwrap();
+        let res: Count = serde_json::from_slice(&payload).unwrap();
+
+        assert_eq!(compression, Some(3));
+
+        assert_eq!(res.0, 2);
+    }
+
+    #[derive(Serialize, Deserialize, Debug)]
+    struct CountingJob;
+
+    impl Map<StatefulWorker<AtomicUsize>, Count> for CountingJob {
+        fn map(self, worker: &StatefulWorker<AtomicUsize>) -> Count {
+            Count(worker.state().fetch_add(1, Ordering::SeqCst) + 1)
+        }
+    }
+
+    #[test]
+    fn stateful() {
+        let num_inits = Arc::new(AtomicUsize::new(0));
+        let worker = StatefulWorker::new({
+            let num_inits = Arc::clone(&num_inits);
+            move || {
+                num_inits.fetch_add(1, Ordering::SeqCst);
+                AtomicUsize::new(0)
+            }
+        });
+
+        for expected in 1..=3 {
+            let mut job = PROTOCOL_VERSION.to_le_bytes().to_vec();
+            job.push(SerFormat::Bincode.tag());
+            job.extend(
+                encode_frame(bincode::serialize(&Task::Job(CountingJob)).unwrap(), None).unwrap(),
+            );
+
+            let mut stream = MockTcpStream::new(job);
+            worker
+                .run_stream::<StatefulWorker<AtomicUsize>, CountingJob, _, _>(&mut stream)
+                .expect("worker failed");
+
+            let result_bytes = &stream.result[..stream.result.len() - END_OF_MESSAGE.len()];
+            let (payload, _) = decode_frame(result_bytes).unwrap();
+            let res: Count = bincode::deserialize(&payload).unwrap();
+
+            assert_eq!(res.0, expected);
+        }
+
+        assert_eq!(num_inits.load(Ordering::SeqCst), 1);
+        assert_eq!(worker.state().load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn protocol_mismatch() {
+        let mut job = (PROTOCOL_VERSION + 1).to_le_bytes().to_vec();
+        job.push(SerFormat::Bincode.tag());
+        job.extend(
+            encode_frame(
+                bincode::serialize(&Task::Job(MockJob { contents: vec![0] })).unwrap(),
+                None,
+            )
+            .unwrap(),
+        );
+
+        let mut stream = MockTcpStream::new(job);
+        let res = StatelessWorker::default()
+            .run_stream::<StatelessWorker, MockJob, Count, _>(&mut stream);
+        assert!(matches!(res, Ok(State::Continue)));
+
+        let result_bytes = &stream.result[..stream.result.len() - END_OF_MESSAGE.len()];
+        match decode_frame(result_bytes) {
+            Err(err @ Error::ProtocolMismatch { manager, worker }) => {
+                assert_eq!(manager, PROTOCOL_VERSION + 1);
+                assert_eq!(worker, PROTOCOL_VERSION);
+                assert!(err.to_string().contains("protocol version"));
+            }
+            _ => panic!("expected a protocol mismatch"),
+        }
+    }
 }
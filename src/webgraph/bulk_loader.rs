@@ -0,0 +1,305 @@
+// Cuely is an open source web search engine.
+// Copyright (C) 2022 Cuely ApS
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Builds a graph from a large number of edges without a read-modify-write of the
+//! adjacency blocks for every edge. The edges are sorted by the node they are stored
+//! under, spilling sorted runs to disk when they don't fit in memory, and every block
+//! is written once when the loader is finished.
+
+use std::{
+    cmp::Reverse,
+    collections::BinaryHeap,
+    fs,
+    io::{BufReader, BufWriter, Seek, Write},
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::{graph_store::GraphStore, Node, NodeID, Store, Webgraph};
+
+/// Memory used for buffered edges before they are spilled to disk.
+const DEFAULT_MEMORY_BUDGET: usize = 1024 * 1024 * 1024;
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+struct SortedEdge {
+    /// The node the edge is stored under in the adjacency.
+    node: NodeID,
+    /// Position of the edge in the insertion order, so the edges of each node keep their order.
+    seq: u64,
+    other: NodeID,
+    label: String,
+}
+
+impl SortedEdge {
+    fn size(&self) -> usize {
+        std::mem::size_of::<SortedEdge>() + self.label.len()
+    }
+}
+
+/// External sort of the edges of one adjacency.
+#[derive(Default)]
+struct EdgeSorter {
+    edges: Vec<SortedEdge>,
+    /// Spilled runs and the number of edges in each.
+    runs: Vec<(fs::File, usize)>,
+}
+
+impl EdgeSorter {
+    fn push(&mut self, edge: SortedEdge) {
+        self.edges.push(edge);
+    }
+
+    /// Writes the buffered edges to a temporary file as a sorted run.
+    fn spill(&mut self) -> crate::Result<()> {
+        if self.edges.is_empty() {
+            return Ok(());
+        }
+
+        self.edges.sort_unstable();
+
+        let path = crate::gen_temp_path();
+        let mut writer = BufWriter::new(
+            fs::OpenOptions::new()
+                .create(true)
+                .read(true)
+                .write(true)
+                .truncate(true)
+                .open(&path)?,
+        );
+        // the file stays readable through the open handle after it is removed
+        fs::remove_file(&path)?;
+
+        for edge in &self.edges {
+            bincode::serialize_into(&mut writer, edge)?;
+        }
+        writer.flush()?;
+
+        let mut file = writer.into_inner().map_err(|err| err.into_error())?;
+        file.rewind()?;
+
+        self.runs.push((file, self.edges.len()));
+        self.edges = Vec::new();
+
+        Ok(())
+    }
+
+    /// All edges sorted by node and insertion order, merged from the spilled runs
+    /// and the edges still in memory.
+    fn into_sorted(mut self) -> impl Iterator<Item = SortedEdge> {
+        self.edges.sort_unstable();
+
+        let mut runs: Vec<Box<dyn Iterator<Item = SortedEdge>>> = self
+            .runs
+            .into_iter()
+            .map(|(file, len)| -> Box<dyn Iterator<Item = SortedEdge>> {
+                let mut reader = BufReader::new(file);
+                Box::new((0..len).map(move |_| {
+                    bincode::deserialize_from(&mut reader).expect("failed to read spilled edges")
+                }))
+            })
+            .collect();
+        runs.push(Box::new(self.edges.into_iter()));
+
+        let mut heap = BinaryHeap::new();
+        for (idx, run) in runs.iter_mut().enumerate() {
+            if let Some(edge) = run.next() {
+                heap.push(Reverse((edge, idx)));
+            }
+        }
+
+        std::iter::from_fn(move || {
+            let Reverse((edge, idx)) = heap.pop()?;
+
+            if let Some(next) = runs[idx].next() {
+                heap.push(Reverse((next, idx)));
+            }
+
+            Some(edge)
+        })
+    }
+}
+
+/// Sorts the edges of the adjacency and the reversed adjacency of a graph.
+#[derive(Default)]
+struct GraphLoader {
+    adjacency: EdgeSorter,
+    reversed_adjacency: EdgeSorter,
+}
+
+impl GraphLoader {
+    fn insert<S: Store>(
+        &mut self,
+        graph: &GraphStore<S>,
+        from: Node,
+        to: Node,
+        label: String,
+        seq: u64,
+    ) -> usize {
+        let from = graph.id_or_assign(from);
+        let to = graph.id_or_assign(to);
+
+        let edge = SortedEdge {
+            node: from,
+            seq,
+            other: to,
+            label: label.clone(),
+        };
+        let reversed_edge = SortedEdge {
+            node: to,
+            seq,
+            other: from,
+            label,
+        };
+        let size = edge.size() + reversed_edge.size();
+
+        self.adjacency.push(edge);
+        self.reversed_adjacency.push(reversed_edge);
+
+        size
+    }
+
+    fn spill(&mut self) -> crate::Result<()> {
+        self.adjacency.spill()?;
+        self.reversed_adjacency.spill()
+    }
+
+    /// Writes the sorted edges to `graph`. `on_new` is called with the endpoints of each
+    /// edge that wasn't in the graph already.
+    fn finish<S: Store, F: FnMut(NodeID, NodeID)>(self, graph: &GraphStore<S>, on_new: F) {
+        let into_triples = |edge: SortedEdge| (edge.node, edge.other, edge.label);
+
+        graph.insert_sorted(
+            self.adjacency.into_sorted().map(into_triples),
+            false,
+            on_new,
+        );
+        graph.insert_sorted(
+            self.reversed_adjacency.into_sorted().map(into_triples),
+            true,
+            |_, _| {},
+        );
+        graph.flush();
+    }
+}
+
+/// Inserts edges into a graph in bulk, see `Webgraph::bulk_loader`.
+pub struct BulkLoader<'a, S: Store> {
+    graph: &'a mut Webgraph<S>,
+    full_graph: GraphLoader,
+    host_graph: GraphLoader,
+    memory_budget: usize,
+    buffered: usize,
+    seq: u64,
+}
+
+impl<'a, S: Store> BulkLoader<'a, S> {
+    pub(super) fn new(graph: &'a mut Webgraph<S>) -> Self {
+        Self {
+            graph,
+            full_graph: GraphLoader::default(),
+            host_graph: GraphLoader::default(),
+            memory_budget: DEFAULT_MEMORY_BUDGET,
+            buffered: 0,
+            seq: 0,
+        }
+    }
+
+    /// Approximate number of bytes of edges kept in memory before they are spilled to
+    /// temporary files. Defaults to 1 GiB.
+    #[allow(unused)]
+    pub fn with_memory_budget(mut self, bytes: usize) -> Self {
+        self.memory_budget = bytes;
+        self
+    }
+
+    /// Same as `Webgraph::insert`, but the edge is only written to the stores when the
+    /// loader is finished. The ids of the nodes are assigned right away.
+    #[allow(unused)]
+    pub fn insert(&mut self, from: Node, to: Node, label: String) -> crate::Result<()> {
+        let seq = self.seq;
+        self.seq += 1;
+
+        if let Some(host_graph) = &self.graph.host_graph {
+            let page = &from;
+            let from = from.clone().into_host(&self.graph.collapse_subdomains);
+            let to = to.clone().into_host(&self.graph.collapse_subdomains);
+
+            if !self.graph.drop_self_loops || from != to {
+                // with the full graph, the link is only counted in `finish` once it is
+                // known to be new. Otherwise the evidence is small compared to the
+                // adjacency, so it is written right away
+                if self.graph.full_graph.is_none() {
+                    let max_samples = self.graph.host_edge_evidence.unwrap_or(0);
+                    host_graph
+                        .get()
+                        .add_evidence(&from, &to, &page.name, max_samples);
+                }
+
+                self.buffered +=
+                    self.host_graph
+                        .insert(host_graph.get(), from, to, label.clone(), seq);
+            }
+        }
+
+        if let Some(full_graph) = &self.graph.full_graph {
+            if !self.graph.drop_self_loops || from != to {
+                self.buffered += self
+                    .full_graph
+                    .insert(full_graph.get(), from, to, label, seq);
+            }
+        }
+
+        if self.buffered > self.memory_budget {
+            self.full_graph.spill()?;
+            self.host_graph.spill()?;
+            self.buffered = 0;
+        }
+
+        Ok(())
+    }
+
+    /// Writes all inserted edges to the graph. Every adjacency block is written once.
+    #[allow(unused)]
+    pub fn finish(self) {
+        let graph = self.graph;
+        let max_samples = graph.host_edge_evidence.unwrap_or(0);
+
+        if let Some(full_graph) = &graph.full_graph {
+            let full_graph = full_graph.get();
+
+            self.full_graph.finish(full_graph, |from, to| {
+                if let (Some(host_graph), Some(page), Some(to)) = (
+                    &graph.host_graph,
+                    full_graph.id2node(&from),
+                    full_graph.id2node(&to),
+                ) {
+                    let from = page.clone().into_host(&graph.collapse_subdomains);
+                    let to = to.into_host(&graph.collapse_subdomains);
+
+                    if !graph.drop_self_loops || from != to {
+                        host_graph
+                            .get()
+                            .add_evidence(&from, &to, &page.name, max_samples);
+                    }
+                }
+            });
+        }
+
+        if let Some(host_graph) = &graph.host_graph {
+            self.host_graph.finish(host_graph.get(), |_, _| {});
+        }
+    }
+}
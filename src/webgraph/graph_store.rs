@@ -15,31 +15,117 @@
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
 use std::{
-    collections::HashMap, hash::Hash, marker::PhantomData, ops::Div, path::Path, sync::Mutex,
+    collections::{HashMap, HashSet},
+    hash::Hash,
+    marker::PhantomData,
+    ops::Div,
+    path::Path,
+    sync::Mutex,
 };
 
 use lru::LruCache;
+use once_cell::sync::OnceCell;
 use serde::{de::DeserializeOwned, Serialize};
 
-use super::{Edge, EdgeIterator, Node, NodeID, Store, StoredEdge};
-use crate::kv::{rocksdb_store::RocksDbStore, Kv};
+use super::{Edge, EdgeEvidence, EdgeIterator, Node, NodeID, Store, StoredEdge, ValidationError};
+use crate::kv::{
+    memory_store::MemoryStore,
+    rocksdb_store::{RocksDbStore, RocksDbTuning},
+    Kv,
+};
 pub(crate) struct Adjacency {
     pub(crate) tree: BlockedCachedTree<NodeID, Vec<StoredEdge>>,
 }
 
 impl Adjacency {
-    fn insert(&mut self, from: NodeID, to: NodeID, label: String) {
+    /// Inserts the edge unless an identical edge (same endpoints and label) is already stored.
+    /// Returns whether the edge was inserted.
+    fn insert(&mut self, from: NodeID, to: NodeID, label: String) -> bool {
+        let mut inserted = false;
+
         self.tree.insert(from, &mut |block| {
-            block.entry(from).or_default().push(StoredEdge {
-                other: to,
-                label: label.clone(),
-            })
+            let edges = block.entry(from).or_default();
+
+            if !edges
+                .iter()
+                .any(|edge| edge.other == to && edge.label == label)
+            {
+                edges.push(StoredEdge {
+                    other: to,
+                    label: label.clone(),
+                });
+                inserted = true;
+            }
         });
+
+        inserted
+    }
+
+    /// Inserts edges that all belong to the block `block_id` with a single read and a
+    /// single write of the block. The edges are `(node, other, label)` triples, and
+    /// identical edges are skipped like in `insert`. `on_new` is called with the node and
+    /// the other node of each edge that is inserted.
+    fn insert_block<F>(
+        &mut self,
+        block_id: u64,
+        edges: Vec<(NodeID, NodeID, String)>,
+        on_new: &mut F,
+    ) where
+        F: FnMut(NodeID, NodeID),
+    {
+        let tree = &mut self.tree.inner;
+
+        // the block is removed from the cache so the cache never has a stale copy of it
+        let mut block = tree.take(&block_id).unwrap_or_default();
+
+        for (node, other, label) in edges {
+            let stored = block.entry(node).or_default();
+
+            if !stored
+                .iter()
+                .any(|edge| edge.other == other && edge.label == label)
+            {
+                stored.push(StoredEdge { other, label });
+                on_new(node, other);
+            }
+        }
+
+        tree.store.insert(block_id, block);
     }
 
     fn edges(&mut self, node: NodeID) -> Vec<StoredEdge> {
         self.tree.get(&node).cloned().unwrap_or_default()
     }
+
+    /// Removes all edges from `from` to `to` and returns how many there were.
+    fn remove_edges(&mut self, from: NodeID, to: NodeID) -> usize {
+        let mut removed = 0;
+
+        self.tree.update(&from, &mut |block| {
+            if let Some(edges) = block.get_mut(&from) {
+                let len = edges.len();
+                edges.retain(|edge| edge.other != to);
+                removed = len - edges.len();
+
+                if edges.is_empty() {
+                    block.remove(&from);
+                }
+            }
+        });
+
+        removed
+    }
+
+    fn remove_node(&mut self, node: NodeID) {
+        self.tree.update(&node, &mut |block| {
+            block.remove(&node);
+        });
+    }
+
+    /// Removes all blocks from the cache and the store.
+    fn clear(&mut self) {
+        self.tree.inner.clear();
+    }
 }
 
 pub(crate) struct BlockedCachedTree<K, V>
@@ -75,6 +161,19 @@ where
         self.inner.insert(block_id, new_block);
     }
 
+    /// Mutates the block containing `key`. Unlike `insert`, no new block is created
+    /// if it doesn't already exist.
+    fn update<B>(&mut self, key: &K, mutate_block: &mut B)
+    where
+        B: FnMut(&mut HashMap<K, V>),
+    {
+        let block_id = key.clone() / self.block_size;
+
+        if let Some(block) = self.inner.get_mut(&block_id) {
+            mutate_block(block);
+        }
+    }
+
     fn get(&mut self, key: &K) -> Option<&V> {
         let block_id = key.clone() / self.block_size;
         self.inner.get(&block_id).and_then(|block| block.get(key))
@@ -88,6 +187,11 @@ where
 {
     pub(crate) store: Box<dyn Kv<K, V> + Send + Sync>,
     pub(crate) cache: LruCache<K, V>,
+    /// Keys of the cached values that have changed since they were read from the store.
+    /// Only these are written back when they are evicted or flushed.
+    dirty: HashSet<K>,
+    /// Read-only trees never write to the store.
+    read_only: bool,
 }
 
 impl<K, V> CachedTree<K, V>
@@ -99,6 +203,19 @@ where
         Self {
             store,
             cache: LruCache::new(cache_size),
+            dirty: HashSet::new(),
+            read_only: false,
+        }
+    }
+
+    fn set_read_only(&mut self) {
+        self.read_only = true;
+        self.dirty.clear();
+    }
+
+    fn mark_dirty(&mut self, key: &K) {
+        if !self.read_only {
+            self.dirty.insert(key.clone());
         }
     }
 
@@ -107,7 +224,7 @@ where
             let val = self.store.get(key);
 
             if let Some(val) = val {
-                self.cache.put(key.clone(), val);
+                self.push_to_cache(key.clone(), val);
             }
         }
     }
@@ -117,24 +234,73 @@ where
         self.cache.get(key)
     }
 
+    /// Same as `get`, but the value is written back to the store once it is evicted
+    /// or flushed, since the caller may modify it.
     fn get_mut(&mut self, key: &K) -> Option<&mut V> {
         self.update_cache(key);
+
+        if self.cache.contains(key) {
+            self.mark_dirty(key);
+        }
+
         self.cache.get_mut(key)
     }
 
-    fn insert(&mut self, key: K, value: V) {
-        if self.cache.len() == self.cache.cap() {
-            if let Some((key, value)) = self.cache.pop_lru() {
-                self.store.insert(key, value);
+    /// Pushes to the cache and writes the evicted entry (if any) to the store if it
+    /// has changed, so modifications of cached values are never lost.
+    fn push_to_cache(&mut self, key: K, value: V) {
+        if let Some((evicted_key, evicted_value)) = self.cache.push(key.clone(), value) {
+            if evicted_key != key && self.dirty.remove(&evicted_key) {
+                self.store.insert(evicted_key, evicted_value);
             }
         }
+    }
+
+    fn insert(&mut self, key: K, value: V) {
+        self.mark_dirty(&key);
+        self.push_to_cache(key, value);
+    }
+
+    /// Removes the value from the cache, or reads it from the store if it isn't cached.
+    /// The caller is responsible for writing the value back.
+    fn take(&mut self, key: &K) -> Option<V> {
+        self.dirty.remove(key);
+
+        match self.cache.pop(key) {
+            Some(value) => Some(value),
+            None => self.store.get(key),
+        }
+    }
+
+    fn resize(&mut self, capacity: usize) {
+        self.flush();
+        self.cache.resize(capacity);
+    }
+
+    fn remove(&mut self, key: &K) {
+        self.dirty.remove(key);
+        self.cache.pop(key);
+        self.store.remove(key);
+    }
+
+    fn clear(&mut self) {
+        self.flush();
 
-        self.cache.push(key, value);
+        let keys: Vec<_> = self.store.iter().map(|(key, _)| key).collect();
+        for key in keys {
+            self.remove(&key);
+        }
     }
 
     fn flush(&mut self) {
-        for (key, value) in self.cache.iter() {
-            self.store.insert(key.clone(), value.clone());
+        if self.read_only {
+            return;
+        }
+
+        for key in self.dirty.drain() {
+            if let Some(value) = self.cache.peek(&key) {
+                self.store.insert(key, value.clone());
+            }
         }
 
         self.store.flush();
@@ -151,21 +317,115 @@ pub struct GraphStore<S> {
     pub(crate) node2id: Mutex<CachedTree<Node, NodeID>>,
     pub(crate) id2node: Mutex<BlockedCachedTree<NodeID, Node>>,
     pub(crate) meta: Mutex<CachedTree<String, u64>>,
+    /// The page links behind each edge, keyed by the endpoints of the edge.
+    /// Only recorded for host graphs.
+    pub(crate) evidence: Mutex<CachedTree<(Node, Node), EdgeEvidence>>,
     pub(crate) store: PhantomData<S>,
 }
 
+type BoxedKv<K, V> = Box<dyn Kv<K, V> + Send + Sync>;
+
 impl<S: Store> GraphStore<S> {
+    fn from_stores(
+        adjacency: BoxedKv<u64, HashMap<NodeID, Vec<StoredEdge>>>,
+        reversed_adjacency: BoxedKv<u64, HashMap<NodeID, Vec<StoredEdge>>>,
+        node2id: BoxedKv<Node, NodeID>,
+        id2node: BoxedKv<u64, HashMap<NodeID, Node>>,
+        meta: BoxedKv<String, u64>,
+        evidence: BoxedKv<(Node, Node), EdgeEvidence>,
+    ) -> Self {
+        GraphStore {
+            adjacency: Mutex::new(Adjacency {
+                tree: BlockedCachedTree {
+                    inner: CachedTree::new(adjacency, 10_000),
+                    block_size: 1_024,
+                },
+            }),
+            reversed_adjacency: Mutex::new(Adjacency {
+                tree: BlockedCachedTree {
+                    inner: CachedTree::new(reversed_adjacency, 10_000),
+                    block_size: 1_024,
+                },
+            }),
+            node2id: Mutex::new(CachedTree::new(node2id, 100_000)),
+            id2node: Mutex::new(BlockedCachedTree {
+                inner: CachedTree::new(id2node, 100_000),
+                block_size: 1_024,
+            }),
+            meta: Mutex::new(CachedTree::new(meta, 1_000)),
+            evidence: Mutex::new(CachedTree::new(evidence, 10_000)),
+            store: Default::default(),
+        }
+    }
+
     #[cfg(test)]
     pub(crate) fn temporary() -> GraphStore<S> {
         S::temporary()
     }
 
+    /// Inserts an edge between two ids without touching the node table.
+    #[cfg(test)]
+    pub(crate) fn insert_raw_edge(&mut self, from: NodeID, to: NodeID, label: String) {
+        self.adjacency
+            .lock()
+            .unwrap()
+            .insert(from, to, label.clone());
+        self.reversed_adjacency
+            .lock()
+            .unwrap()
+            .insert(to, from, label);
+    }
+
     pub fn open<P: AsRef<Path>>(path: P) -> Self {
         S::open(path)
     }
 
+    /// Opens the graph without ever writing to its stores, not even when it is flushed.
     pub fn open_read_only<P: AsRef<Path>>(path: P) -> Self {
-        S::open_read_only(path)
+        let graph = S::open_read_only(path);
+
+        graph.adjacency.lock().unwrap().tree.inner.set_read_only();
+        graph
+            .reversed_adjacency
+            .lock()
+            .unwrap()
+            .tree
+            .inner
+            .set_read_only();
+        graph.node2id.lock().unwrap().set_read_only();
+        graph.id2node.lock().unwrap().inner.set_read_only();
+        graph.meta.lock().unwrap().set_read_only();
+        graph.evidence.lock().unwrap().set_read_only();
+
+        graph
+    }
+
+    pub fn open_tuned<P: AsRef<Path>>(path: P, tuning: &RocksDbTuning) -> Self {
+        S::open_tuned(path, tuning)
+    }
+
+    /// Flushes the store and restores the normal settings if it was opened for bulk loading.
+    pub fn finish_bulk_load(&self) {
+        self.flush();
+
+        self.adjacency
+            .lock()
+            .unwrap()
+            .tree
+            .inner
+            .store
+            .finish_bulk_load();
+        self.reversed_adjacency
+            .lock()
+            .unwrap()
+            .tree
+            .inner
+            .store
+            .finish_bulk_load();
+        self.node2id.lock().unwrap().store.finish_bulk_load();
+        self.id2node.lock().unwrap().inner.store.finish_bulk_load();
+        self.meta.lock().unwrap().store.finish_bulk_load();
+        self.evidence.lock().unwrap().store.finish_bulk_load();
     }
 
     fn next_id(&self) -> NodeID {
@@ -199,7 +459,7 @@ impl<S: Store> GraphStore<S> {
         });
     }
 
-    fn id_or_assign(&self, node: Node) -> NodeID {
+    pub(crate) fn id_or_assign(&self, node: Node) -> NodeID {
         if let Some(id) = self.node2id.lock().unwrap().get(&node) {
             return *id;
         }
@@ -237,6 +497,31 @@ impl<S: Store> GraphStore<S> {
             .collect()
     }
 
+    /// Number of page links behind the edges from each node with an edge to `node`.
+    /// Edges without recorded page links, e.g. in the full graph, count once per label.
+    pub fn ingoing_edge_counts(&self, node: NodeID) -> HashMap<NodeID, usize> {
+        let mut counts = HashMap::new();
+
+        for edge in self.reversed_adjacency.lock().unwrap().edges(node) {
+            *counts.entry(edge.other).or_default() += 1;
+        }
+
+        if let Some(to) = self.id2node(&node) {
+            for (from, count) in counts.iter_mut() {
+                let links = self
+                    .id2node(from)
+                    .and_then(|from| self.evidence(&from, &to))
+                    .map_or(0, |evidence| evidence.count);
+
+                if links > 0 {
+                    *count = links as usize;
+                }
+            }
+        }
+
+        counts
+    }
+
     pub fn nodes(&self) -> impl Iterator<Item = NodeID> {
         self.node2id
             .lock()
@@ -247,11 +532,24 @@ impl<S: Store> GraphStore<S> {
             .into_iter()
     }
 
-    pub fn insert(&mut self, from: Node, to: Node, label: String) {
+    /// All nodes together with their ids. Nodes that haven't been flushed are not included.
+    pub fn node_ids(&self) -> impl Iterator<Item = (Node, NodeID)> {
+        self.node2id
+            .lock()
+            .unwrap()
+            .iter()
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    /// Inserts the edge and returns whether it is new, i.e. no edge with the same
+    /// endpoints and label was stored already.
+    pub fn insert(&mut self, from: Node, to: Node, label: String) -> bool {
         let from_id = self.id_or_assign(from);
         let to_id = self.id_or_assign(to);
 
-        self.adjacency
+        let inserted = self
+            .adjacency
             .lock()
             .unwrap()
             .insert(from_id, to_id, label.clone());
@@ -259,6 +557,217 @@ impl<S: Store> GraphStore<S> {
             .lock()
             .unwrap()
             .insert(to_id, from_id, label);
+
+        inserted
+    }
+
+    /// Inserts all edges grouped by the adjacency block they belong to, so consecutive
+    /// inserts hit the same cached block instead of evicting each other.
+    /// The resulting graph is identical to inserting the edges one by one, and like
+    /// `insert` it returns whether each edge is new, in the order of `edges`.
+    pub fn insert_batch(&mut self, edges: Vec<(Node, Node, String)>) -> Vec<bool> {
+        let edges: Vec<_> = edges
+            .into_iter()
+            .map(|(from, to, label)| {
+                let from_id = self.id_or_assign(from);
+                let to_id = self.id_or_assign(to);
+                (from_id, to_id, label)
+            })
+            .collect();
+
+        // the sorts are stable, so edges of the same node keep their insertion order
+        let mut inserted = vec![false; edges.len()];
+        let mut by_from: Vec<_> = edges.iter().enumerate().collect();
+        let mut adjacency = self.adjacency.lock().unwrap();
+        let block_size = adjacency.tree.block_size;
+        by_from.sort_by_key(|(_, (from_id, _, _))| from_id / block_size);
+
+        for (i, (from_id, to_id, label)) in by_from {
+            inserted[i] = adjacency.insert(*from_id, *to_id, label.clone());
+        }
+        drop(adjacency);
+
+        let mut by_to = edges;
+        let mut reversed_adjacency = self.reversed_adjacency.lock().unwrap();
+        let block_size = reversed_adjacency.tree.block_size;
+        by_to.sort_by_key(|(_, to_id, _)| to_id / block_size);
+
+        for (from_id, to_id, label) in by_to {
+            reversed_adjacency.insert(to_id, from_id, label);
+        }
+
+        inserted
+    }
+
+    /// Inserts `(node, other, label)` edges that are sorted by `node`, so each adjacency
+    /// block is only read and written once. `other` is the node the edge points to, or
+    /// the node it comes from if `reversed`, in which case the edges are inserted in the
+    /// reversed adjacency. Edges of the same node are stored in the order they are given.
+    /// `on_new` is called with `node` and `other` of each edge that wasn't stored already.
+    pub(crate) fn insert_sorted<I, F>(&self, edges: I, reversed: bool, mut on_new: F)
+    where
+        I: IntoIterator<Item = (NodeID, NodeID, String)>,
+        F: FnMut(NodeID, NodeID),
+    {
+        let mut adjacency = if reversed {
+            self.reversed_adjacency.lock().unwrap()
+        } else {
+            self.adjacency.lock().unwrap()
+        };
+        let block_size = adjacency.tree.block_size;
+
+        let mut block_id = None;
+        let mut block = Vec::new();
+
+        for (node, other, label) in edges {
+            if block_id != Some(node / block_size) {
+                if let Some(block_id) = block_id {
+                    adjacency.insert_block(block_id, std::mem::take(&mut block), &mut on_new);
+                }
+
+                block_id = Some(node / block_size);
+            }
+
+            block.push((node, other, label));
+        }
+
+        if let Some(block_id) = block_id {
+            adjacency.insert_block(block_id, block, &mut on_new);
+        }
+    }
+
+    /// Changes the number of cached `Node` to `NodeID` translations (and vice versa).
+    pub fn with_id_cache(self, capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+        let block_size = self.id2node.lock().unwrap().block_size as usize;
+
+        self.node2id.lock().unwrap().resize(capacity);
+        self.id2node
+            .lock()
+            .unwrap()
+            .inner
+            .resize(capacity.div_ceil(block_size));
+
+        self
+    }
+
+    /// Removes all edges from `from` to `to`, regardless of their label, and returns
+    /// how many edges were removed.
+    pub fn remove_edge(&mut self, from: &Node, to: &Node) -> usize {
+        let mut removed = 0;
+
+        if let (Some(from_id), Some(to_id)) = (self.node2id(from), self.node2id(to)) {
+            removed = self.adjacency.lock().unwrap().remove_edges(from_id, to_id);
+            self.reversed_adjacency
+                .lock()
+                .unwrap()
+                .remove_edges(to_id, from_id);
+            self.evidence
+                .lock()
+                .unwrap()
+                .remove(&(from.clone(), to.clone()));
+        }
+
+        removed
+    }
+
+    /// Removes `count` of the page links recorded behind the edge from `from` to `to`.
+    /// `url` is removed from the sample urls, so it should only be set once the page has
+    /// no links left along the edge. The edge itself is only removed once no links are
+    /// left, or right away if no links were recorded for it.
+    pub fn remove_links(&mut self, from: &Node, to: &Node, url: Option<&str>, count: u64) {
+        if count == 0 {
+            return;
+        }
+
+        let remaining = match self
+            .evidence
+            .lock()
+            .unwrap()
+            .get_mut(&(from.clone(), to.clone()))
+        {
+            Some(edge_evidence) => {
+                edge_evidence.count = edge_evidence.count.saturating_sub(count);
+                if let Some(url) = url {
+                    edge_evidence.sample_urls.retain(|sample| sample != url);
+                }
+                edge_evidence.count
+            }
+            None => 0,
+        };
+
+        if remaining == 0 {
+            self.remove_edge(from, to);
+        }
+    }
+
+    /// Removes the node if it has no ingoing or outgoing edges left.
+    pub fn remove_isolated(&mut self, node: &Node) {
+        if let Some(node_id) = self.node2id(node) {
+            if self.outgoing_edges(node_id).is_empty() && self.ingoing_edges(node_id).is_empty() {
+                self.remove_node(node);
+            }
+        }
+    }
+
+    /// Removes the node along with all its ingoing and outgoing edges.
+    /// Besides the blocks of the node itself, the block of every neighbour
+    /// needs to be rewritten, so the cost is proportional to the degree of the node.
+    pub fn remove_node(&mut self, node: &Node) {
+        if let Some(node_id) = self.node2id(node) {
+            for edge in self.outgoing_edges(node_id) {
+                self.reversed_adjacency
+                    .lock()
+                    .unwrap()
+                    .remove_edges(edge.to, node_id);
+
+                if let Some(to) = self.id2node(&edge.to) {
+                    self.evidence.lock().unwrap().remove(&(node.clone(), to));
+                }
+            }
+
+            for edge in self.ingoing_edges(node_id) {
+                self.adjacency
+                    .lock()
+                    .unwrap()
+                    .remove_edges(edge.from, node_id);
+
+                if let Some(from) = self.id2node(&edge.from) {
+                    self.evidence.lock().unwrap().remove(&(from, node.clone()));
+                }
+            }
+
+            self.adjacency.lock().unwrap().remove_node(node_id);
+            self.reversed_adjacency.lock().unwrap().remove_node(node_id);
+
+            self.node2id.lock().unwrap().remove(node);
+            self.id2node.lock().unwrap().update(&node_id, &mut |block| {
+                block.remove(&node_id);
+            });
+        }
+    }
+
+    /// Records that the page `url` links along the edge from `from` to `to`.
+    pub fn add_evidence(&self, from: &Node, to: &Node, url: &str, max_samples: usize) {
+        let mut evidence = self.evidence.lock().unwrap();
+        let key = (from.clone(), to.clone());
+
+        match evidence.get_mut(&key) {
+            Some(edge_evidence) => edge_evidence.add(url, max_samples),
+            None => {
+                let mut edge_evidence = EdgeEvidence::default();
+                edge_evidence.add(url, max_samples);
+                evidence.insert(key, edge_evidence);
+            }
+        }
+    }
+
+    pub fn evidence(&self, from: &Node, to: &Node) -> Option<EdgeEvidence> {
+        self.evidence
+            .lock()
+            .unwrap()
+            .get(&(from.clone(), to.clone()))
+            .cloned()
     }
 
     pub fn node2id(&self, node: &Node) -> Option<NodeID> {
@@ -275,6 +784,56 @@ impl<S: Store> GraphStore<S> {
         self.node2id.lock().unwrap().flush();
         self.id2node.lock().unwrap().inner.flush();
         self.meta.lock().unwrap().flush();
+        self.evidence.lock().unwrap().flush();
+    }
+
+    /// Reassigns contiguous ids to the nodes, so ids freed by removed nodes are reused.
+    /// The relative order of the ids and the order of the edges of each node are preserved.
+    /// All edges are kept in memory while the adjacency blocks are rewritten.
+    pub fn compact(&mut self) {
+        self.flush();
+
+        let mut nodes: Vec<(Node, NodeID)> = self.node2id.lock().unwrap().iter().collect();
+        nodes.sort_by_key(|(_, id)| *id);
+
+        let new_ids: HashMap<NodeID, NodeID> = nodes
+            .iter()
+            .enumerate()
+            .map(|(new_id, (_, old_id))| (*old_id, new_id as NodeID))
+            .collect();
+
+        let edges: Vec<_> = nodes
+            .iter()
+            .map(|(_, id)| (self.outgoing_edges(*id), self.ingoing_edges(*id)))
+            .collect();
+
+        self.adjacency.lock().unwrap().clear();
+        self.reversed_adjacency.lock().unwrap().clear();
+        self.id2node.lock().unwrap().inner.clear();
+
+        for (node, old_id) in &nodes {
+            self.assign_id(node.clone(), new_ids[old_id]);
+        }
+
+        for (outgoing, ingoing) in edges {
+            let mut adjacency = self.adjacency.lock().unwrap();
+            for edge in outgoing {
+                adjacency.insert(new_ids[&edge.from], new_ids[&edge.to], edge.label);
+            }
+            drop(adjacency);
+
+            let mut reversed_adjacency = self.reversed_adjacency.lock().unwrap();
+            for edge in ingoing {
+                reversed_adjacency.insert(new_ids[&edge.to], new_ids[&edge.from], edge.label);
+            }
+        }
+
+        self.meta
+            .lock()
+            .unwrap()
+            .insert("next_id".to_string(), nodes.len() as NodeID);
+
+        self.flush();
     }
 
     pub fn edges(&self) -> EdgeIterator<'_> {
@@ -283,6 +842,33 @@ impl<S: Store> GraphStore<S> {
         EdgeIterator::new(&self.adjacency)
     }
 
+    /// Edges that refer to ids without a node and nodes without any edges.
+    pub fn validate(&self) -> Vec<ValidationError> {
+        let mut errors = Vec::new();
+
+        for edge in self.edges() {
+            for id in [edge.from, edge.to] {
+                if self.id2node(&id).is_none() {
+                    errors.push(ValidationError::UnknownNode {
+                        edge: edge.clone(),
+                        id,
+                    });
+                }
+            }
+        }
+
+        for (node, id) in self.node_ids() {
+            if self.outgoing_edges(id).is_empty() && self.ingoing_edges(id).is_empty() {
+                errors.push(ValidationError::IsolatedNode(node));
+            }
+        }
+
+        errors
+    }
+
+    /// Inserts all edges of `other`. The ids of `other` are translated through its
+    /// nodes, so a node present in both graphs ends up as a single node with the
+    /// union of its edges. The page links recorded behind the edges are added up.
     pub fn append(&mut self, other: GraphStore<S>) {
         for edge in other.edges() {
             let from = other.id2node(&edge.from).expect("node not found");
@@ -290,69 +876,136 @@ impl<S: Store> GraphStore<S> {
 
             self.insert(from, to, edge.label);
         }
+
+        // `other.edges()` flushed the evidence of `other`, so its store is complete
+        let mut evidence = self.evidence.lock().unwrap();
+        for (key, other_evidence) in other.evidence.lock().unwrap().iter() {
+            match evidence.get_mut(&key) {
+                Some(edge_evidence) => edge_evidence.merge(other_evidence),
+                None => evidence.insert(key, other_evidence),
+            }
+        }
+    }
+}
+
+/// Graph store that is opened on first access, so the files of a graph that is
+/// never used are not touched.
+pub(crate) struct LazyGraphStore<S> {
+    path: Box<Path>,
+    read_only: bool,
+    id_cache_capacity: Option<usize>,
+    tuning: Option<RocksDbTuning>,
+    store: OnceCell<GraphStore<S>>,
+}
+
+impl<S: Store> LazyGraphStore<S> {
+    pub(crate) fn new(
+        path: Box<Path>,
+        read_only: bool,
+        id_cache_capacity: Option<usize>,
+        tuning: Option<RocksDbTuning>,
+    ) -> Self {
+        Self {
+            path,
+            read_only,
+            id_cache_capacity,
+            tuning,
+            store: OnceCell::new(),
+        }
+    }
+
+    fn open(&self) -> GraphStore<S> {
+        let store = match (&self.tuning, self.read_only) {
+            (_, true) => GraphStore::open_read_only(&self.path),
+            (Some(tuning), false) => GraphStore::open_tuned(&self.path, tuning),
+            (None, false) => GraphStore::open(&self.path),
+        };
+
+        match self.id_cache_capacity {
+            Some(capacity) => store.with_id_cache(capacity),
+            None => store,
+        }
+    }
+
+    pub(crate) fn get(&self) -> &GraphStore<S> {
+        self.store.get_or_init(|| self.open())
+    }
+
+    pub(crate) fn get_mut(&mut self) -> &mut GraphStore<S> {
+        self.get();
+        self.store.get_mut().unwrap()
+    }
+
+    pub(crate) fn get_if_open(&self) -> Option<&GraphStore<S>> {
+        self.store.get()
+    }
+
+    pub(crate) fn into_inner(self) -> GraphStore<S> {
+        self.get();
+        self.store.into_inner().unwrap()
     }
 }
 
 impl Store for RocksDbStore {
     fn open<P: AsRef<std::path::Path>>(path: P) -> GraphStore<Self> {
-        let adjacency = RocksDbStore::open(path.as_ref().join("adjacency"));
-        let reversed_adjacency = RocksDbStore::open(path.as_ref().join("reversed_adjacency"));
-        let node2id = RocksDbStore::open(path.as_ref().join("node2id"));
-        let id2node = RocksDbStore::open(path.as_ref().join("id2node"));
-        let meta = RocksDbStore::open(path.as_ref().join("meta"));
+        GraphStore::from_stores(
+            RocksDbStore::open(path.as_ref().join("adjacency")),
+            RocksDbStore::open(path.as_ref().join("reversed_adjacency")),
+            RocksDbStore::open(path.as_ref().join("node2id")),
+            RocksDbStore::open(path.as_ref().join("id2node")),
+            RocksDbStore::open(path.as_ref().join("meta")),
+            RocksDbStore::open(path.as_ref().join("evidence")),
+        )
+    }
 
-        GraphStore {
-            adjacency: Mutex::new(Adjacency {
-                tree: BlockedCachedTree {
-                    inner: CachedTree::new(adjacency, 10_000),
-                    block_size: 1_024,
-                },
-            }),
-            reversed_adjacency: Mutex::new(Adjacency {
-                tree: BlockedCachedTree {
-                    inner: CachedTree::new(reversed_adjacency, 10_000),
-                    block_size: 1_024,
-                },
-            }),
-            node2id: Mutex::new(CachedTree::new(node2id, 100_000)),
-            id2node: Mutex::new(BlockedCachedTree {
-                inner: CachedTree::new(id2node, 100_000),
-                block_size: 1_024,
-            }),
-            meta: Mutex::new(CachedTree::new(meta, 1_000)),
-            store: Default::default(),
-        }
+    fn open_read_only<P: AsRef<Path>>(path: P) -> GraphStore<Self> {
+        GraphStore::from_stores(
+            RocksDbStore::open_read_only(path.as_ref().join("adjacency")),
+            RocksDbStore::open_read_only(path.as_ref().join("reversed_adjacency")),
+            RocksDbStore::open_read_only(path.as_ref().join("node2id")),
+            RocksDbStore::open_read_only(path.as_ref().join("id2node")),
+            RocksDbStore::open_read_only(path.as_ref().join("meta")),
+            // graphs created before the evidence was stored don't have the store
+            if path.as_ref().join("evidence").exists() {
+                RocksDbStore::open_read_only(path.as_ref().join("evidence"))
+            } else {
+                MemoryStore::open()
+            },
+        )
+    }
+
+    fn open_tuned<P: AsRef<Path>>(path: P, tuning: &RocksDbTuning) -> GraphStore<Self> {
+        GraphStore::from_stores(
+            RocksDbStore::open_tuned(path.as_ref().join("adjacency"), tuning),
+            RocksDbStore::open_tuned(path.as_ref().join("reversed_adjacency"), tuning),
+            RocksDbStore::open_tuned(path.as_ref().join("node2id"), tuning),
+            RocksDbStore::open_tuned(path.as_ref().join("id2node"), tuning),
+            RocksDbStore::open_tuned(path.as_ref().join("meta"), tuning),
+            RocksDbStore::open_tuned(path.as_ref().join("evidence"), tuning),
+        )
+    }
+}
+
+/// Keeps the graph in memory. The path is ignored, so nothing is written to disk,
+/// and a read-only graph is always empty.
+impl Store for MemoryStore {
+    fn open<P: AsRef<Path>>(_path: P) -> GraphStore<Self> {
+        GraphStore::from_stores(
+            MemoryStore::open(),
+            MemoryStore::open(),
+            MemoryStore::open(),
+            MemoryStore::open(),
+            MemoryStore::open(),
+            MemoryStore::open(),
+        )
     }
 
     fn open_read_only<P: AsRef<Path>>(path: P) -> GraphStore<Self> {
-        let adjacency = RocksDbStore::open_read_only(path.as_ref().join("adjacency"));
-        let reversed_adjacency =
-            RocksDbStore::open_read_only(path.as_ref().join("reversed_adjacency"));
-        let node2id = RocksDbStore::open_read_only(path.as_ref().join("node2id"));
-        let id2node = RocksDbStore::open_read_only(path.as_ref().join("id2node"));
-        let meta = RocksDbStore::open_read_only(path.as_ref().join("meta"));
+        Self::open(path)
+    }
 
-        GraphStore {
-            adjacency: Mutex::new(Adjacency {
-                tree: BlockedCachedTree {
-                    inner: CachedTree::new(adjacency, 10_000),
-                    block_size: 1_024,
-                },
-            }),
-            reversed_adjacency: Mutex::new(Adjacency {
-                tree: BlockedCachedTree {
-                    inner: CachedTree::new(reversed_adjacency, 10_000),
-                    block_size: 1_024,
-                },
-            }),
-            node2id: Mutex::new(CachedTree::new(node2id, 100_000)),
-            id2node: Mutex::new(BlockedCachedTree {
-                inner: CachedTree::new(id2node, 100_000),
-                block_size: 1_024,
-            }),
-            meta: Mutex::new(CachedTree::new(meta, 1_000)),
-            store: Default::default(),
-        }
+    fn open_tuned<P: AsRef<Path>>(path: P, _tuning: &RocksDbTuning) -> GraphStore<Self> {
+        Self::open(path)
     }
 }
 
@@ -457,4 +1110,35 @@ mod test {
             },]
         );
     }
+
+    #[test]
+    fn only_changed_entries_written_back() {
+        let mut tree: CachedTree<u64, u64> = CachedTree::new(MemoryStore::open(), 1);
+
+        tree.insert(1, 1);
+        tree.insert(2, 2);
+        assert_eq!(tree.store.get(&1), Some(1));
+
+        // 1 is only read, so evicting or flushing it doesn't write it back
+        assert_eq!(tree.get(&1), Some(&1));
+        assert_eq!(tree.store.get(&2), Some(2));
+        tree.store.remove(&1);
+        tree.flush();
+        assert_eq!(tree.store.get(&1), None);
+        tree.insert(3, 3);
+        assert_eq!(tree.store.get(&1), None);
+
+        *tree.get_mut(&2).unwrap() = 4;
+        tree.flush();
+        assert_eq!(tree.store.get(&2), Some(4));
+
+        let mut tree: CachedTree<u64, u64> = CachedTree::new(MemoryStore::open(), 1);
+        tree.set_read_only();
+
+        tree.insert(1, 1);
+        tree.insert(2, 2);
+        tree.flush();
+        assert_eq!(tree.store.get(&1), None);
+        assert_eq!(tree.store.get(&2), None);
+    }
 }
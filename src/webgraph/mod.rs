@@ -13,25 +13,40 @@
 //
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
+mod bulk_loader;
 mod graph_store;
 
-use indicatif::{ProgressBar, ProgressIterator, ProgressStyle};
+use indicatif::{ProgressBar, ProgressStyle};
+use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
-use std::collections::{BinaryHeap, HashMap};
-use std::path::Path;
-use std::sync::Mutex;
-use std::{cmp, fs};
-use tracing::info;
+use std::collections::hash_map::Entry;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::io::{BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Component, Path};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{mpsc, Mutex};
+use std::{cmp, fs, thread};
+use thiserror::Error;
+use tracing::{info, warn};
 
-use graph_store::GraphStore;
+pub use bulk_loader::BulkLoader;
+use graph_store::{GraphStore, LazyGraphStore};
 
 use crate::directory::{self, DirEntry};
 use crate::webpage::Url;
 
 use self::graph_store::Adjacency;
-use crate::kv::rocksdb_store::RocksDbStore;
+use crate::kv::rocksdb_store::{RocksDbStore, RocksDbTuning};
 
-type NodeID = u64;
+/// Identifier of a node within a single graph store. The same node can have
+/// different ids in the full graph and the host graph.
+pub type NodeID = u64;
+
+const PAGERANK_TOLERANCE: f64 = 1e-6;
+
+/// Version of the file format written by `Webgraph::save_id_mapping`.
+/// Must be incremented whenever the format changes.
+const ID_MAPPING_VERSION: u32 = 1;
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub(crate) struct StoredEdge {
@@ -45,10 +60,20 @@ pub struct Node {
 }
 
 impl Node {
-    fn into_host(self) -> Node {
+    /// Node of the normalized url (see `Url::normalize`), so urls that only differ in
+    /// casing of the scheme and host, default ports or a trailing slash become the
+    /// same node. The `From` implementations use the url as is.
+    #[allow(unused)]
+    pub fn canonical(url: &Url) -> Node {
+        Node {
+            name: url.normalize().raw().to_string(),
+        }
+    }
+
+    fn into_host(self, collapse_subdomains: &[String]) -> Node {
         let url = Url::from(self.name);
 
-        let host = url.host_without_specific_subdomains();
+        let host = url.host_without_subdomains(collapse_subdomains);
 
         Node {
             name: host.to_string(),
@@ -84,11 +109,15 @@ impl From<Url> for Node {
     }
 }
 
+/// Iterates all edges in the adjacency store. Only the edges of a single node
+/// are kept in memory at a time.
 pub struct EdgeIterator<'a> {
     current_block_idx: usize,
     blocks: Vec<u64>,
     adjacency: &'a Mutex<Adjacency>,
-    current_block: Option<Box<dyn Iterator<Item = Edge>>>,
+    current_block_id: u64,
+    current_block_nodes: Vec<NodeID>,
+    current_edges: std::vec::IntoIter<Edge>,
 }
 
 impl<'a> EdgeIterator<'a> {
@@ -107,33 +136,64 @@ impl<'a> EdgeIterator<'a> {
             current_block_idx: 0,
             blocks,
             adjacency,
-            current_block: None,
+            current_block_id: 0,
+            current_block_nodes: Vec::new(),
+            current_edges: Vec::new().into_iter(),
         }
     }
 
-    fn load_next_block(&mut self) {
-        if self.current_block_idx < self.blocks.len() {
-            let block_id = self.blocks[self.current_block_idx];
-            let block = self
-                .adjacency
-                .lock()
-                .unwrap()
-                .tree
-                .inner
-                .get(&block_id)
-                .unwrap()
-                .clone();
+    /// Loads the node ids of the next block. Returns false if there are no more blocks.
+    fn load_next_block(&mut self) -> bool {
+        if self.current_block_idx >= self.blocks.len() {
+            return false;
+        }
 
-            self.current_block = Some(Box::new(block.into_iter().flat_map(|(node_id, edges)| {
-                edges.into_iter().map(move |edge| Edge {
-                    from: node_id,
-                    to: edge.other,
-                    label: edge.label,
-                })
-            })));
+        self.current_block_id = self.blocks[self.current_block_idx];
+        self.current_block_nodes = self
+            .adjacency
+            .lock()
+            .unwrap()
+            .tree
+            .inner
+            .get(&self.current_block_id)
+            .map(|block| block.keys().copied().collect())
+            .unwrap_or_default();
 
-            self.current_block_idx += 1;
-        }
+        self.current_block_idx += 1;
+
+        true
+    }
+
+    /// Loads the edges of the next node in the current block. Returns false if
+    /// all nodes in the block have been visited.
+    fn load_next_node(&mut self) -> bool {
+        let node_id = match self.current_block_nodes.pop() {
+            Some(node_id) => node_id,
+            None => return false,
+        };
+
+        let edges = self
+            .adjacency
+            .lock()
+            .unwrap()
+            .tree
+            .inner
+            .get(&self.current_block_id)
+            .and_then(|block| block.get(&node_id))
+            .cloned()
+            .unwrap_or_default();
+
+        self.current_edges = edges
+            .into_iter()
+            .map(|edge| Edge {
+                from: node_id,
+                to: edge.other,
+                label: edge.label,
+            })
+            .collect::<Vec<_>>()
+            .into_iter();
+
+        true
     }
 }
 
@@ -141,18 +201,30 @@ impl<'a> Iterator for EdgeIterator<'a> {
     type Item = Edge;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if let Some(res) = self.current_block.as_mut().and_then(|it| it.next()) {
-            return Some(res);
-        }
+        loop {
+            if let Some(edge) = self.current_edges.next() {
+                return Some(edge);
+            }
 
-        if self.current_block.is_none() {
-            self.load_next_block();
+            if !self.load_next_node() && !self.load_next_block() {
+                return None;
+            }
         }
-
-        self.current_block.as_mut().and_then(|it| it.next())
     }
 }
 
+/// A labelled edge between two nodes of the same graph store.
+///
+/// ```
+/// use cuely::{Edge, NodeID};
+///
+/// let edge: Edge = serde_json::from_str(r#"{"from": 1, "to": 2, "label": "home"}"#).unwrap();
+///
+/// let from: NodeID = edge.from();
+/// assert_eq!(from, 1);
+/// assert_eq!(edge.to(), 2);
+/// assert_eq!(edge.label(), "home");
+/// ```
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Edge {
     from: NodeID,
@@ -160,6 +232,64 @@ pub struct Edge {
     label: String,
 }
 
+impl Edge {
+    pub fn from(&self) -> NodeID {
+        self.from
+    }
+
+    pub fn to(&self) -> NodeID {
+        self.to
+    }
+
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+}
+
+/// The page links behind an edge in the host graph.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EdgeEvidence {
+    /// Number of distinct page links between the two hosts. Inserting a page link
+    /// that is already in the full graph doesn't count it again.
+    pub count: u64,
+    /// Distinct urls of some of the pages the links come from. Only kept for graphs
+    /// opened with `WebgraphBuilder::with_host_edge_evidence`.
+    pub sample_urls: Vec<String>,
+}
+
+impl EdgeEvidence {
+    fn add(&mut self, url: &str, max_samples: usize) {
+        self.count += 1;
+
+        if self.sample_urls.len() < max_samples && !self.sample_urls.iter().any(|u| u == url) {
+            self.sample_urls.push(url.to_string());
+        }
+    }
+
+    /// Adds the links of `other`. Sample urls are added until there are as many as
+    /// in the longer of the two lists, which is the sample limit unless both edges
+    /// have fewer linking pages.
+    fn merge(&mut self, other: EdgeEvidence) {
+        let max_samples = self.sample_urls.len().max(other.sample_urls.len());
+        self.count += other.count;
+
+        for url in other.sample_urls {
+            if self.sample_urls.len() < max_samples && !self.sample_urls.contains(&url) {
+                self.sample_urls.push(url);
+            }
+        }
+    }
+}
+
+/// An inconsistency in a graph found by `Webgraph::validate`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValidationError {
+    /// The edge refers to `id`, which isn't in the node table.
+    UnknownNode { edge: Edge, id: NodeID },
+    /// The node is in the node table, but has no edges.
+    IsolatedNode(Node),
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct FullEdge {
     pub from: Node,
@@ -167,11 +297,172 @@ pub struct FullEdge {
     pub label: String,
 }
 
+impl FullEdge {
+    fn reversed(self) -> FullEdge {
+        FullEdge {
+            from: self.to,
+            to: self.from,
+            label: self.label,
+        }
+    }
+}
+
+type VisitedNodes = HashMap<NodeID, (Option<Edge>, usize)>;
+
+/// Visits all unvisited neighbours of the nodes in the frontier and returns them
+/// as the next frontier. The edge used to reach each node is stored in `visited`.
+fn expand_frontier<F1, F2>(
+    frontier: &[NodeID],
+    visited: &mut VisitedNodes,
+    node_edges: F1,
+    edge_node: F2,
+) -> Vec<NodeID>
+where
+    F1: Fn(NodeID) -> Vec<Edge>,
+    F2: Fn(&Edge) -> NodeID,
+{
+    let mut next_frontier = Vec::new();
+
+    for node in frontier {
+        let dist = visited[node].1;
+
+        for edge in node_edges(*node) {
+            let next = edge_node(&edge);
+
+            if let Entry::Vacant(entry) = visited.entry(next) {
+                entry.insert((Some(edge), dist + 1));
+                next_frontier.push(next);
+            }
+        }
+    }
+
+    next_frontier
+}
+
+/// Finds the node in the frontier that is closest to the search from the other direction.
+fn closest_meeting_node(frontier: &[NodeID], other: &VisitedNodes) -> Option<NodeID> {
+    frontier
+        .iter()
+        .filter_map(|node| other.get(node).map(|(_, dist)| (*dist, *node)))
+        .min()
+        .map(|(_, node)| node)
+}
+
+/// How the harmonic centrality of a node is scaled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Normalization {
+    /// The sum of reciprocal distances divided by the number of other nodes in the graph,
+    /// so centralities are between 0 and 1.
+    #[default]
+    Normalized,
+    /// The sum of reciprocal distances.
+    Raw,
+}
+
+impl Normalization {
+    /// The number the summed reciprocals are divided by. Graphs with a single node have
+    /// no other nodes, so the factor is at least 1 to avoid dividing by zero.
+    fn norm_factor(&self, num_nodes: usize) -> f64 {
+        match self {
+            Normalization::Normalized => num_nodes.saturating_sub(1).max(1) as f64,
+            Normalization::Raw => 1.0,
+        }
+    }
+}
+
+/// Where an interrupted `Webgraph::harmonic_centrality_chunked` can be continued.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CentralityResumeToken {
+    processed_nodes: usize,
+    /// Length of the output file after the last finished chunk.
+    file_len: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GraphStats {
+    pub num_nodes: usize,
+    pub num_edges: usize,
+    pub num_host_nodes: usize,
+    pub num_host_edges: usize,
+    pub avg_out_degree: f64,
+}
+
+fn find_root(parents: &mut HashMap<NodeID, NodeID>, node: NodeID) -> NodeID {
+    let mut root = node;
+    while *parents.entry(root).or_insert(root) != root {
+        root = parents[&root];
+    }
+
+    let mut current = node;
+    while current != root {
+        let next = parents[&current];
+        parents.insert(current, root);
+        current = next;
+    }
+
+    root
+}
+
+/// The contribution of a node at distance `dist` to the harmonic centrality.
+fn harmonic_contribution(dist: usize) -> f64 {
+    1.0 / dist as f64
+}
+
+/// Calls `progress` with the number of finished nodes and the total number of nodes.
+fn report_progress<P>(progress: &P, finished: &AtomicU64, total: u64)
+where
+    P: Fn(u64, u64),
+{
+    progress(finished.fetch_add(1, Ordering::SeqCst) + 1, total);
+}
+
+struct ScoredNode {
+    centrality: f64,
+    node: Node,
+}
+
+impl PartialEq for ScoredNode {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == cmp::Ordering::Equal
+    }
+}
+
+impl Eq for ScoredNode {}
+
+impl PartialOrd for ScoredNode {
+    fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for ScoredNode {
+    fn cmp(&self, other: &Self) -> cmp::Ordering {
+        self.centrality
+            .partial_cmp(&other.centrality)
+            .unwrap_or(cmp::Ordering::Equal)
+            .then_with(|| other.node.cmp(&self.node))
+    }
+}
+
+/// Pushes to a min-heap and pops the smallest element if the heap grows beyond `n` elements.
+fn push_bounded(heap: &mut BinaryHeap<cmp::Reverse<ScoredNode>>, scored: ScoredNode, n: usize) {
+    heap.push(cmp::Reverse(scored));
+
+    if heap.len() > n {
+        heap.pop();
+    }
+}
+
 pub struct WebgraphBuilder {
     path: Box<Path>,
     full_graph_path: Option<Box<Path>>,
     host_graph_path: Option<Box<Path>>,
     read_only: bool,
+    id_cache_capacity: Option<usize>,
+    rocksdb_tuning: Option<RocksDbTuning>,
+    collapse_subdomains: Vec<String>,
+    drop_self_loops: bool,
+    host_edge_evidence: Option<usize>,
 }
 
 impl WebgraphBuilder {
@@ -189,6 +480,11 @@ impl WebgraphBuilder {
             full_graph_path: None,
             host_graph_path: None,
             read_only: false,
+            id_cache_capacity: None,
+            rocksdb_tuning: None,
+            collapse_subdomains: vec!["www".to_string()],
+            drop_self_loops: false,
+            host_edge_evidence: None,
         }
     }
 
@@ -208,29 +504,117 @@ impl WebgraphBuilder {
         self
     }
 
+    /// Sets the number of node translations (`Node` to `NodeID` and vice versa)
+    /// that are kept in memory before falling back to the store.
+    #[allow(unused)]
+    pub fn with_id_cache(mut self, capacity: usize) -> Self {
+        self.id_cache_capacity = Some(capacity);
+        self
+    }
+
+    /// Sets the RocksDB options used for the stores of the graph. Ignored if the
+    /// graph is opened read-only. If `bulk_load` is set, `Webgraph::finish_bulk_load`
+    /// must be called once the graph has been built.
+    #[allow(unused)]
+    pub fn with_rocksdb_options(mut self, tuning: RocksDbTuning) -> Self {
+        self.rocksdb_tuning = Some(tuning);
+        self
+    }
+
+    /// Sets the subdomains that are stripped when deriving the host of a node,
+    /// so e.g. `m.example.com` and `example.com` become the same host node.
+    /// Defaults to only stripping `www`.
+    #[allow(unused)]
+    pub fn collapse_subdomains(mut self, subdomains: Vec<String>) -> Self {
+        self.collapse_subdomains = subdomains;
+        self
+    }
+
+    /// Discards edges from a node to itself when they are inserted. This is checked
+    /// separately for the full graph and the host graph, so links between pages on
+    /// the same host are not added to the host graph.
+    #[allow(unused)]
+    pub fn drop_self_loops(mut self, drop_self_loops: bool) -> Self {
+        self.drop_self_loops = drop_self_loops;
+        self
+    }
+
+    /// Keeps the urls of at most `max_samples` source pages for each edge of the host
+    /// graph. The number of page links behind each host edge is always recorded.
+    /// See `Webgraph::host_edge_evidence`.
+    #[allow(unused)]
+    pub fn with_host_edge_evidence(mut self, max_samples: usize) -> Self {
+        self.host_edge_evidence = Some(max_samples);
+        self
+    }
+
+    /// Opens the graph and inserts all edges from a file with one
+    /// `from\tto\tlabel` line per edge. The label column is optional.
+    /// Malformed lines are skipped.
+    #[allow(unused)]
+    pub fn from_edge_list<P: AsRef<Path>>(self, path: P) -> std::io::Result<Webgraph> {
+        let reader = BufReader::new(fs::File::open(path)?);
+        let mut graph = self.open();
+
+        for (line_num, line) in reader.lines().enumerate() {
+            let line = line?;
+            let columns: Vec<_> = line.split('\t').collect();
+
+            let (from, to, label) = match columns[..] {
+                [from, to] => (from, to, ""),
+                [from, to, label] => (from, to, label),
+                _ => {
+                    warn!(
+                        "skipping malformed line {} in edge list: {:?}",
+                        line_num + 1,
+                        line
+                    );
+                    continue;
+                }
+            };
+
+            graph.insert(Node::from(from), Node::from(to), label.to_string());
+        }
+
+        graph.flush();
+
+        Ok(graph)
+    }
+
+    /// Creates the graph. The stores of the full graph and the host graph are only
+    /// opened once they are accessed.
     pub fn open(self) -> Webgraph {
-        if self.read_only {
-            Webgraph {
-                full_graph: self.full_graph_path.map(GraphStore::open_read_only),
-                host_graph: self.host_graph_path.map(GraphStore::open_read_only),
-                path: self.path.to_str().unwrap().to_string(),
-            }
-        } else {
-            Webgraph {
-                full_graph: self.full_graph_path.map(GraphStore::open),
-                host_graph: self.host_graph_path.map(GraphStore::open),
-                path: self.path.to_str().unwrap().to_string(),
-            }
+        self.open_with()
+    }
+
+    /// Opens the graph with `S` as storage backend instead of RocksDB.
+    pub fn open_with<S: Store>(self) -> Webgraph<S> {
+        let read_only = self.read_only;
+        let id_cache_capacity = self.id_cache_capacity;
+        let tuning = self.rocksdb_tuning;
+        let lazy_store =
+            |path| LazyGraphStore::new(path, read_only, id_cache_capacity, tuning.clone());
+
+        Webgraph {
+            full_graph: self.full_graph_path.map(lazy_store),
+            host_graph: self.host_graph_path.map(lazy_store),
+            path: self.path.to_str().unwrap().to_string(),
+            show_progress: true,
+            collapse_subdomains: self.collapse_subdomains,
+            read_only,
+            drop_self_loops: self.drop_self_loops,
+            host_edge_evidence: self.host_edge_evidence,
         }
     }
 }
 
 pub trait Store
 where
-    Self: Sized,
+    Self: Sized + Send + Sync,
 {
     fn open<P: AsRef<Path>>(path: P) -> GraphStore<Self>;
     fn open_read_only<P: AsRef<Path>>(path: P) -> GraphStore<Self>;
+    fn open_tuned<P: AsRef<Path>>(path: P, tuning: &RocksDbTuning) -> GraphStore<Self>;
 
     fn temporary() -> GraphStore<Self> {
         Self::open(crate::gen_temp_path())
@@ -239,30 +623,314 @@ where
 
 pub struct Webgraph<S: Store = RocksDbStore> {
     pub path: String,
-    full_graph: Option<GraphStore<S>>,
-    host_graph: Option<GraphStore<S>>,
+    full_graph: Option<LazyGraphStore<S>>,
+    host_graph: Option<LazyGraphStore<S>>,
+    show_progress: bool,
+    collapse_subdomains: Vec<String>,
+    read_only: bool,
+    drop_self_loops: bool,
+    /// Maximum number of sample urls kept per host edge, if evidence is recorded.
+    host_edge_evidence: Option<usize>,
 }
 
 impl<S: Store> Webgraph<S> {
+    fn full_graph(&self) -> Option<&GraphStore<S>> {
+        self.full_graph.as_ref().map(LazyGraphStore::get)
+    }
+
+    fn host_graph(&self) -> Option<&GraphStore<S>> {
+        self.host_graph.as_ref().map(LazyGraphStore::get)
+    }
+
+    /// Whether the graph was opened with the full graph (`with_full_graph`).
+    #[allow(unused)]
+    pub fn has_full_graph(&self) -> bool {
+        self.full_graph.is_some()
+    }
+
+    /// Whether the graph was opened with the host graph (`with_host_graph`).
+    #[allow(unused)]
+    pub fn has_host_graph(&self) -> bool {
+        self.host_graph.is_some()
+    }
+
+    /// Panics if the graph is opened read-only, since writes would otherwise
+    /// fail deep inside the store.
+    fn assert_writable(&self) {
+        assert!(
+            !self.read_only,
+            "cannot modify the webgraph at {}: it is opened read-only",
+            self.path
+        );
+    }
+
     pub fn insert(&mut self, from: Node, to: Node, label: String) {
+        self.assert_writable();
+
+        // without the full graph there is no way to tell whether the page link is new
+        let mut is_new_link = true;
+
+        if let Some(full_graph) = &mut self.full_graph {
+            if !self.drop_self_loops || from != to {
+                is_new_link = full_graph
+                    .get_mut()
+                    .insert(from.clone(), to.clone(), label.clone());
+            }
+        }
+
+        if let Some(host_graph) = &mut self.host_graph {
+            let url = from.name.clone();
+            let from = from.into_host(&self.collapse_subdomains);
+            let to = to.into_host(&self.collapse_subdomains);
+
+            if !self.drop_self_loops || from != to {
+                if is_new_link {
+                    let max_samples = self.host_edge_evidence.unwrap_or(0);
+                    host_graph.get().add_evidence(&from, &to, &url, max_samples);
+                }
+
+                host_graph.get_mut().insert(from, to, label);
+            }
+        }
+    }
+
+    /// Inserts all edges in bulk and flushes the graph afterwards. Host edges are
+    /// derived from each edge the same way as in `insert`.
+    #[allow(unused)]
+    pub fn insert_batch(&mut self, edges: impl IntoIterator<Item = (Node, Node, String)>) {
+        self.assert_writable();
+
+        let edges: Vec<_> = edges.into_iter().collect();
+
+        // the full graph goes first, so the host graph only counts page links that are new
+        let is_new_link: Vec<_> = match &mut self.full_graph {
+            Some(full_graph) => {
+                let mut inserted = full_graph
+                    .get_mut()
+                    .insert_batch(
+                        edges
+                            .iter()
+                            .filter(|(from, to, _)| !self.drop_self_loops || from != to)
+                            .cloned()
+                            .collect(),
+                    )
+                    .into_iter();
+
+                edges
+                    .iter()
+                    .map(|(from, to, _)| {
+                        (!self.drop_self_loops || from != to) && inserted.next().unwrap_or(false)
+                    })
+                    .collect()
+            }
+            None => vec![true; edges.len()],
+        };
+
+        if let Some(host_graph) = &mut self.host_graph {
+            let host_edges: Vec<_> = edges
+                .iter()
+                .zip(is_new_link)
+                .map(|((from, to, label), is_new_link)| {
+                    (
+                        from,
+                        is_new_link,
+                        from.clone().into_host(&self.collapse_subdomains),
+                        to.clone().into_host(&self.collapse_subdomains),
+                        label.clone(),
+                    )
+                })
+                .filter(|(_, _, from, to, _)| !self.drop_self_loops || from != to)
+                .collect();
+
+            let max_samples = self.host_edge_evidence.unwrap_or(0);
+            for (page, is_new_link, from, to, _) in &host_edges {
+                if *is_new_link {
+                    host_graph
+                        .get()
+                        .add_evidence(from, to, &page.name, max_samples);
+                }
+            }
+
+            host_graph.get_mut().insert_batch(
+                host_edges
+                    .into_iter()
+                    .map(|(_, _, from, to, label)| (from, to, label))
+                    .collect(),
+            );
+        }
+
+        self.flush();
+    }
+
+    /// Loader for inserting a large number of edges. Unlike `insert` and `insert_batch`,
+    /// which read and write the adjacency block of the edge for every edge, the loader
+    /// sorts the edges by block and writes each block once when it is finished.
+    /// The resulting graph is identical to inserting the edges one by one.
+    #[allow(unused)]
+    pub fn bulk_loader(&mut self) -> BulkLoader<'_, S> {
+        self.assert_writable();
+
+        BulkLoader::new(self)
+    }
+
+    /// Removes all edges between the two nodes. The edge between their hosts is only
+    /// removed from the host graph once none of the page links behind it are left, and
+    /// `from` is only removed from its sample urls once the page has no links left to
+    /// the host of `to`. Without the full graph, each call removes a single page link
+    /// from the host edge and the sample urls are kept.
+    #[allow(unused)]
+    pub fn remove_edge(&mut self, from: Node, to: Node) {
+        self.assert_writable();
+
+        let from_host = from.clone().into_host(&self.collapse_subdomains);
+        let to_host = to.clone().into_host(&self.collapse_subdomains);
+
+        let (removed, last_link) = match &mut self.full_graph {
+            Some(full_graph) => {
+                let full_graph = full_graph.get_mut();
+                let removed = full_graph.remove_edge(&from, &to) as u64;
+                let last_link = !Webgraph::links_to_host(
+                    full_graph,
+                    &from,
+                    &to_host,
+                    &self.collapse_subdomains,
+                );
+
+                (removed, last_link)
+            }
+            None => (1, false),
+        };
+
+        if let Some(host_graph) = &mut self.host_graph {
+            let url = last_link.then_some(from.name.as_str());
+
+            host_graph
+                .get_mut()
+                .remove_links(&from_host, &to_host, url, removed);
+        }
+    }
+
+    /// Removes the node and all edges incident to it. In the host graph, the page links
+    /// of the node are removed from the host edges like in `remove_edge`, and the host
+    /// of the node is removed once it has no edges left. Without the full graph the
+    /// links of the node are unknown, so the host is removed along with all its edges.
+    #[allow(unused)]
+    pub fn remove_node(&mut self, node: Node) {
+        self.assert_writable();
+
+        let collapse_subdomains = &self.collapse_subdomains;
+        let links = self.full_graph.as_mut().map(|full_graph| {
+            let full_graph = full_graph.get_mut();
+            let links = Webgraph::page_links(full_graph, &node);
+            full_graph.remove_node(&node);
+
+            // the linking page is only removed from the sample urls with its last link to
+            // the host, which is always the case for the links of the removed node itself
+            links
+                .into_iter()
+                .map(|(from, to)| {
+                    let to = to.into_host(collapse_subdomains);
+                    let url =
+                        (!Webgraph::links_to_host(full_graph, &from, &to, collapse_subdomains))
+                            .then(|| from.name.clone());
+
+                    (from.into_host(collapse_subdomains), to, url)
+                })
+                .collect::<Vec<_>>()
+        });
+
+        if let Some(host_graph) = &mut self.host_graph {
+            let host_graph = host_graph.get_mut();
+            let host = node.into_host(&self.collapse_subdomains);
+
+            match links {
+                Some(links) => {
+                    for (from, to, url) in links {
+                        host_graph.remove_links(&from, &to, url.as_deref(), 1);
+                    }
+
+                    host_graph.remove_isolated(&host);
+                }
+                None => host_graph.remove_node(&host),
+            }
+        }
+    }
+
+    /// The page links of `node` as `(from, to)` pairs, one for each stored edge
+    /// incident to it. Edges to unknown ids are skipped.
+    fn page_links(graph: &GraphStore<S>, node: &Node) -> Vec<(Node, Node)> {
+        let node_id = match graph.node2id(node) {
+            Some(node_id) => node_id,
+            None => return Vec::new(),
+        };
+
+        let outgoing = graph
+            .outgoing_edges(node_id)
+            .into_iter()
+            .filter_map(|edge| Some((node.clone(), Webgraph::resolve_node(graph, &edge.to)?)));
+        // self-loops are among the outgoing edges already
+        let ingoing = graph
+            .ingoing_edges(node_id)
+            .into_iter()
+            .filter(|edge| edge.from != node_id)
+            .filter_map(|edge| Some((Webgraph::resolve_node(graph, &edge.from)?, node.clone())));
+
+        outgoing.chain(ingoing).collect()
+    }
+
+    /// Whether `page` still links to a page on `host`.
+    fn links_to_host(
+        graph: &GraphStore<S>,
+        page: &Node,
+        host: &Node,
+        collapse_subdomains: &[String],
+    ) -> bool {
+        let node_id = match graph.node2id(page) {
+            Some(node_id) => node_id,
+            None => return false,
+        };
+
+        graph
+            .outgoing_edges(node_id)
+            .into_iter()
+            .filter_map(|edge| Webgraph::resolve_node(graph, &edge.to))
+            .any(|to| to.into_host(collapse_subdomains) == *host)
+    }
+
+    /// Reassigns contiguous `NodeID`s in both graphs, so the id space no longer
+    /// contains holes left by removed nodes.
+    #[allow(unused)]
+    pub fn compact(&mut self) -> crate::Result<()> {
+        if self.read_only {
+            return Err(crate::Error::ReadOnlyWebgraph);
+        }
+
         if let Some(full_graph) = &mut self.full_graph {
-            full_graph.insert(from.clone(), to.clone(), label.clone());
+            full_graph.get_mut().compact();
         }
 
         if let Some(host_graph) = &mut self.host_graph {
-            host_graph.insert(from.into_host(), to.into_host(), label);
+            host_graph.get_mut().compact();
         }
+
+        Ok(())
     }
 
     pub fn merge(&mut self, other: Webgraph<S>) {
+        self.assert_writable();
+
         match (&mut self.full_graph, other.full_graph) {
-            (Some(self_graph), Some(other_graph)) => self_graph.append(other_graph),
+            (Some(self_graph), Some(other_graph)) => {
+                self_graph.get_mut().append(other_graph.into_inner())
+            }
             (None, Some(other_graph)) => self.full_graph = Some(other_graph),
             (Some(_), None) | (None, None) => {}
         }
 
         match (&mut self.host_graph, other.host_graph) {
-            (Some(self_graph), Some(other_graph)) => self_graph.append(other_graph),
+            (Some(self_graph), Some(other_graph)) => {
+                self_graph.get_mut().append(other_graph.into_inner())
+            }
             (None, Some(other_graph)) => self.host_graph = Some(other_graph),
             (Some(_), None) | (None, None) => {}
         }
@@ -270,30 +938,55 @@ impl<S: Store> Webgraph<S> {
         self.flush();
     }
 
-    fn dijkstra<F1, F2>(
+    fn dijkstra<F1, F2, F3>(
         source: Node,
         node_edges: F1,
         edge_node: F2,
+        edge_weight: F3,
+        max_dist: Option<usize>,
         store: &GraphStore<S>,
     ) -> HashMap<NodeID, usize>
     where
         F1: Fn(NodeID) -> Vec<Edge>,
         F2: Fn(&Edge) -> NodeID,
+        F3: Fn(&Edge) -> usize,
     {
-        let source_id = store.node2id(&source);
-        if source_id.is_none() {
-            return HashMap::new();
+        match store.node2id(&source) {
+            Some(source_id) => Webgraph::<S>::multi_source_dijkstra(
+                &[source_id],
+                node_edges,
+                edge_node,
+                edge_weight,
+                max_dist,
+            ),
+            None => HashMap::new(),
         }
+    }
 
-        let source_id = source_id.unwrap();
-        let mut distances: HashMap<NodeID, usize> = HashMap::default();
-
-        let mut queue = BinaryHeap::new();
-
-        queue.push(cmp::Reverse((0_usize, source_id)));
-        distances.insert(source_id, 0);
-
-        while let Some(state) = queue.pop() {
+    /// Dijkstra starting from all `sources` at once, so the distance of each node
+    /// is its distance to the closest source.
+    fn multi_source_dijkstra<F1, F2, F3>(
+        sources: &[NodeID],
+        node_edges: F1,
+        edge_node: F2,
+        edge_weight: F3,
+        max_dist: Option<usize>,
+    ) -> HashMap<NodeID, usize>
+    where
+        F1: Fn(NodeID) -> Vec<Edge>,
+        F2: Fn(&Edge) -> NodeID,
+        F3: Fn(&Edge) -> usize,
+    {
+        let mut distances: HashMap<NodeID, usize> = HashMap::default();
+
+        let mut queue = BinaryHeap::new();
+
+        for source_id in sources {
+            queue.push(cmp::Reverse((0_usize, *source_id)));
+            distances.insert(*source_id, 0);
+        }
+
+        while let Some(state) = queue.pop() {
             let (cost, v) = state.0;
             let current_dist = distances.get(&v).unwrap_or(&usize::MAX);
 
@@ -302,10 +995,16 @@ impl<S: Store> Webgraph<S> {
             }
 
             for edge in node_edges(v) {
-                if cost + 1 < *distances.get(&edge_node(&edge)).unwrap_or(&usize::MAX) {
-                    let next = cmp::Reverse((cost + 1, edge_node(&edge)));
+                let next_cost = cost.saturating_add(edge_weight(&edge));
+
+                if next_cost > max_dist.unwrap_or(usize::MAX) {
+                    continue;
+                }
+
+                if next_cost < *distances.get(&edge_node(&edge)).unwrap_or(&usize::MAX) {
+                    let next = cmp::Reverse((next_cost, edge_node(&edge)));
                     queue.push(next);
-                    distances.insert(edge_node(&edge), cost + 1);
+                    distances.insert(edge_node(&edge), next_cost);
                 }
             }
         }
@@ -315,33 +1014,342 @@ impl<S: Store> Webgraph<S> {
 
     #[allow(unused)]
     pub fn distances(&self, source: Node) -> HashMap<Node, usize> {
-        self.full_graph
-            .as_ref()
+        self.weighted_distances(source, |_| 1)
+    }
+
+    /// Distance from each node in the full graph to the closest of the `sources`.
+    /// Sources that are not in the graph are ignored.
+    #[allow(unused)]
+    pub fn distances_from_set(&self, sources: &[Node]) -> HashMap<Node, usize> {
+        self.full_graph()
+            .map(|full_graph| {
+                let source_ids: Vec<_> = sources
+                    .iter()
+                    .filter_map(|source| full_graph.node2id(source))
+                    .collect();
+
+                Webgraph::<S>::multi_source_dijkstra(
+                    &source_ids,
+                    |node_id| full_graph.outgoing_edges(node_id),
+                    |edge| edge.to,
+                    |_| 1,
+                    None,
+                )
+                .into_iter()
+                .filter_map(|(id, dist)| {
+                    Webgraph::resolve_node(full_graph, &id).map(|node| (node, dist))
+                })
+                .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Same as `distances`, but sorted by distance with ties broken by node name,
+    /// so the order is deterministic. The source is included with a distance of 0.
+    #[allow(unused)]
+    pub fn distances_sorted(&self, source: Node) -> Vec<(Node, usize)> {
+        let mut distances: Vec<_> = self.distances(source).into_iter().collect();
+
+        distances.sort_by(|(a_node, a_dist), (b_node, b_dist)| {
+            a_dist.cmp(b_dist).then_with(|| a_node.cmp(b_node))
+        });
+
+        distances
+    }
+
+    /// Same as `distances`, but the cost of traversing an edge is given by `edge_weight`.
+    /// Edges with a weight of `usize::MAX` are never traversed.
+    #[allow(unused)]
+    pub fn weighted_distances<F>(&self, source: Node, edge_weight: F) -> HashMap<Node, usize>
+    where
+        F: Fn(&Edge) -> usize,
+    {
+        self.full_graph()
             .map(|full_graph| {
                 let distances = Webgraph::dijkstra(
                     source,
                     |node_id| full_graph.outgoing_edges(node_id),
                     |edge| edge.to,
+                    edge_weight,
+                    None,
+                    full_graph,
+                );
+
+                distances
+                    .into_iter()
+                    .filter_map(|(id, dist)| {
+                        Webgraph::resolve_node(full_graph, &id).map(|node| (node, dist))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Same as `distances`, but only edges with a label in `allowed` are traversed.
+    #[allow(unused)]
+    pub fn distances_with_labels(
+        &self,
+        source: Node,
+        allowed: &HashSet<String>,
+    ) -> HashMap<Node, usize> {
+        self.full_graph()
+            .map(|full_graph| {
+                let distances = Webgraph::dijkstra(
+                    source,
+                    |node_id| {
+                        full_graph
+                            .outgoing_edges(node_id)
+                            .into_iter()
+                            .filter(|edge| allowed.contains(&edge.label))
+                            .collect()
+                    },
+                    |edge| edge.to,
+                    |_| 1,
+                    None,
                     full_graph,
                 );
 
                 distances
                     .into_iter()
-                    .map(|(id, dist)| (full_graph.id2node(&id).expect("unknown node"), dist))
+                    .filter_map(|(id, dist)| {
+                        Webgraph::resolve_node(full_graph, &id).map(|node| (node, dist))
+                    })
                     .collect()
             })
             .unwrap_or_default()
     }
 
+    /// Bidirectional breadth-first search between `source` and `target`. The smallest
+    /// frontier is expanded in each step, and the search stops as soon as the two
+    /// searches meet. Returns the edges on the path.
+    fn raw_shortest_path(
+        graph: &GraphStore<S>,
+        source: NodeID,
+        target: NodeID,
+    ) -> Option<Vec<Edge>> {
+        let mut forward: VisitedNodes = HashMap::new();
+        let mut backward: VisitedNodes = HashMap::new();
+
+        forward.insert(source, (None, 0));
+        backward.insert(target, (None, 0));
+
+        if source == target {
+            return Some(Vec::new());
+        }
+
+        let mut forward_frontier = vec![source];
+        let mut backward_frontier = vec![target];
+
+        while !forward_frontier.is_empty() && !backward_frontier.is_empty() {
+            let meeting_node = if forward_frontier.len() <= backward_frontier.len() {
+                forward_frontier = expand_frontier(
+                    &forward_frontier,
+                    &mut forward,
+                    |node| graph.outgoing_edges(node),
+                    |edge| edge.to,
+                );
+                closest_meeting_node(&forward_frontier, &backward)
+            } else {
+                backward_frontier = expand_frontier(
+                    &backward_frontier,
+                    &mut backward,
+                    |node| graph.ingoing_edges(node),
+                    |edge| edge.from,
+                );
+                closest_meeting_node(&backward_frontier, &forward)
+            };
+
+            if let Some(meeting_node) = meeting_node {
+                let mut path = Vec::new();
+
+                let mut current = meeting_node;
+                while let Some((Some(edge), _)) = forward.get(&current) {
+                    current = edge.from;
+                    path.push(edge.clone());
+                }
+
+                path.reverse();
+
+                let mut current = meeting_node;
+                while let Some((Some(edge), _)) = backward.get(&current) {
+                    current = edge.to;
+                    path.push(edge.clone());
+                }
+
+                return Some(path);
+            }
+        }
+
+        None
+    }
+
+    /// Breadth-first search from `source` that doesn't explore further than `max_hops`.
+    fn bounded_bfs<F1, F2>(
+        source: NodeID,
+        max_hops: usize,
+        node_edges: F1,
+        edge_node: F2,
+    ) -> HashMap<NodeID, usize>
+    where
+        F1: Fn(NodeID) -> Vec<Edge>,
+        F2: Fn(&Edge) -> NodeID,
+    {
+        let mut distances = HashMap::new();
+        distances.insert(source, 0);
+
+        let mut frontier = vec![source];
+
+        for hop in 1..=max_hops {
+            let mut next_frontier = Vec::new();
+
+            for node in frontier {
+                for edge in node_edges(node) {
+                    let next = edge_node(&edge);
+
+                    if let Entry::Vacant(entry) = distances.entry(next) {
+                        entry.insert(hop);
+                        next_frontier.push(next);
+                    }
+                }
+            }
+
+            if next_frontier.is_empty() {
+                break;
+            }
+
+            frontier = next_frontier;
+        }
+
+        distances
+    }
+
+    /// All nodes within `max_hops` of `source` in the full graph together with their
+    /// distance, sorted by distance. The source itself is not included. If `ingoing`
+    /// is true, the edges are followed backwards so the result contains the nodes
+    /// linking to the source.
+    #[allow(unused)]
+    pub fn neighborhood(&self, source: Node, max_hops: usize, ingoing: bool) -> Vec<(Node, usize)> {
+        let graph = match self.full_graph() {
+            Some(graph) => graph,
+            None => return Vec::new(),
+        };
+
+        let source_id = match graph.node2id(&source) {
+            Some(id) => id,
+            None => return Vec::new(),
+        };
+
+        let distances = if ingoing {
+            Webgraph::<S>::bounded_bfs(
+                source_id,
+                max_hops,
+                |node| graph.ingoing_edges(node),
+                |edge| edge.from,
+            )
+        } else {
+            Webgraph::<S>::bounded_bfs(
+                source_id,
+                max_hops,
+                |node| graph.outgoing_edges(node),
+                |edge| edge.to,
+            )
+        };
+
+        let mut neighbours: Vec<_> = distances
+            .into_iter()
+            .filter(|(id, _)| *id != source_id)
+            .filter_map(|(id, dist)| Webgraph::resolve_node(graph, &id).map(|node| (node, dist)))
+            .collect();
+
+        neighbours.sort_by(|(a_node, a_dist), (b_node, b_dist)| {
+            a_dist.cmp(b_dist).then_with(|| a_node.cmp(b_node))
+        });
+
+        neighbours
+    }
+
+    /// The nodes in the full graph whose distance from `source` is between `min` and `max`
+    /// (both included), sorted by distance. The source itself is included if `min` is 0.
+    #[allow(unused)]
+    pub fn nodes_in_distance_range(&self, source: Node, min: usize, max: usize) -> Vec<Node> {
+        let graph = match self.full_graph() {
+            Some(graph) => graph,
+            None => return Vec::new(),
+        };
+
+        if min > max {
+            return Vec::new();
+        }
+
+        let source_id = match graph.node2id(&source) {
+            Some(id) => id,
+            None => return Vec::new(),
+        };
+
+        let mut nodes: Vec<_> = Webgraph::<S>::bounded_bfs(
+            source_id,
+            max,
+            |node| graph.outgoing_edges(node),
+            |edge| edge.to,
+        )
+        .into_iter()
+        .filter(|(_, dist)| *dist >= min)
+        .filter_map(|(id, dist)| Webgraph::resolve_node(graph, &id).map(|node| (dist, node)))
+        .collect();
+
+        nodes.sort();
+
+        nodes.into_iter().map(|(_, node)| node).collect()
+    }
+
+    /// Returns the nodes on a shortest path from `from` to `to` (both included)
+    /// in the full graph, or `None` if no such path exists.
+    #[allow(unused)]
+    pub fn shortest_path(&self, from: Node, to: Node) -> Option<Vec<Node>> {
+        let graph = self.full_graph()?;
+        let source = graph.node2id(&from)?;
+        let target = graph.node2id(&to)?;
+
+        let path = Webgraph::raw_shortest_path(graph, source, target)?;
+
+        let mut nodes = vec![from];
+        for edge in path {
+            nodes.push(graph.id2node(&edge.to)?);
+        }
+
+        Some(nodes)
+    }
+
+    /// Same as `shortest_path`, but returns the edges on the path with their labels.
+    /// If there are several edges between two nodes on the path, one of them is returned.
+    #[allow(unused)]
+    pub fn shortest_path_edges(&self, from: Node, to: Node) -> Option<Vec<FullEdge>> {
+        let graph = self.full_graph()?;
+        let source = graph.node2id(&from)?;
+        let target = graph.node2id(&to)?;
+
+        Webgraph::raw_shortest_path(graph, source, target)?
+            .into_iter()
+            .map(|edge| {
+                Some(FullEdge {
+                    from: graph.id2node(&edge.from)?,
+                    to: graph.id2node(&edge.to)?,
+                    label: edge.label,
+                })
+            })
+            .collect()
+    }
+
     #[allow(unused)]
     fn raw_reversed_distances(&self, source: Node) -> HashMap<NodeID, usize> {
-        self.full_graph
-            .as_ref()
+        self.full_graph()
             .map(|full_graph| {
                 Webgraph::dijkstra(
                     source,
                     |node| full_graph.ingoing_edges(node),
                     |edge| edge.from,
+                    |_| 1,
+                    None,
                     full_graph,
                 )
             })
@@ -350,12 +1358,46 @@ impl<S: Store> Webgraph<S> {
 
     #[allow(unused)]
     pub fn reversed_distances(&self, source: Node) -> HashMap<Node, usize> {
-        self.full_graph
-            .as_ref()
+        self.full_graph()
             .map(|full_graph| {
                 self.raw_reversed_distances(source)
                     .into_iter()
-                    .map(|(id, dist)| (full_graph.id2node(&id).expect("unknown node"), dist))
+                    .filter_map(|(id, dist)| {
+                        Webgraph::resolve_node(full_graph, &id).map(|node| (node, dist))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Distances in the full graph when every edge can be followed in both directions.
+    #[allow(unused)]
+    pub fn undirected_distances(&self, source: Node) -> HashMap<Node, usize> {
+        self.full_graph()
+            .map(|full_graph| {
+                let distances = Webgraph::dijkstra(
+                    source,
+                    |node| {
+                        // ingoing edges are flipped so `edge.to` is always the neighbour
+                        let mut edges = full_graph.outgoing_edges(node);
+                        edges.extend(full_graph.ingoing_edges(node).into_iter().map(|edge| Edge {
+                            from: edge.to,
+                            to: edge.from,
+                            label: edge.label,
+                        }));
+                        edges
+                    },
+                    |edge| edge.to,
+                    |_| 1,
+                    None,
+                    full_graph,
+                );
+
+                distances
+                    .into_iter()
+                    .filter_map(|(id, dist)| {
+                        Webgraph::resolve_node(full_graph, &id).map(|node| (node, dist))
+                    })
                     .collect()
             })
             .unwrap_or_default()
@@ -363,32 +1405,46 @@ impl<S: Store> Webgraph<S> {
 
     #[allow(unused)]
     pub fn host_distances(&self, source: Node) -> HashMap<Node, usize> {
-        self.host_graph
-            .as_ref()
+        self.host_graph()
             .map(|host_graph| {
                 let distances = Webgraph::dijkstra(
                     source,
                     |node| host_graph.outgoing_edges(node),
                     |edge| edge.to,
+                    |_| 1,
+                    None,
                     host_graph,
                 );
 
                 distances
                     .into_iter()
-                    .map(|(id, dist)| (host_graph.id2node(&id).expect("unknown node"), dist))
+                    .filter_map(|(id, dist)| {
+                        Webgraph::resolve_node(host_graph, &id).map(|node| (node, dist))
+                    })
                     .collect()
             })
             .unwrap_or_default()
     }
 
     fn raw_host_reversed_distances(&self, source: Node) -> HashMap<NodeID, usize> {
-        self.host_graph
-            .as_ref()
+        self.raw_host_reversed_distances_cutoff(source, None)
+    }
+
+    /// Reversed distances in the host graph where nodes further away than `max_dist`
+    /// are not explored.
+    fn raw_host_reversed_distances_cutoff(
+        &self,
+        source: Node,
+        max_dist: Option<usize>,
+    ) -> HashMap<NodeID, usize> {
+        self.host_graph()
             .map(|host_graph| {
                 Webgraph::dijkstra(
                     source,
                     |node| host_graph.ingoing_edges(node),
                     |edge| edge.from,
+                    |_| 1,
+                    max_dist,
                     host_graph,
                 )
             })
@@ -397,24 +1453,20 @@ impl<S: Store> Webgraph<S> {
 
     #[allow(unused)]
     pub fn host_reversed_distances(&self, source: Node) -> HashMap<Node, usize> {
-        self.host_graph
-            .as_ref()
+        self.host_graph()
             .map(|host_graph| {
                 self.raw_host_reversed_distances(source)
                     .into_iter()
-                    .map(|(id, dist)| (host_graph.id2node(&id).expect("unknown node"), dist))
+                    .filter_map(|(id, dist)| {
+                        Webgraph::resolve_node(host_graph, &id).map(|node| (node, dist))
+                    })
                     .collect()
             })
             .unwrap_or_default()
     }
 
-    fn calculate_centrality<F>(graph: &GraphStore<S>, node_distances: F) -> HashMap<Node, f64>
-    where
-        F: Fn(Node) -> HashMap<NodeID, usize>,
-    {
-        let nodes: Vec<_> = graph.nodes().collect();
-        info!("Found {} nodes in the graph", nodes.len());
-        let pb = ProgressBar::new(nodes.len() as u64);
+    fn progress_bar(len: u64) -> ProgressBar {
+        let pb = ProgressBar::new(len);
         pb.set_style(
             ProgressStyle::default_bar()
                 .template(
@@ -422,157 +1474,3146 @@ impl<S: Store> Webgraph<S> {
                 )
                 .progress_chars("#>-"),
         );
-        let norm_factor = (nodes.len() - 1) as f64;
-        nodes
-            .iter()
-            .progress_with(pb)
-            .map(|node_id| {
-                let node = graph.id2node(node_id).expect("unknown node");
-                let centrality_values: HashMap<NodeID, f64> = node_distances(node.clone())
-                    .into_iter()
-                    .filter(|(other_id, _)| *other_id != *node_id)
-                    .map(|(other_node, dist)| (other_node, 1f64 / dist as f64))
-                    .collect();
-
-                let centrality = centrality_values
-                    .into_iter()
-                    .map(|(_, val)| val)
-                    .sum::<f64>()
-                    / norm_factor;
 
-                (node, centrality)
-            })
-            .filter(|(_, centrality)| *centrality > 0.0)
-            .collect()
-    }
-
-    #[allow(unused)]
-    pub fn harmonic_centrality(&self) -> HashMap<Node, f64> {
-        self.full_graph
-            .as_ref()
-            .map(|full_graph| {
-                Webgraph::calculate_centrality(full_graph, |node| self.raw_reversed_distances(node))
-            })
-            .unwrap_or_default()
+        pb
     }
 
-    pub fn host_harmonic_centrality(&self) -> HashMap<Node, f64> {
-        self.host_graph
-            .as_ref()
-            .map(|host_graph| {
-                Webgraph::calculate_centrality(host_graph, |node| {
-                    self.raw_host_reversed_distances(node)
-                })
-            })
-            .unwrap_or_default()
-    }
+    /// The progress reporter used when no callback is given. Shows a progress bar on
+    /// stderr unless progress has been disabled with `with_progress(false)`, in which
+    /// case no progress bar is constructed.
+    fn default_progress(&self) -> impl Fn(u64, u64) + Sync {
+        let pb = if self.show_progress {
+            Some(Webgraph::<S>::progress_bar(0))
+        } else {
+            None
+        };
 
-    pub fn flush(&self) {
-        if let Some(full_graph) = &self.full_graph {
-            full_graph.flush();
-        }
-        if let Some(host_graph) = &self.host_graph {
-            host_graph.flush();
+        move |_, total| {
+            if let Some(pb) = &pb {
+                pb.set_length(total);
+                pb.inc(1);
+            }
         }
     }
 
-    pub fn ingoing_edges(&self, node: Node) -> Vec<FullEdge> {
-        if let Some(graph) = &self.full_graph {
-            if let Some(node_id) = graph.node2id(&node) {
-                graph
-                    .ingoing_edges(node_id)
-                    .into_iter()
-                    .map(|edge| FullEdge {
-                        from: graph.id2node(&edge.from).unwrap(),
-                        to: graph.id2node(&edge.to).unwrap(),
-                        label: edge.label,
-                    })
-                    .collect()
-            } else {
-                Vec::new()
-            }
-        } else {
-            Vec::new()
-        }
+    #[allow(unused)]
+    pub fn with_progress(mut self, show_progress: bool) -> Self {
+        self.show_progress = show_progress;
+        self
     }
-}
 
-impl From<FrozenWebgraph> for Webgraph {
-    fn from(frozen: FrozenWebgraph) -> Self {
-        let path = match &frozen.root {
-            DirEntry::Folder { name, entries: _ } => name.clone(),
-            DirEntry::File {
-                name: _,
-                content: _,
-            } => {
-                panic!("Cannot open webgraph from a file - must be directory.")
-            }
-        };
+    /// Looks up the node with the given id. Ids without a node (e.g. in a partially
+    /// written store) are logged, so callers can skip them instead of panicking.
+    fn resolve_node(graph: &GraphStore<S>, id: &NodeID) -> Option<Node> {
+        let node = graph.id2node(id);
 
-        if Path::new(&path).exists() {
-            fs::remove_dir_all(&path).unwrap();
+        if node.is_none() {
+            warn!("unable to resolve node id {}", id);
         }
 
-        directory::recreate_folder(&frozen.root).unwrap();
+        node
+    }
 
-        let mut builder = WebgraphBuilder::new(path);
+    fn node_centrality<F, C>(
+        graph: &GraphStore<S>,
+        node_id: &NodeID,
+        node_distances: &F,
+        contribution: &C,
+        norm_factor: f64,
+    ) -> Option<(Node, f64)>
+    where
+        F: Fn(Node) -> HashMap<NodeID, usize>,
+        C: Fn(usize) -> f64,
+    {
+        let node = Webgraph::resolve_node(graph, node_id)?;
+        let centrality_values: HashMap<NodeID, f64> = node_distances(node.clone())
+            .into_iter()
+            .filter(|(other_id, _)| *other_id != *node_id)
+            .map(|(other_node, dist)| (other_node, contribution(dist)))
+            .collect();
 
-        if frozen.has_full {
-            builder = builder.with_full_graph();
-        }
+        let centrality = centrality_values
+            .into_iter()
+            .map(|(_, val)| val)
+            .sum::<f64>()
+            / norm_factor;
 
-        if frozen.has_host {
-            builder = builder.with_host_graph();
-        }
+        Some((node, centrality))
+    }
 
-        builder.open()
+    /// Sums `contribution(dist)` over the nodes that can reach each node according to
+    /// `node_distances`. The harmonic centrality uses `harmonic_contribution`.
+    fn calculate_centrality<F, C, P>(
+        graph: &GraphStore<S>,
+        node_distances: F,
+        contribution: C,
+        normalization: Normalization,
+        progress: P,
+    ) -> HashMap<Node, f64>
+    where
+        F: Fn(Node) -> HashMap<NodeID, usize> + Sync,
+        C: Fn(usize) -> f64 + Sync,
+        P: Fn(u64, u64) + Sync,
+    {
+        let nodes: Vec<_> = graph.nodes().collect();
+        info!("Found {} nodes in the graph", nodes.len());
+        let total = nodes.len() as u64;
+        let finished = AtomicU64::new(0);
+        let norm_factor = normalization.norm_factor(nodes.len());
+        nodes
+            .par_iter()
+            .filter_map(|node_id| {
+                let res = Webgraph::node_centrality(
+                    graph,
+                    node_id,
+                    &node_distances,
+                    &contribution,
+                    norm_factor,
+                );
+                report_progress(&progress, &finished, total);
+                res
+            })
+            .filter(|(_, centrality)| *centrality > 0.0)
+            .collect()
     }
-}
 
-impl From<Webgraph> for FrozenWebgraph {
-    fn from(graph: Webgraph) -> Self {
-        graph.flush();
-        let path = graph.path.clone();
-        let has_full = graph.full_graph.is_some();
-        let has_host = graph.host_graph.is_some();
-        drop(graph);
-        let root = directory::scan_folder(path).unwrap();
+    /// Same as `calculate_centrality`, but only keeps the `n` nodes with the highest
+    /// centrality in a bounded heap. The result is sorted by descending centrality.
+    fn calculate_top_centrality<F, P>(
+        graph: &GraphStore<S>,
+        node_distances: F,
+        n: usize,
+        progress: P,
+    ) -> Vec<(Node, f64)>
+    where
+        F: Fn(Node) -> HashMap<NodeID, usize> + Sync,
+        P: Fn(u64, u64) + Sync,
+    {
+        let nodes: Vec<_> = graph.nodes().collect();
+        info!("Found {} nodes in the graph", nodes.len());
+        let total = nodes.len() as u64;
+        let finished = AtomicU64::new(0);
+        let norm_factor = Normalization::Normalized.norm_factor(nodes.len());
+
+        let heap = nodes
+            .par_iter()
+            .filter_map(|node_id| {
+                let res = Webgraph::node_centrality(
+                    graph,
+                    node_id,
+                    &node_distances,
+                    &harmonic_contribution,
+                    norm_factor,
+                );
+                report_progress(&progress, &finished, total);
+                res
+            })
+            .filter(|(_, centrality)| *centrality > 0.0)
+            .fold(BinaryHeap::new, |mut heap, (node, centrality)| {
+                push_bounded(&mut heap, ScoredNode { centrality, node }, n);
+                heap
+            })
+            .reduce(BinaryHeap::new, |mut heap, other| {
+                for cmp::Reverse(scored) in other {
+                    push_bounded(&mut heap, scored, n);
+                }
+                heap
+            });
+
+        heap.into_sorted_vec()
+            .into_iter()
+            .map(|cmp::Reverse(scored)| (scored.node, scored.centrality))
+            .collect()
+    }
+
+    /// Harmonic centrality where each node that can reach `node_id` contributes
+    /// `count / dist` instead of `1 / dist`, and `count` is the number of its links
+    /// to the nodes one step closer to `node_id` (see `GraphStore::ingoing_edge_counts`).
+    fn weighted_node_centrality(
+        graph: &GraphStore<S>,
+        node_id: &NodeID,
+        norm_factor: f64,
+    ) -> Option<(Node, f64)> {
+        let node = Webgraph::resolve_node(graph, node_id)?;
+
+        let mut visited = HashSet::new();
+        visited.insert(*node_id);
+
+        let mut frontier = vec![*node_id];
+        let mut dist = 0;
+        let mut centrality = 0.0;
+
+        while !frontier.is_empty() {
+            dist += 1;
+
+            let mut counts: HashMap<NodeID, usize> = HashMap::new();
+            for other in frontier {
+                for (from, count) in graph.ingoing_edge_counts(other) {
+                    if !visited.contains(&from) {
+                        *counts.entry(from).or_default() += count;
+                    }
+                }
+            }
+
+            centrality += counts.values().sum::<usize>() as f64 / dist as f64;
+
+            frontier = counts.into_keys().collect();
+            visited.extend(frontier.iter().copied());
+        }
+
+        Some((node, centrality / norm_factor))
+    }
+
+    /// Closeness centrality is `(reachable - 1) / sum_of_distances`, where only the nodes
+    /// that can reach the node are considered. Nodes that can't be reached from any
+    /// other node get a centrality of 0.
+    fn calculate_closeness_centrality<F, P>(
+        graph: &GraphStore<S>,
+        node_distances: F,
+        progress: P,
+    ) -> HashMap<Node, f64>
+    where
+        F: Fn(Node) -> HashMap<NodeID, usize> + Sync,
+        P: Fn(u64, u64) + Sync,
+    {
+        let nodes: Vec<_> = graph.nodes().collect();
+        info!("Found {} nodes in the graph", nodes.len());
+        let total = nodes.len() as u64;
+        let finished = AtomicU64::new(0);
+        nodes
+            .par_iter()
+            .filter_map(|node_id| {
+                let res = Webgraph::resolve_node(graph, node_id).map(|node| {
+                    let (reachable, sum_distances) = node_distances(node.clone())
+                        .into_iter()
+                        .filter(|(other_id, _)| *other_id != *node_id)
+                        .fold((0_usize, 0_usize), |(reachable, sum), (_, dist)| {
+                            (reachable + 1, sum + dist)
+                        });
+
+                    let centrality = if sum_distances == 0 {
+                        0.0
+                    } else {
+                        reachable as f64 / sum_distances as f64
+                    };
+
+                    (node, centrality)
+                });
+
+                report_progress(&progress, &finished, total);
+
+                res
+            })
+            .collect()
+    }
+
+    #[allow(unused)]
+    pub fn host_closeness_centrality(&self) -> HashMap<Node, f64> {
+        self.host_graph()
+            .map(|host_graph| {
+                Webgraph::calculate_closeness_centrality(
+                    host_graph,
+                    |node| self.raw_host_reversed_distances(node),
+                    self.default_progress(),
+                )
+            })
+            .unwrap_or_default()
+    }
+
+    #[allow(unused)]
+    pub fn harmonic_centrality(&self) -> HashMap<Node, f64> {
+        self.harmonic_centrality_with_progress(self.default_progress())
+    }
+
+    /// Same as `harmonic_centrality`, but `progress` is called with the number of
+    /// processed nodes and the total number of nodes every time a node is processed.
+    #[allow(unused)]
+    pub fn harmonic_centrality_with_progress<P>(&self, progress: P) -> HashMap<Node, f64>
+    where
+        P: Fn(u64, u64) + Sync,
+    {
+        self.calculate_harmonic_centrality(Normalization::default(), progress)
+    }
+
+    /// Same as `harmonic_centrality`, but scaled according to `normalization`.
+    #[allow(unused)]
+    pub fn harmonic_centrality_with_normalization(
+        &self,
+        normalization: Normalization,
+    ) -> HashMap<Node, f64> {
+        self.calculate_harmonic_centrality(normalization, self.default_progress())
+    }
+
+    /// Same as `harmonic_centrality`, but the nodes are processed in chunks of `chunk_size`
+    /// nodes and the centralities of each chunk are appended to `path`, with one
+    /// `node\tcentrality` line per node with a positive centrality, before the next chunk
+    /// is started. After each chunk, `checkpoint` is called with a token that can be passed
+    /// as `resume` to continue an interrupted run after that chunk. Lines written after the
+    /// token was created are removed from the file when resuming, and the graph must not
+    /// change in between.
+    ///
+    /// Only the centralities of a single chunk are kept in memory, besides the ids of all
+    /// nodes and the distances of the nodes currently being processed (one map per thread).
+    /// The running time is the same as for `harmonic_centrality`, since a Dijkstra is still
+    /// run from every node, so the chunk size trades memory against the work that is lost
+    /// when a run is interrupted.
+    #[allow(unused)]
+    pub fn harmonic_centrality_chunked<P, F>(
+        &self,
+        path: P,
+        chunk_size: usize,
+        resume: Option<CentralityResumeToken>,
+        mut checkpoint: F,
+    ) -> std::io::Result<()>
+    where
+        P: AsRef<Path>,
+        F: FnMut(CentralityResumeToken),
+    {
+        let full_graph = match self.full_graph() {
+            Some(full_graph) => full_graph,
+            None => return Ok(()),
+        };
+
+        let mut token = resume.unwrap_or_default();
+        // not truncated on open, since the lines up to the resume token have to be kept
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(false)
+            .open(path)?;
+        file.set_len(token.file_len)?;
+        file.seek(SeekFrom::End(0))?;
+        let mut writer = BufWriter::new(file);
+
+        // the ids are sorted so the chunks are the same every time the graph is processed
+        let mut nodes: Vec<_> = full_graph.nodes().collect();
+        nodes.sort_unstable();
+        info!("Found {} nodes in the graph", nodes.len());
+
+        let start = token.processed_nodes.min(nodes.len());
+        let total = nodes.len() as u64;
+        let finished = AtomicU64::new(start as u64);
+        let norm_factor = Normalization::Normalized.norm_factor(nodes.len());
+        let progress = self.default_progress();
+
+        for chunk in nodes[start..].chunks(chunk_size.max(1)) {
+            let centralities: Vec<_> = chunk
+                .par_iter()
+                .filter_map(|node_id| {
+                    let res = Webgraph::node_centrality(
+                        full_graph,
+                        node_id,
+                        &|node| self.raw_reversed_distances(node),
+                        &harmonic_contribution,
+                        norm_factor,
+                    );
+                    report_progress(&progress, &finished, total);
+                    res
+                })
+                .filter(|(_, centrality)| *centrality > 0.0)
+                .collect();
+
+            for (node, centrality) in centralities {
+                writeln!(writer, "{}\t{}", node.name, centrality)?;
+            }
+            writer.flush()?;
+
+            token.processed_nodes += chunk.len();
+            token.file_len = writer.get_mut().stream_position()?;
+            checkpoint(token);
+        }
+
+        Ok(())
+    }
+
+    fn calculate_harmonic_centrality<P>(
+        &self,
+        normalization: Normalization,
+        progress: P,
+    ) -> HashMap<Node, f64>
+    where
+        P: Fn(u64, u64) + Sync,
+    {
+        self.full_graph()
+            .map(|full_graph| {
+                Webgraph::calculate_centrality(
+                    full_graph,
+                    |node| self.raw_reversed_distances(node),
+                    harmonic_contribution,
+                    normalization,
+                    progress,
+                )
+            })
+            .unwrap_or_default()
+    }
+
+    /// Same as `harmonic_centrality`, but only edges with a label in `allowed` are traversed.
+    #[allow(unused)]
+    pub fn harmonic_centrality_with_labels(&self, allowed: &HashSet<String>) -> HashMap<Node, f64> {
+        self.full_graph()
+            .map(|full_graph| {
+                Webgraph::calculate_centrality(
+                    full_graph,
+                    |node| {
+                        Webgraph::dijkstra(
+                            node,
+                            |node_id| {
+                                full_graph
+                                    .ingoing_edges(node_id)
+                                    .into_iter()
+                                    .filter(|edge| allowed.contains(&edge.label))
+                                    .collect()
+                            },
+                            |edge| edge.from,
+                            |_| 1,
+                            None,
+                            full_graph,
+                        )
+                    },
+                    harmonic_contribution,
+                    Normalization::default(),
+                    self.default_progress(),
+                )
+            })
+            .unwrap_or_default()
+    }
+
+    /// Generalization of `harmonic_centrality`, where a node at distance `dist`
+    /// contributes `decay(dist)` to the centrality instead of `1 / dist`, e.g.
+    /// `|dist| (-0.5 * dist as f64).exp()` for an exponential decay.
+    #[allow(unused)]
+    pub fn centrality_with_decay<D>(&self, decay: D) -> HashMap<Node, f64>
+    where
+        D: Fn(usize) -> f64 + Sync,
+    {
+        self.full_graph()
+            .map(|full_graph| {
+                Webgraph::calculate_centrality(
+                    full_graph,
+                    |node| self.raw_reversed_distances(node),
+                    decay,
+                    Normalization::default(),
+                    self.default_progress(),
+                )
+            })
+            .unwrap_or_default()
+    }
+
+    /// Same as `centrality_with_decay`, but on the host graph.
+    #[allow(unused)]
+    pub fn host_centrality_with_decay<D>(&self, decay: D) -> HashMap<Node, f64>
+    where
+        D: Fn(usize) -> f64 + Sync,
+    {
+        self.host_graph()
+            .map(|host_graph| {
+                Webgraph::calculate_centrality(
+                    host_graph,
+                    |node| self.raw_host_reversed_distances(node),
+                    decay,
+                    Normalization::default(),
+                    self.default_progress(),
+                )
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn host_harmonic_centrality(&self) -> HashMap<Node, f64> {
+        self.host_harmonic_centrality_with_progress(self.default_progress())
+    }
+
+    /// Approximates the harmonic centrality by only considering nodes within `max_dist`
+    /// of each node. Each node further away than `max_dist` would have contributed
+    /// less than `1 / max_dist`, so the approximation is never larger than the exact
+    /// centrality and at most `1 / (max_dist + 1)` smaller.
+    #[allow(unused)]
+    pub fn host_harmonic_centrality_cutoff(&self, max_dist: usize) -> HashMap<Node, f64> {
+        self.host_graph()
+            .map(|host_graph| {
+                Webgraph::calculate_centrality(
+                    host_graph,
+                    |node| self.raw_host_reversed_distances_cutoff(node, Some(max_dist)),
+                    harmonic_contribution,
+                    Normalization::default(),
+                    self.default_progress(),
+                )
+            })
+            .unwrap_or_default()
+    }
+
+    #[allow(unused)]
+    pub fn host_harmonic_centrality_with_progress<P>(&self, progress: P) -> HashMap<Node, f64>
+    where
+        P: Fn(u64, u64) + Sync,
+    {
+        self.calculate_host_harmonic_centrality(Normalization::default(), progress)
+    }
+
+    /// Same as `host_harmonic_centrality`, but scaled according to `normalization`.
+    #[allow(unused)]
+    pub fn host_harmonic_centrality_with_normalization(
+        &self,
+        normalization: Normalization,
+    ) -> HashMap<Node, f64> {
+        self.calculate_host_harmonic_centrality(normalization, self.default_progress())
+    }
+
+    /// Same as `host_harmonic_centrality`, but more page links between two hosts make the
+    /// link stronger: a host with `count` page links towards the node contributes
+    /// `count / dist` instead of `1 / dist`. `count` is the number of distinct page links
+    /// behind the host edge (see `EdgeEvidence`), regardless of their labels.
+    #[allow(unused)]
+    pub fn host_weighted_harmonic_centrality(&self) -> HashMap<Node, f64> {
+        let host_graph = match self.host_graph() {
+            Some(host_graph) => host_graph,
+            None => return HashMap::new(),
+        };
+
+        let nodes: Vec<_> = host_graph.nodes().collect();
+        info!("Found {} nodes in the graph", nodes.len());
+        let total = nodes.len() as u64;
+        let finished = AtomicU64::new(0);
+        let norm_factor = Normalization::Normalized.norm_factor(nodes.len());
+        let progress = self.default_progress();
+
+        nodes
+            .par_iter()
+            .filter_map(|node_id| {
+                let res = Webgraph::weighted_node_centrality(host_graph, node_id, norm_factor);
+                report_progress(&progress, &finished, total);
+                res
+            })
+            .filter(|(_, centrality)| *centrality > 0.0)
+            .collect()
+    }
+
+    /// Same as `host_harmonic_centrality`, but the centralities are written to `path` as
+    /// they are computed, so the result never has to fit in memory. The file has one
+    /// `node\tcentrality` line per node with a positive centrality, in no particular order.
+    #[allow(unused)]
+    pub fn host_harmonic_centrality_to_file<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        let host_graph = match self.host_graph() {
+            Some(host_graph) => host_graph,
+            None => return Ok(()),
+        };
+
+        let mut writer = BufWriter::new(fs::File::create(path)?);
+        let (sender, receiver) = mpsc::sync_channel::<(Node, f64)>(10_000);
+
+        let writer_thread = thread::spawn(move || -> std::io::Result<()> {
+            for (node, centrality) in receiver {
+                writeln!(writer, "{}\t{}", node.name, centrality)?;
+            }
+
+            writer.flush()
+        });
+
+        let nodes: Vec<_> = host_graph.nodes().collect();
+        info!("Found {} nodes in the graph", nodes.len());
+        let total = nodes.len() as u64;
+        let finished = AtomicU64::new(0);
+        let norm_factor = Normalization::Normalized.norm_factor(nodes.len());
+        let progress = self.default_progress();
+
+        // sending only fails if the writer has stopped, in which case its error is returned below
+        let _ = nodes
+            .par_iter()
+            .try_for_each_with(sender, |sender, node_id| {
+                let res = Webgraph::node_centrality(
+                    host_graph,
+                    node_id,
+                    &|node| self.raw_host_reversed_distances(node),
+                    &harmonic_contribution,
+                    norm_factor,
+                );
+                report_progress(&progress, &finished, total);
+
+                match res {
+                    Some((node, centrality)) if centrality > 0.0 => sender.send((node, centrality)),
+                    _ => Ok(()),
+                }
+            });
+
+        writer_thread.join().expect("centrality writer panicked")
+    }
+
+    fn calculate_host_harmonic_centrality<P>(
+        &self,
+        normalization: Normalization,
+        progress: P,
+    ) -> HashMap<Node, f64>
+    where
+        P: Fn(u64, u64) + Sync,
+    {
+        self.host_graph()
+            .map(|host_graph| {
+                Webgraph::calculate_centrality(
+                    host_graph,
+                    |node| self.raw_host_reversed_distances(node),
+                    harmonic_contribution,
+                    normalization,
+                    progress,
+                )
+            })
+            .unwrap_or_default()
+    }
+
+    /// Same as `host_harmonic_centrality`, but only computes the centrality of the hosts of
+    /// `nodes`. The centrality is still measured against the entire host graph.
+    #[allow(unused)]
+    pub fn host_harmonic_centrality_for(&self, nodes: &[Node]) -> HashMap<Node, f64> {
+        let host_graph = match self.host_graph() {
+            Some(host_graph) => host_graph,
+            None => return HashMap::new(),
+        };
+
+        let node_ids: HashSet<_> = nodes
+            .iter()
+            .filter_map(|node| {
+                host_graph.node2id(&node.clone().into_host(&self.collapse_subdomains))
+            })
+            .collect();
+
+        self.host_harmonic_centrality_of(host_graph, &node_ids)
+    }
+
+    /// Same as `host_harmonic_centrality`, but only computes the centrality of hosts with
+    /// links from at least `min_in_degree` distinct hosts. All hosts still count when
+    /// measuring the distances, so this only changes which hosts are in the result and
+    /// not their centrality.
+    #[allow(unused)]
+    pub fn host_harmonic_centrality_min_degree(&self, min_in_degree: usize) -> HashMap<Node, f64> {
+        let host_graph = match self.host_graph() {
+            Some(host_graph) => host_graph,
+            None => return HashMap::new(),
+        };
+
+        let node_ids: HashSet<_> = host_graph
+            .nodes()
+            .filter(|node_id| {
+                let in_degree = host_graph
+                    .ingoing_edges(*node_id)
+                    .into_iter()
+                    .map(|edge| edge.from)
+                    .collect::<HashSet<_>>()
+                    .len();
+
+                in_degree >= min_in_degree
+            })
+            .collect();
+
+        self.host_harmonic_centrality_of(host_graph, &node_ids)
+    }
+
+    /// Normalized harmonic centrality of `node_ids` in the host graph.
+    fn host_harmonic_centrality_of(
+        &self,
+        host_graph: &GraphStore<S>,
+        node_ids: &HashSet<NodeID>,
+    ) -> HashMap<Node, f64> {
+        let norm_factor = Normalization::Normalized.norm_factor(host_graph.nodes().count());
+        node_ids
+            .par_iter()
+            .filter_map(|node_id| {
+                Webgraph::node_centrality(
+                    host_graph,
+                    node_id,
+                    &|node| self.raw_host_reversed_distances(node),
+                    &harmonic_contribution,
+                    norm_factor,
+                )
+            })
+            .filter(|(_, centrality)| *centrality > 0.0)
+            .collect()
+    }
+
+    /// Patches the host harmonic centrality in `cached` (as computed by `host_harmonic_centrality`)
+    /// after edges incident to `changed_nodes` have been inserted.
+    ///
+    /// New edges only shorten the distances towards the nodes downstream of the changed nodes,
+    /// so only the centrality of the nodes within `radius` hops downstream is recomputed.
+    /// This is an approximation: nodes further away keep their stale centrality even though
+    /// they may have become closer to other nodes, and if new nodes were added, the
+    /// untouched nodes are still normalized by the old number of nodes. Run a full
+    /// `host_harmonic_centrality` now and then to get rid of the accumulated staleness.
+    #[allow(unused)]
+    pub fn update_centrality(
+        &self,
+        cached: &mut HashMap<Node, f64>,
+        changed_nodes: &[Node],
+        radius: usize,
+    ) {
+        let host_graph = match self.host_graph() {
+            Some(host_graph) => host_graph,
+            None => return,
+        };
+
+        self.flush();
+
+        let mut affected = HashSet::new();
+        for node in changed_nodes {
+            let host = node.clone().into_host(&self.collapse_subdomains);
+
+            if let Some(node_id) = host_graph.node2id(&host) {
+                affected.extend(
+                    Webgraph::<S>::bounded_bfs(
+                        node_id,
+                        radius,
+                        |node| host_graph.outgoing_edges(node),
+                        |edge| edge.to,
+                    )
+                    .into_keys(),
+                );
+            }
+        }
+
+        let norm_factor = Normalization::Normalized.norm_factor(host_graph.nodes().count());
+        let updated: Vec<_> = affected
+            .par_iter()
+            .filter_map(|node_id| {
+                Webgraph::node_centrality(
+                    host_graph,
+                    node_id,
+                    &|node| self.raw_host_reversed_distances(node),
+                    &harmonic_contribution,
+                    norm_factor,
+                )
+            })
+            .collect();
+
+        for (node, centrality) in updated {
+            if centrality > 0.0 {
+                cached.insert(node, centrality);
+            } else {
+                cached.remove(&node);
+            }
+        }
+    }
+
+    fn calculate_pagerank(
+        graph: &GraphStore<S>,
+        damping: f64,
+        iterations: usize,
+    ) -> HashMap<Node, f64> {
+        let nodes: Vec<_> = graph.nodes().collect();
+
+        if nodes.is_empty() {
+            return HashMap::new();
+        }
+
+        let num_nodes = nodes.len() as f64;
+        let out_degrees: HashMap<NodeID, usize> = nodes
+            .iter()
+            .map(|node_id| (*node_id, graph.outgoing_edges(*node_id).len()))
+            .collect();
+
+        let mut ranks: HashMap<NodeID, f64> = nodes
+            .iter()
+            .map(|node_id| (*node_id, 1.0 / num_nodes))
+            .collect();
+
+        for _ in 0..iterations {
+            let dangling_mass: f64 = nodes
+                .iter()
+                .filter(|node_id| out_degrees[*node_id] == 0)
+                .map(|node_id| ranks[node_id])
+                .sum();
+
+            let base = (1.0 - damping) / num_nodes + damping * dangling_mass / num_nodes;
+
+            let new_ranks: HashMap<NodeID, f64> = nodes
+                .iter()
+                .map(|node_id| {
+                    let incoming: f64 = graph
+                        .ingoing_edges(*node_id)
+                        .into_iter()
+                        .map(|edge| ranks[&edge.from] / out_degrees[&edge.from] as f64)
+                        .sum();
+
+                    (*node_id, base + damping * incoming)
+                })
+                .collect();
+
+            let delta: f64 = nodes
+                .iter()
+                .map(|node_id| (new_ranks[node_id] - ranks[node_id]).abs())
+                .sum();
+
+            ranks = new_ranks;
+
+            if delta < PAGERANK_TOLERANCE {
+                break;
+            }
+        }
+
+        ranks
+            .into_iter()
+            .filter_map(|(id, rank)| Webgraph::resolve_node(graph, &id).map(|node| (node, rank)))
+            .collect()
+    }
+
+    /// Computes pagerank on the host graph using power iteration. The iteration stops
+    /// when the L1 difference between two iterations is below a small tolerance or when
+    /// `iterations` is reached. The rank of dangling nodes is distributed uniformly.
+    #[allow(unused)]
+    pub fn host_pagerank(&self, damping: f64, iterations: usize) -> HashMap<Node, f64> {
+        self.host_graph()
+            .map(|host_graph| Webgraph::calculate_pagerank(host_graph, damping, iterations))
+            .unwrap_or_default()
+    }
+
+    #[allow(unused)]
+    pub fn full_pagerank(&self, damping: f64, iterations: usize) -> HashMap<Node, f64> {
+        self.full_graph()
+            .map(|full_graph| Webgraph::calculate_pagerank(full_graph, damping, iterations))
+            .unwrap_or_default()
+    }
+
+    /// Assigns a component id to each node in the full graph, where edges are
+    /// treated as undirected. Components are numbered in the order of the smallest
+    /// node id they contain, so the ids are stable for a given graph.
+    #[allow(unused)]
+    pub fn weakly_connected_components(&self) -> HashMap<Node, usize> {
+        let graph = match self.full_graph() {
+            Some(graph) => graph,
+            None => return HashMap::new(),
+        };
+
+        let mut nodes: Vec<_> = graph.nodes().collect();
+        nodes.sort_unstable();
+
+        let mut parents: HashMap<NodeID, NodeID> =
+            nodes.iter().map(|node_id| (*node_id, *node_id)).collect();
+
+        for edge in graph.edges() {
+            let from_root = find_root(&mut parents, edge.from);
+            let to_root = find_root(&mut parents, edge.to);
+
+            if from_root != to_root {
+                parents.insert(cmp::max(from_root, to_root), cmp::min(from_root, to_root));
+            }
+        }
+
+        let mut component_ids: HashMap<NodeID, usize> = HashMap::new();
+        let mut components = HashMap::new();
+
+        for node_id in nodes {
+            let root = find_root(&mut parents, node_id);
+            let next_id = component_ids.len();
+            let component = *component_ids.entry(root).or_insert(next_id);
+
+            if let Some(node) = Webgraph::resolve_node(graph, &node_id) {
+                components.insert(node, component);
+            }
+        }
+
+        components
+    }
+
+    #[allow(unused)]
+    pub fn num_components(&self) -> usize {
+        self.weakly_connected_components()
+            .into_values()
+            .collect::<HashSet<_>>()
+            .len()
+    }
+
+    /// Creates a new graph in a temporary location containing only the edges where
+    /// both endpoints are in `nodes`. The subgraph is built from the full graph if
+    /// present, otherwise from the host graph.
+    #[allow(unused)]
+    pub fn subgraph(&self, nodes: &HashSet<Node>) -> Webgraph {
+        let mut builder = WebgraphBuilder::new(crate::gen_temp_path())
+            .collapse_subdomains(self.collapse_subdomains.clone());
+
+        if self.full_graph.is_some() {
+            builder = builder.with_full_graph();
+        }
+
+        if self.host_graph.is_some() {
+            builder = builder.with_host_graph();
+        }
+
+        let mut subgraph = builder.open();
+
+        if let Some(graph) = self.full_graph().or(self.host_graph()) {
+            let node_ids: HashSet<NodeID> = nodes
+                .iter()
+                .filter_map(|node| graph.node2id(node))
+                .collect();
+
+            for edge in graph.edges() {
+                if node_ids.contains(&edge.from) && node_ids.contains(&edge.to) {
+                    subgraph.insert(
+                        graph.id2node(&edge.from).expect("unknown node"),
+                        graph.id2node(&edge.to).expect("unknown node"),
+                        edge.label,
+                    );
+                }
+            }
+        }
+
+        subgraph.flush();
+
+        subgraph
+    }
+
+    /// Node and edge counts of the full and host graphs. `avg_out_degree` is
+    /// calculated on the full graph.
+    #[allow(unused)]
+    pub fn stats(&self) -> GraphStats {
+        let (num_nodes, num_edges) = self
+            .full_graph()
+            .map(|graph| (graph.nodes().count(), graph.edges().count()))
+            .unwrap_or_default();
+
+        let (num_host_nodes, num_host_edges) = self
+            .host_graph()
+            .map(|graph| (graph.nodes().count(), graph.edges().count()))
+            .unwrap_or_default();
+
+        let avg_out_degree = if num_nodes > 0 {
+            num_edges as f64 / num_nodes as f64
+        } else {
+            0.0
+        };
+
+        GraphStats {
+            num_nodes,
+            num_edges,
+            num_host_nodes,
+            num_host_edges,
+            avg_out_degree,
+        }
+    }
+
+    /// Nodes in the full graph with an edge to themselves, sorted by name.
+    #[allow(unused)]
+    pub fn self_loops(&self) -> Vec<Node> {
+        let graph = match self.full_graph() {
+            Some(graph) => graph,
+            None => return Vec::new(),
+        };
+
+        let mut nodes: Vec<_> = graph
+            .edges()
+            .filter(|edge| edge.from == edge.to)
+            .map(|edge| edge.from)
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .filter_map(|id| Webgraph::resolve_node(graph, &id))
+            .collect();
+        nodes.sort();
+
+        nodes
+    }
+
+    /// Checks the full graph for edges referring to unknown node ids and for nodes
+    /// without any edges. Intended for checking a store after a crash.
+    #[allow(unused)]
+    pub fn validate(&self) -> Vec<ValidationError> {
+        self.full_graph()
+            .map(|graph| graph.validate())
+            .unwrap_or_default()
+    }
+
+    /// Same as `validate`, but for the host graph.
+    #[allow(unused)]
+    pub fn host_validate(&self) -> Vec<ValidationError> {
+        self.host_graph()
+            .map(|graph| graph.validate())
+            .unwrap_or_default()
+    }
+
+    /// Number of edges with each distinct label in the full graph.
+    /// Edges without a label are counted under the empty string.
+    #[allow(unused)]
+    pub fn label_histogram(&self) -> HashMap<String, usize> {
+        let mut histogram = HashMap::new();
+
+        if let Some(graph) = self.full_graph() {
+            for edge in graph.edges() {
+                *histogram.entry(edge.label).or_default() += 1;
+            }
+        }
+
+        histogram
+    }
+
+    fn export_edges<P: AsRef<Path>>(graph: &GraphStore<S>, path: P) -> std::io::Result<()> {
+        let mut writer = BufWriter::new(fs::File::create(path)?);
+
+        for edge in graph.edges() {
+            let from = graph.id2node(&edge.from).expect("unknown node");
+            let to = graph.id2node(&edge.to).expect("unknown node");
+            let label = edge.label.replace(['\t', '\n', '\r'], " ");
+
+            writeln!(writer, "{}\t{}\t{}", from.name, to.name, label)?;
+        }
+
+        writer.flush()
+    }
+
+    /// Writes the full graph to `path` with one `from\tto\tlabel` line per edge.
+    /// Tabs and newlines in labels are replaced by spaces.
+    #[allow(unused)]
+    pub fn export_edge_list<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        match self.full_graph() {
+            Some(graph) => Webgraph::export_edges(graph, path),
+            None => Ok(()),
+        }
+    }
+
+    #[allow(unused)]
+    pub fn host_export_edge_list<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        match self.host_graph() {
+            Some(graph) => Webgraph::export_edges(graph, path),
+            None => Ok(()),
+        }
+    }
+
+    fn raw_nodes(graph: &GraphStore<S>) -> impl Iterator<Item = Node> + '_ {
+        graph.flush();
+
+        graph
+            .nodes()
+            .filter_map(move |id| Webgraph::resolve_node(graph, &id))
+    }
+
+    /// All nodes in the full graph. The nodes are returned in the order of the
+    /// underlying store, which has no meaning and shouldn't be relied upon.
+    #[allow(unused)]
+    pub fn nodes(&self) -> impl Iterator<Item = Node> + '_ {
+        self.full_graph()
+            .into_iter()
+            .flat_map(Webgraph::<S>::raw_nodes)
+    }
+
+    /// All nodes in the host graph, in no particular order.
+    #[allow(unused)]
+    pub fn host_nodes(&self) -> impl Iterator<Item = Node> + '_ {
+        self.host_graph()
+            .into_iter()
+            .flat_map(Webgraph::<S>::raw_nodes)
+    }
+
+    /// GraphViz representation of the full graph with at most `max_edges` edges.
+    /// Edges are labeled by their label when it is non-empty. Intended for
+    /// visualising small graphs, e.g. the result of `subgraph`.
+    #[allow(unused)]
+    pub fn to_dot(&self, max_edges: usize) -> String {
+        let escape = |s: &str| s.replace('\\', "\\\\").replace('"', "\\\"");
+        let mut dot = String::from("digraph {\n");
+
+        if let Some(graph) = self.full_graph() {
+            let edges = graph.edges().filter_map(|edge| {
+                let from = Webgraph::resolve_node(graph, &edge.from)?;
+                let to = Webgraph::resolve_node(graph, &edge.to)?;
+
+                Some((from, to, edge.label))
+            });
+
+            for (num_edges, (from, to, label)) in edges.enumerate() {
+                if num_edges == max_edges {
+                    warn!("dot output truncated to {} edges", max_edges);
+                    break;
+                }
+
+                dot.push_str(&format!(
+                    "    \"{}\" -> \"{}\"",
+                    escape(&from.name),
+                    escape(&to.name)
+                ));
+
+                if !label.is_empty() {
+                    dot.push_str(&format!(" [label=\"{}\"]", escape(&label)));
+                }
+
+                dot.push_str(";\n");
+            }
+        }
+
+        dot.push_str("}\n");
+
+        dot
+    }
+
+    /// The `n` hosts with the highest harmonic centrality, sorted by descending centrality.
+    #[allow(unused)]
+    pub fn top_host_harmonic_centrality(&self, n: usize) -> Vec<(Node, f64)> {
+        self.host_graph()
+            .map(|host_graph| {
+                Webgraph::calculate_top_centrality(
+                    host_graph,
+                    |node| self.raw_host_reversed_distances(node),
+                    n,
+                    self.default_progress(),
+                )
+            })
+            .unwrap_or_default()
+    }
+
+    /// Flushes the stores that have been opened.
+    pub fn flush(&self) {
+        for graph in self.full_graph.iter().chain(self.host_graph.iter()) {
+            if let Some(graph) = graph.get_if_open() {
+                graph.flush();
+            }
+        }
+    }
+
+    /// Flushes the graph and re-enables the compactions and write-ahead log that were
+    /// disabled by `RocksDbTuning::bulk_load`. The stores are compacted afterwards,
+    /// so this can take a while for large graphs.
+    #[allow(unused)]
+    pub fn finish_bulk_load(&self) {
+        for graph in self.full_graph.iter().chain(self.host_graph.iter()) {
+            if let Some(graph) = graph.get_if_open() {
+                graph.finish_bulk_load();
+            }
+        }
+    }
+
+    fn raw_in_degree(graph: &GraphStore<S>, node: &Node) -> usize {
+        graph
+            .node2id(node)
+            .map(|node_id| {
+                graph
+                    .ingoing_edges(node_id)
+                    .into_iter()
+                    .map(|edge| edge.from)
+                    .collect::<HashSet<_>>()
+                    .len()
+            })
+            .unwrap_or(0)
+    }
+
+    fn raw_out_degree(graph: &GraphStore<S>, node: &Node) -> usize {
+        graph
+            .node2id(node)
+            .map(|node_id| {
+                graph
+                    .outgoing_edges(node_id)
+                    .into_iter()
+                    .map(|edge| edge.to)
+                    .collect::<HashSet<_>>()
+                    .len()
+            })
+            .unwrap_or(0)
+    }
+
+    /// Number of distinct nodes linking to `node` in the full graph. Parallel edges
+    /// with different labels are only counted once.
+    #[allow(unused)]
+    pub fn in_degree(&self, node: Node) -> usize {
+        self.full_graph()
+            .map(|graph| Webgraph::raw_in_degree(graph, &node))
+            .unwrap_or(0)
+    }
+
+    /// The page links behind the edge between the hosts of `from` and `to`. Sample urls
+    /// are only kept if the graph was opened with `WebgraphBuilder::with_host_edge_evidence`.
+    #[allow(unused)]
+    pub fn host_edge_evidence(&self, from: Node, to: Node) -> EdgeEvidence {
+        self.host_graph()
+            .and_then(|host_graph| {
+                host_graph.evidence(
+                    &from.into_host(&self.collapse_subdomains),
+                    &to.into_host(&self.collapse_subdomains),
+                )
+            })
+            .unwrap_or_default()
+    }
+
+    /// Number of distinct nodes `node` links to in the full graph. Parallel edges
+    /// with different labels are only counted once.
+    #[allow(unused)]
+    pub fn out_degree(&self, node: Node) -> usize {
+        self.full_graph()
+            .map(|graph| Webgraph::raw_out_degree(graph, &node))
+            .unwrap_or(0)
+    }
+
+    #[allow(unused)]
+    pub fn host_in_degree(&self, node: Node) -> usize {
+        self.host_graph()
+            .map(|graph| Webgraph::raw_in_degree(graph, &node))
+            .unwrap_or(0)
+    }
+
+    #[allow(unused)]
+    pub fn host_out_degree(&self, node: Node) -> usize {
+        self.host_graph()
+            .map(|graph| Webgraph::raw_out_degree(graph, &node))
+            .unwrap_or(0)
+    }
+
+    #[allow(unused)]
+    pub fn outgoing_edges(&self, node: Node) -> Vec<FullEdge> {
+        if let Some(graph) = self.full_graph() {
+            if let Some(node_id) = graph.node2id(&node) {
+                graph
+                    .outgoing_edges(node_id)
+                    .into_iter()
+                    .map(|edge| FullEdge {
+                        from: graph.id2node(&edge.from).unwrap(),
+                        to: graph.id2node(&edge.to).unwrap(),
+                        label: edge.label,
+                    })
+                    .collect()
+            } else {
+                Vec::new()
+            }
+        } else {
+            Vec::new()
+        }
+    }
+
+    pub fn ingoing_edges(&self, node: Node) -> Vec<FullEdge> {
+        if let Some(graph) = self.full_graph() {
+            if let Some(node_id) = graph.node2id(&node) {
+                graph
+                    .ingoing_edges(node_id)
+                    .into_iter()
+                    .map(|edge| FullEdge {
+                        from: graph.id2node(&edge.from).unwrap(),
+                        to: graph.id2node(&edge.to).unwrap(),
+                        label: edge.label,
+                    })
+                    .collect()
+            } else {
+                Vec::new()
+            }
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// Streams the edges of `graph`. The endpoints are resolved as the edges are
+    /// visited, and edges with an endpoint that can't be resolved are skipped.
+    fn resolved_edges(graph: &GraphStore<S>) -> impl Iterator<Item = FullEdge> + '_ {
+        graph.edges().filter_map(move |edge| {
+            Some(FullEdge {
+                from: Webgraph::resolve_node(graph, &edge.from)?,
+                to: Webgraph::resolve_node(graph, &edge.to)?,
+                label: edge.label,
+            })
+        })
+    }
+
+    /// All edges in the full graph.
+    #[allow(unused)]
+    pub fn edges(&self) -> impl Iterator<Item = FullEdge> + '_ {
+        self.full_graph()
+            .into_iter()
+            .flat_map(Webgraph::resolved_edges)
+    }
+
+    /// All edges in the host graph.
+    #[allow(unused)]
+    pub fn host_edges(&self) -> impl Iterator<Item = FullEdge> + '_ {
+        self.host_graph()
+            .into_iter()
+            .flat_map(Webgraph::resolved_edges)
+    }
+
+    /// A view of the graph where the direction of every edge is flipped.
+    #[allow(unused)]
+    pub fn reversed(&self) -> ReversedView<'_, S> {
+        ReversedView { graph: self }
+    }
+}
+
+/// The graph with all edges reversed, so the forward methods of the view follow
+/// the edges of the underlying graph backwards. Nothing is copied.
+pub struct ReversedView<'a, S: Store = RocksDbStore> {
+    graph: &'a Webgraph<S>,
+}
+
+#[allow(unused)]
+impl<'a, S: Store> ReversedView<'a, S> {
+    /// The graph with the original direction of the edges.
+    pub fn reversed(&self) -> &'a Webgraph<S> {
+        self.graph
+    }
+
+    pub fn distances(&self, source: Node) -> HashMap<Node, usize> {
+        self.graph.reversed_distances(source)
+    }
+
+    pub fn reversed_distances(&self, source: Node) -> HashMap<Node, usize> {
+        self.graph.distances(source)
+    }
+
+    pub fn host_distances(&self, source: Node) -> HashMap<Node, usize> {
+        self.graph.host_reversed_distances(source)
+    }
+
+    pub fn host_reversed_distances(&self, source: Node) -> HashMap<Node, usize> {
+        self.graph.host_distances(source)
+    }
+
+    pub fn neighborhood(&self, source: Node, max_hops: usize, ingoing: bool) -> Vec<(Node, usize)> {
+        self.graph.neighborhood(source, max_hops, !ingoing)
+    }
+
+    pub fn outgoing_edges(&self, node: Node) -> Vec<FullEdge> {
+        self.graph
+            .ingoing_edges(node)
+            .into_iter()
+            .map(FullEdge::reversed)
+            .collect()
+    }
+
+    pub fn ingoing_edges(&self, node: Node) -> Vec<FullEdge> {
+        self.graph
+            .outgoing_edges(node)
+            .into_iter()
+            .map(FullEdge::reversed)
+            .collect()
+    }
+}
+
+impl Webgraph {
+    /// Writes the entire graph to a single file, which can be opened again
+    /// with `load_from` regardless of where the file is moved to. The graph is closed
+    /// before its folder is read, so the stores are consistent on disk.
+    #[allow(unused)]
+    pub fn save_to<P: AsRef<Path>>(self, path: P) -> crate::Result<()> {
+        self.flush();
+
+        let graph_path = self.path.clone();
+        let has_full = self.full_graph.is_some();
+        let has_host = self.host_graph.is_some();
+        drop(self);
+
+        let root = directory::scan_folder(graph_path)?;
+        let frozen = FrozenWebgraph {
+            root: directory::relocate(root, ""),
+            has_full,
+            has_host,
+        };
+
+        let mut writer = BufWriter::new(fs::File::create(path)?);
+        bincode::serialize_into(&mut writer, &frozen)?;
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    /// Opens a graph written by `save_to`. The graph is unpacked into a folder
+    /// next to the file, named after the file with a `.graph` suffix.
+    #[allow(unused)]
+    pub fn load_from<P: AsRef<Path>>(path: P) -> crate::Result<Webgraph> {
+        let reader = BufReader::new(fs::File::open(path.as_ref())?);
+        let mut frozen: FrozenWebgraph = bincode::deserialize_from(reader)?;
+
+        check_frozen_paths(&frozen.root, Path::new(""))?;
+
+        let graph_path = format!("{}.graph", path.as_ref().to_str().unwrap());
+        frozen.root = directory::relocate(frozen.root, &graph_path);
+
+        Ok(Webgraph::unpack(frozen)?)
+    }
+
+    /// Writes the ids of the nodes in the full graph to `path`. The file starts with
+    /// `ID_MAPPING_VERSION` as a little-endian `u32`, followed by the bincode encoded
+    /// `(Node, NodeID)` pairs. Use `Webgraph::load_id_mapping` to read it.
+    #[allow(unused)]
+    pub fn save_id_mapping<P: AsRef<Path>>(&self, path: P) -> crate::Result<()> {
+        self.flush();
+
+        let entries: Vec<_> = self
+            .full_graph()
+            .map(|graph| graph.node_ids().collect())
+            .unwrap_or_default();
+
+        let mut writer = BufWriter::new(fs::File::create(path)?);
+        writer.write_all(&ID_MAPPING_VERSION.to_le_bytes())?;
+        bincode::serialize_into(&mut writer, &entries)?;
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    /// Reads a mapping written by `save_id_mapping`.
+    #[allow(unused)]
+    pub fn load_id_mapping<P: AsRef<Path>>(path: P) -> crate::Result<IdMapping> {
+        let mut reader = BufReader::new(fs::File::open(path)?);
+
+        let mut version = [0; 4];
+        reader.read_exact(&mut version)?;
+        let version = u32::from_le_bytes(version);
+
+        if version != ID_MAPPING_VERSION {
+            return Err(crate::Error::UnsupportedIdMappingVersion(version));
+        }
+
+        let entries: Vec<(Node, NodeID)> = bincode::deserialize_from(reader)?;
+
+        Ok(IdMapping {
+            id2node: entries
+                .iter()
+                .map(|(node, id)| (*id, node.clone()))
+                .collect(),
+            node2id: entries.into_iter().collect(),
+        })
+    }
+}
+
+/// The ids of the nodes in the full graph, as written by `Webgraph::save_id_mapping`.
+/// Can be used to translate between nodes and ids without opening the graph.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct IdMapping {
+    node2id: HashMap<Node, NodeID>,
+    id2node: HashMap<NodeID, Node>,
+}
+
+impl IdMapping {
+    #[allow(unused)]
+    pub fn node2id(&self, node: &Node) -> Option<NodeID> {
+        self.node2id.get(node).copied()
+    }
+
+    #[allow(unused)]
+    pub fn id2node(&self, id: &NodeID) -> Option<&Node> {
+        self.id2node.get(id)
+    }
+
+    #[allow(unused)]
+    pub fn len(&self) -> usize {
+        self.node2id.len()
+    }
+
+    #[allow(unused)]
+    pub fn is_empty(&self) -> bool {
+        self.node2id.is_empty()
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum FrozenError {
+    #[error("Cannot open webgraph from a file - must be directory")]
+    FileRoot,
+
+    #[error("Refusing to unpack webgraph entry outside of its root: {0}")]
+    UnsafePath(String),
+
+    #[error("Refusing to replace existing folder that is not a webgraph: {0}")]
+    ExistingFolder(String),
+
+    #[error("Failed to unpack the webgraph")]
+    Io(#[from] std::io::Error),
+}
+
+/// Checks that `entry` and all its children are inside `root` and don't contain `..` or `.`,
+/// so unpacking a frozen graph can't touch anything outside of its own folder.
+fn check_frozen_paths(entry: &DirEntry, root: &Path) -> Result<(), FrozenError> {
+    let (name, entries) = match entry {
+        DirEntry::Folder { name, entries } => (name, entries.as_slice()),
+        DirEntry::File { name, content: _ } => (name, &[][..]),
+    };
+
+    let path = Path::new(name);
+    if !path.starts_with(root)
+        || path
+            .components()
+            .any(|c| matches!(c, Component::ParentDir | Component::CurDir))
+    {
+        return Err(FrozenError::UnsafePath(name.clone()));
+    }
+
+    entries
+        .iter()
+        .try_for_each(|entry| check_frozen_paths(entry, root))
+}
+
+/// Whether `path` is a folder that only contains the stores a graph creates.
+fn is_webgraph_folder(path: &Path) -> std::io::Result<bool> {
+    if !path.is_dir() {
+        return Ok(false);
+    }
+
+    for entry in fs::read_dir(path)? {
+        let name = entry?.file_name();
+
+        if name != "full" && name != "host" {
+            return Ok(false);
+        }
+    }
+
+    Ok(true)
+}
+
+impl Webgraph {
+    /// Unpacks the frozen graph into the folder of its root and opens it.
+    /// The root must be a non-empty relative path, as the payload may come from another machine.
+    pub fn try_from_frozen(frozen: FrozenWebgraph) -> Result<Webgraph, FrozenError> {
+        let path = Webgraph::frozen_root(&frozen)?;
+
+        if path.is_empty() || Path::new(&path).is_absolute() {
+            return Err(FrozenError::UnsafePath(path));
+        }
+
+        check_frozen_paths(&frozen.root, Path::new(&path))?;
+
+        Webgraph::unpack(frozen)
+    }
+
+    fn frozen_root(frozen: &FrozenWebgraph) -> Result<String, FrozenError> {
+        match &frozen.root {
+            DirEntry::Folder { name, entries: _ } => Ok(name.clone()),
+            DirEntry::File {
+                name: _,
+                content: _,
+            } => Err(FrozenError::FileRoot),
+        }
+    }
+
+    /// Unpacks the frozen graph into the folder of its root. An existing folder is only
+    /// replaced if it contains nothing but the stores of a graph.
+    fn unpack(frozen: FrozenWebgraph) -> Result<Webgraph, FrozenError> {
+        let path = Webgraph::frozen_root(&frozen)?;
+
+        if Path::new(&path).exists() {
+            if !is_webgraph_folder(Path::new(&path))? {
+                return Err(FrozenError::ExistingFolder(path));
+            }
+
+            fs::remove_dir_all(&path)?;
+        }
+
+        directory::recreate_folder(&frozen.root)?;
+
+        let mut builder = WebgraphBuilder::new(path);
+
+        if frozen.has_full {
+            builder = builder.with_full_graph();
+        }
+
+        if frozen.has_host {
+            builder = builder.with_host_graph();
+        }
+
+        Ok(builder.open())
+    }
+}
+
+impl From<FrozenWebgraph> for Webgraph {
+    fn from(frozen: FrozenWebgraph) -> Self {
+        Webgraph::try_from_frozen(frozen).unwrap()
+    }
+}
+
+impl From<Webgraph> for FrozenWebgraph {
+    fn from(graph: Webgraph) -> Self {
+        graph.flush();
+        let path = graph.path.clone();
+        let has_full = graph.full_graph.is_some();
+        let has_host = graph.host_graph.is_some();
+        drop(graph);
+        let root = directory::scan_folder(path).unwrap();
+
+        Self {
+            root,
+            has_full,
+            has_host,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FrozenWebgraph {
+    pub root: DirEntry,
+    has_full: bool,
+    has_host: bool,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::kv::memory_store::MemoryStore;
+
+    /// Frozen graphs must have a relative root, while the test graphs live in the temp dir.
+    fn relative_temp_path() -> String {
+        crate::gen_temp_path()
+            .file_name()
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_string()
+    }
+
+    fn test_graph() -> Webgraph {
+        //     ┌────┐
+        //     │    │
+        // ┌───A◄─┐ │
+        // │      │ │
+        // ▼      │ │
+        // B─────►C◄┘
+        //        ▲
+        //        │
+        //        │
+        //        D
+
+        let mut graph = WebgraphBuilder::new_memory()
+            .with_full_graph()
+            .with_host_graph()
+            .open();
+
+        graph.insert(Node::from("A"), Node::from("B"), String::new());
+        graph.insert(Node::from("B"), Node::from("C"), String::new());
+        graph.insert(Node::from("A"), Node::from("C"), String::new());
+        graph.insert(Node::from("C"), Node::from("A"), String::new());
+        graph.insert(Node::from("D"), Node::from("C"), String::new());
+
+        graph.flush();
+
+        graph
+    }
+
+    #[test]
+    fn distance_calculation() {
+        let graph = test_graph();
+
+        let distances = graph.distances(Node::from("D"));
+
+        assert_eq!(distances.get(&Node::from("C")), Some(&1));
+        assert_eq!(distances.get(&Node::from("A")), Some(&2));
+        assert_eq!(distances.get(&Node::from("B")), Some(&3));
+    }
+
+    #[test]
+    fn undirected_distances() {
+        let graph = test_graph();
+
+        let distances = graph.undirected_distances(Node::from("D"));
+
+        assert_eq!(distances.get(&Node::from("C")), Some(&1));
+        assert_eq!(distances.get(&Node::from("A")), Some(&2));
+        // via C←B instead of D→C→A→B
+        assert_eq!(distances.get(&Node::from("B")), Some(&2));
+
+        // D only has an outgoing edge, so it can't be reached from B when following the edges
+        assert_eq!(graph.distances(Node::from("B")).get(&Node::from("D")), None);
+        assert_eq!(
+            graph
+                .undirected_distances(Node::from("B"))
+                .get(&Node::from("D")),
+            Some(&2)
+        );
+    }
+
+    #[test]
+    fn sorted_distances() {
+        let graph = test_graph();
+
+        assert_eq!(
+            graph.distances_sorted(Node::from("D")),
+            vec![
+                (Node::from("D"), 0),
+                (Node::from("C"), 1),
+                (Node::from("A"), 2),
+                (Node::from("B"), 3),
+            ]
+        );
+
+        assert_eq!(
+            graph.distances_sorted(Node::from("C")),
+            vec![
+                (Node::from("C"), 0),
+                (Node::from("A"), 1),
+                (Node::from("B"), 2),
+            ]
+        );
+        assert!(graph.distances_sorted(Node::from("E")).is_empty());
+    }
+
+    #[test]
+    fn weighted_distance_calculation() {
+        let mut graph = WebgraphBuilder::new_memory().with_full_graph().open();
+
+        graph.insert(Node::from("A"), Node::from("B"), String::new());
+        graph.insert(Node::from("B"), Node::from("C"), String::new());
+        graph.insert(Node::from("A"), Node::from("C"), "nofollow".to_string());
+
+        graph.flush();
+
+        let distances = graph.distances(Node::from("A"));
+        assert_eq!(distances.get(&Node::from("C")), Some(&1));
+
+        let distances = graph.weighted_distances(Node::from("A"), |edge| {
+            if edge.label == "nofollow" {
+                5
+            } else {
+                1
+            }
+        });
+        assert_eq!(distances.get(&Node::from("C")), Some(&2));
+
+        let distances = graph.weighted_distances(Node::from("A"), |edge| {
+            if edge.label.is_empty() {
+                1
+            } else {
+                usize::MAX
+            }
+        });
+        assert_eq!(distances.get(&Node::from("C")), Some(&2));
+        assert_eq!(distances.get(&Node::from("B")), Some(&1));
+    }
+
+    #[test]
+    fn shortest_path() {
+        let graph = test_graph();
+
+        assert_eq!(
+            graph.shortest_path(Node::from("D"), Node::from("A")),
+            Some(vec![Node::from("D"), Node::from("C"), Node::from("A")])
+        );
+
+        assert_eq!(
+            graph.shortest_path(Node::from("D"), Node::from("B")),
+            Some(vec![
+                Node::from("D"),
+                Node::from("C"),
+                Node::from("A"),
+                Node::from("B")
+            ])
+        );
+
+        assert_eq!(
+            graph.shortest_path(Node::from("A"), Node::from("A")),
+            Some(vec![Node::from("A")])
+        );
+
+        assert_eq!(graph.shortest_path(Node::from("B"), Node::from("D")), None);
+        assert_eq!(graph.shortest_path(Node::from("D"), Node::from("E")), None);
+        assert_eq!(graph.shortest_path(Node::from("E"), Node::from("D")), None);
+    }
+
+    #[test]
+    fn shortest_path_edges() {
+        let mut graph = WebgraphBuilder::new_memory().with_full_graph().open();
+
+        graph.insert(Node::from("A"), Node::from("B"), "a to b".to_string());
+        graph.insert(Node::from("B"), Node::from("C"), "b to c".to_string());
+        graph.insert(Node::from("A"), Node::from("C"), "a to c".to_string());
+        graph.insert(Node::from("C"), Node::from("A"), "c to a".to_string());
+        graph.insert(Node::from("D"), Node::from("C"), "d to c".to_string());
+        graph.flush();
+
+        assert_eq!(
+            graph.shortest_path_edges(Node::from("D"), Node::from("A")),
+            Some(vec![
+                FullEdge {
+                    from: Node::from("D"),
+                    to: Node::from("C"),
+                    label: "d to c".to_string(),
+                },
+                FullEdge {
+                    from: Node::from("C"),
+                    to: Node::from("A"),
+                    label: "c to a".to_string(),
+                },
+            ])
+        );
+
+        assert_eq!(
+            graph.shortest_path_edges(Node::from("A"), Node::from("A")),
+            Some(Vec::new())
+        );
+        assert_eq!(
+            graph.shortest_path_edges(Node::from("B"), Node::from("D")),
+            None
+        );
+    }
+
+    #[test]
+    fn neighborhood() {
+        let graph = test_graph();
+
+        assert_eq!(
+            graph.neighborhood(Node::from("D"), 1, false),
+            vec![(Node::from("C"), 1)]
+        );
+
+        assert_eq!(
+            graph.neighborhood(Node::from("D"), 2, false),
+            vec![(Node::from("C"), 1), (Node::from("A"), 2)]
+        );
+
+        assert!(graph.neighborhood(Node::from("D"), 1, true).is_empty());
+
+        assert_eq!(
+            graph.neighborhood(Node::from("C"), 1, true),
+            vec![
+                (Node::from("A"), 1),
+                (Node::from("B"), 1),
+                (Node::from("D"), 1)
+            ]
+        );
+
+        assert!(graph.neighborhood(Node::from("E"), 3, false).is_empty());
+    }
+
+    #[test]
+    fn small_id_cache() {
+        let graph = test_graph();
+
+        let mut cached_graph = WebgraphBuilder::new_memory()
+            .with_full_graph()
+            .with_host_graph()
+            .with_id_cache(1)
+            .open();
+
+        cached_graph.insert(Node::from("A"), Node::from("B"), String::new());
+        cached_graph.insert(Node::from("B"), Node::from("C"), String::new());
+        cached_graph.insert(Node::from("A"), Node::from("C"), String::new());
+        cached_graph.insert(Node::from("C"), Node::from("A"), String::new());
+        cached_graph.insert(Node::from("D"), Node::from("C"), String::new());
+        cached_graph.flush();
+
+        for node in ["A", "B", "C", "D"] {
+            assert_eq!(
+                graph.distances(Node::from(node)),
+                cached_graph.distances(Node::from(node))
+            );
+            assert_eq!(
+                graph.host_reversed_distances(Node::from(node)),
+                cached_graph.host_reversed_distances(Node::from(node))
+            );
+        }
+    }
+
+    #[test]
+    fn nonexisting_node() {
+        let graph = test_graph();
+        assert_eq!(graph.distances(Node::from("E")).len(), 0);
+        assert_eq!(graph.reversed_distances(Node::from("E")).len(), 0);
+    }
+
+    #[test]
+    fn reversed_distance_calculation() {
+        let graph = test_graph();
+
+        let distances = graph.reversed_distances(Node::from("D"));
+
+        assert_eq!(distances.get(&Node::from("C")), None);
+        assert_eq!(distances.get(&Node::from("A")), None);
+        assert_eq!(distances.get(&Node::from("B")), None);
+
+        let distances = graph.reversed_distances(Node::from("A"));
+
+        assert_eq!(distances.get(&Node::from("C")), Some(&1));
+        assert_eq!(distances.get(&Node::from("D")), Some(&2));
+        assert_eq!(distances.get(&Node::from("B")), Some(&2));
+    }
+
+    #[test]
+    fn harmonic_centrality() {
+        let graph = test_graph();
+
+        let centrality = graph.harmonic_centrality();
+
+        assert_eq!(centrality.get(&Node::from("C")).unwrap(), &1.0);
+        assert_eq!(centrality.get(&Node::from("D")), None);
+        assert_eq!(
+            (*centrality.get(&Node::from("A")).unwrap() * 100.0).round() / 100.0,
+            0.67
+        );
+        assert_eq!(
+            (*centrality.get(&Node::from("B")).unwrap() * 100.0).round() / 100.0,
+            0.61
+        );
+    }
+
+    #[test]
+    fn centrality_with_decay() {
+        let graph = test_graph();
+
+        let harmonic = graph.centrality_with_decay(|dist| 1.0 / dist as f64);
+        let expected = graph.harmonic_centrality();
+        for (node, centrality) in &expected {
+            assert!((harmonic[node] - centrality).abs() < 1e-9);
+        }
+
+        let decayed = graph.centrality_with_decay(|dist| (-(dist as f64)).exp());
+        let centrality = |node: &str| decayed[&Node::from(node)];
+
+        // A is reached from C at distance 1 and from B and D at distance 2,
+        // B from A at distance 1, from C at distance 2 and from D at distance 3
+        let e = |dist: f64| (-dist).exp();
+        assert!((centrality("A") - (e(1.0) + 2.0 * e(2.0)) / 3.0).abs() < 1e-9);
+        assert!((centrality("B") - (e(1.0) + e(2.0) + e(3.0)) / 3.0).abs() < 1e-9);
+        assert!((centrality("C") - e(1.0)).abs() < 1e-9);
+        assert!(!decayed.contains_key(&Node::from("D")));
+
+        // the gap between A and B shrinks, as distant nodes contribute less than
+        // with the harmonic decay
+        assert!(centrality("C") > centrality("A"));
+        assert!(
+            centrality("A") - centrality("B")
+                < expected[&Node::from("A")] - expected[&Node::from("B")]
+        );
+    }
+
+    #[test]
+    fn raw_harmonic_centrality() {
+        let graph = test_graph();
+
+        let raw = graph.harmonic_centrality_with_normalization(Normalization::Raw);
+        let normalized = graph.harmonic_centrality_with_normalization(Normalization::Normalized);
+
+        // A, B and D all link directly to C
+        assert_eq!(raw.get(&Node::from("C")), Some(&3.0));
+
+        for (node, centrality) in &normalized {
+            assert!((raw[node] / 3.0 - centrality).abs() < 1e-9);
+        }
+
+        let raw = graph.host_harmonic_centrality_with_normalization(Normalization::Raw);
+        assert_eq!(raw.get(&Node::from("C")), Some(&3.0));
+    }
+
+    #[test]
+    fn outgoing_edges() {
+        let graph = test_graph();
+
+        let mut edges = graph.outgoing_edges(Node::from("A"));
+        edges.sort_by(|a, b| a.to.cmp(&b.to));
+
+        assert_eq!(
+            edges,
+            vec![
+                FullEdge {
+                    from: Node::from("A"),
+                    to: Node::from("B"),
+                    label: String::new(),
+                },
+                FullEdge {
+                    from: Node::from("A"),
+                    to: Node::from("C"),
+                    label: String::new(),
+                },
+            ]
+        );
+
+        assert!(graph.outgoing_edges(Node::from("E")).is_empty());
+
+        let host_only = WebgraphBuilder::new_memory().with_host_graph().open();
+        assert!(host_only.outgoing_edges(Node::from("A")).is_empty());
+    }
+
+    #[test]
+    fn degrees() {
+        let mut graph = test_graph();
+
+        assert_eq!(graph.in_degree(Node::from("C")), 3);
+        assert_eq!(graph.out_degree(Node::from("C")), 1);
+        assert_eq!(graph.in_degree(Node::from("D")), 0);
+        assert_eq!(graph.out_degree(Node::from("A")), 2);
+        assert_eq!(graph.host_in_degree(Node::from("C")), 3);
+        assert_eq!(graph.host_out_degree(Node::from("A")), 2);
+
+        assert_eq!(graph.in_degree(Node::from("E")), 0);
+        assert_eq!(graph.out_degree(Node::from("E")), 0);
+
+        graph.insert(Node::from("A"), Node::from("B"), "other label".to_string());
+        graph.flush();
+
+        assert_eq!(graph.out_degree(Node::from("A")), 2);
+        assert_eq!(graph.in_degree(Node::from("B")), 1);
+    }
+
+    #[test]
+    fn duplicate_edges_ignored() {
+        let mut graph = WebgraphBuilder::new_memory()
+            .with_full_graph()
+            .with_host_graph()
+            .open();
+
+        graph.insert(Node::from("A"), Node::from("B"), String::new());
+        graph.insert(Node::from("A"), Node::from("B"), String::new());
+        graph.flush();
+
+        assert_eq!(graph.out_degree(Node::from("A")), 1);
+        assert_eq!(graph.outgoing_edges(Node::from("A")).len(), 1);
+        assert_eq!(graph.ingoing_edges(Node::from("B")).len(), 1);
+        assert_eq!(graph.stats().num_edges, 1);
+        assert_eq!(graph.stats().num_host_edges, 1);
+
+        graph.insert(Node::from("A"), Node::from("B"), "label".to_string());
+        graph.flush();
+
+        assert_eq!(graph.outgoing_edges(Node::from("A")).len(), 2);
+    }
+
+    #[test]
+    fn pagerank() {
+        let graph = test_graph();
+
+        for pagerank in [
+            graph.full_pagerank(0.85, 100),
+            graph.host_pagerank(0.85, 100),
+        ] {
+            assert_eq!(pagerank.len(), 4);
+
+            let (top, _) = pagerank
+                .iter()
+                .max_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap())
+                .unwrap();
+            assert_eq!(top, &Node::from("C"));
+
+            let sum: f64 = pagerank.values().sum();
+            assert!((sum - 1.0).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn parallel_centrality_matches_sequential() {
+        let graph = test_graph();
+        let centrality = graph.harmonic_centrality();
+
+        let nodes = ["A", "B", "C", "D"];
+        let norm_factor = (nodes.len() - 1) as f64;
+
+        for name in nodes {
+            let node = Node::from(name);
+            let expected = graph
+                .reversed_distances(node.clone())
+                .into_iter()
+                .filter(|(other, _)| *other != node)
+                .map(|(_, dist)| 1.0 / dist as f64)
+                .sum::<f64>()
+                / norm_factor;
+
+            assert!((centrality.get(&node).copied().unwrap_or(0.0) - expected).abs() < 1e-9);
+        }
+    }
+
+    fn host_test_graph() -> Webgraph {
+        let mut graph = WebgraphBuilder::new_memory()
+            .with_full_graph()
+            .with_host_graph()
+            .open();
+
+        graph.insert(Node::from("A.com/1"), Node::from("A.com/2"), String::new());
+        graph.insert(Node::from("A.com/1"), Node::from("A.com/3"), String::new());
+        graph.insert(Node::from("A.com/1"), Node::from("A.com/4"), String::new());
+        graph.insert(Node::from("A.com/2"), Node::from("A.com/1"), String::new());
+        graph.insert(Node::from("A.com/2"), Node::from("A.com/3"), String::new());
+        graph.insert(Node::from("A.com/2"), Node::from("A.com/4"), String::new());
+        graph.insert(Node::from("A.com/3"), Node::from("A.com/1"), String::new());
+        graph.insert(Node::from("A.com/3"), Node::from("A.com/2"), String::new());
+        graph.insert(Node::from("A.com/3"), Node::from("A.com/4"), String::new());
+        graph.insert(Node::from("A.com/4"), Node::from("A.com/1"), String::new());
+        graph.insert(Node::from("A.com/4"), Node::from("A.com/2"), String::new());
+        graph.insert(Node::from("A.com/4"), Node::from("A.com/3"), String::new());
+        graph.insert(Node::from("C.com"), Node::from("B.com"), String::new());
+        graph.insert(Node::from("D.com"), Node::from("B.com"), String::new());
+
+        graph.flush();
+
+        graph
+    }
+
+    #[test]
+    fn host_closeness_centrality() {
+        let mut graph = test_graph();
+
+        let closeness = graph.host_closeness_centrality();
+        assert_eq!(closeness.get(&Node::from("C")), Some(&1.0));
+        assert_eq!(closeness.get(&Node::from("A")), Some(&0.6));
+        assert_eq!(closeness.get(&Node::from("B")), Some(&0.5));
+        assert_eq!(closeness.get(&Node::from("D")), Some(&0.0));
+
+        // F is only reachable from E, but at distance 1. Closeness disregards the
+        // number of nodes that can reach F, so it ranks F above A, whereas harmonic
+        // centrality ranks A above F.
+        graph.insert(Node::from("E"), Node::from("F"), String::new());
+        graph.flush();
+
+        let closeness = graph.host_closeness_centrality();
+        let harmonic = graph.host_harmonic_centrality();
+
+        assert!(closeness[&Node::from("F")] > closeness[&Node::from("A")]);
+        assert!(harmonic[&Node::from("F")] < harmonic[&Node::from("A")]);
+    }
+
+    #[test]
+    fn centrality_progress_callback() {
+        let graph = test_graph().with_progress(false);
+
+        let calls = Mutex::new(Vec::new());
+        let centrality = graph.harmonic_centrality_with_progress(|pos, total| {
+            calls.lock().unwrap().push((pos, total));
+        });
+
+        let mut nodes: Vec<_> = centrality.into_keys().collect();
+        nodes.sort();
+        assert_eq!(
+            nodes,
+            vec![Node::from("A"), Node::from("B"), Node::from("C")]
+        );
+
+        let mut calls = calls.into_inner().unwrap();
+        calls.sort_unstable();
+        assert_eq!(calls, vec![(1, 4), (2, 4), (3, 4), (4, 4)]);
+    }
+
+    #[test]
+    fn host_harmonic_centrality_cutoff() {
+        let graph = test_graph();
+
+        let full = graph.host_harmonic_centrality();
+
+        for max_dist in 1..4 {
+            let cutoff = graph.host_harmonic_centrality_cutoff(max_dist);
+
+            for (node, centrality) in &full {
+                let approx = cutoff.get(node).copied().unwrap_or(0.0);
+                assert!(approx <= *centrality + 1e-9);
+                assert!(*centrality - approx <= 1.0 / (max_dist + 1) as f64);
+            }
+        }
+
+        let cutoff = graph.host_harmonic_centrality_cutoff(1);
+        assert_eq!(cutoff.get(&Node::from("C")), Some(&1.0));
+        assert!(cutoff[&Node::from("A")] < full[&Node::from("A")]);
+
+        let cutoff = graph.host_harmonic_centrality_cutoff(3);
+        assert_eq!(cutoff.len(), full.len());
+    }
+
+    #[test]
+    fn host_harmonic_centrality_to_file() {
+        let graph = test_graph();
+        let path = crate::gen_temp_path();
+        graph.host_harmonic_centrality_to_file(&path).unwrap();
+
+        let contents = fs::read_to_string(&path).unwrap();
+        let written: HashMap<_, _> = contents
+            .lines()
+            .map(|line| {
+                let (node, centrality) = line.split_once('\t').unwrap();
+                (Node::from(node), centrality.parse::<f64>().unwrap())
+            })
+            .collect();
+
+        let expected = graph.host_harmonic_centrality();
+
+        assert_eq!(contents.lines().count(), expected.len());
+        assert_eq!(written.len(), expected.len());
+        for (node, centrality) in expected {
+            assert!((written[&node] - centrality).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn host_harmonic_centrality() {
+        let graph = host_test_graph();
+
+        let centrality = graph.harmonic_centrality();
+        assert!(
+            centrality.get(&Node::from("A.com/1")).unwrap()
+                > centrality.get(&Node::from("B.com")).unwrap()
+        );
+
+        let host_centrality = graph.host_harmonic_centrality();
+        assert!(
+            host_centrality.get(&Node::from("B.com")).unwrap()
+                > host_centrality.get(&Node::from("A.com")).unwrap_or(&0.0)
+        );
+    }
+
+    #[test]
+    fn host_weighted_harmonic_centrality() {
+        let mut graph = WebgraphBuilder::new_memory().with_host_graph().open();
+
+        // x.com gets three links from pages on a.com, y.com a single link from b.com
+        for (page, label) in [("a.com/1", "x"), ("a.com/2", "x.com"), ("a.com/3", "home")] {
+            graph.insert(Node::from(page), Node::from("x.com"), label.to_string());
+        }
+        graph.insert(Node::from("b.com"), Node::from("y.com"), "y".to_string());
+        graph.insert(Node::from("x.com"), Node::from("c.com"), String::new());
+        graph.flush();
+
+        let unweighted = graph.host_harmonic_centrality();
+        assert_eq!(
+            unweighted[&Node::from("x.com")],
+            unweighted[&Node::from("y.com")]
+        );
+
+        let weighted = graph.host_weighted_harmonic_centrality();
+        assert!(weighted[&Node::from("x.com")] > weighted[&Node::from("y.com")]);
+
+        // a.com reaches c.com through its three edges to x.com
+        let norm_factor = 4.0;
+        assert!((weighted[&Node::from("c.com")] - (1.0 + 3.0 / 2.0) / norm_factor).abs() < 1e-9);
+        assert_eq!(weighted[&Node::from("y.com")], 1.0 / norm_factor);
+    }
+
+    #[test]
+    fn host_weighted_harmonic_centrality_same_label() {
+        let mut graph = WebgraphBuilder::new_memory()
+            .with_full_graph()
+            .with_host_graph()
+            .open();
+
+        // the host graph stores a single a.com -> x.com edge, but three pages link x.com
+        for page in ["https://a.com/1", "https://a.com/2", "https://a.com/3"] {
+            graph.insert(
+                Node::from(page),
+                Node::from("https://x.com"),
+                "x".to_string(),
+            );
+        }
+        graph.insert(
+            Node::from("https://a.com/1"),
+            Node::from("https://x.com"),
+            "x".to_string(),
+        );
+        graph.insert(
+            Node::from("https://b.com/1"),
+            Node::from("https://y.com"),
+            "x".to_string(),
+        );
+        graph.flush();
+
+        assert_eq!(graph.host_in_degree(Node::from("x.com")), 1);
+
+        let norm_factor = 3.0;
+        let weighted = graph.host_weighted_harmonic_centrality();
+        assert_eq!(weighted[&Node::from("x.com")], 3.0 / norm_factor);
+        assert_eq!(weighted[&Node::from("y.com")], 1.0 / norm_factor);
+    }
+
+    #[test]
+    fn top_host_harmonic_centrality() {
+        let graph = host_test_graph();
+
+        let top = graph.top_host_harmonic_centrality(1);
+        assert_eq!(top.len(), 1);
+        assert_eq!(top[0].0, Node::from("B.com"));
+
+        let centrality = graph.host_harmonic_centrality();
+        let mut expected: Vec<_> = centrality.into_iter().collect();
+        expected.sort_by(|(a_node, a), (b_node, b)| {
+            b.partial_cmp(a).unwrap().then_with(|| a_node.cmp(b_node))
+        });
+
+        assert_eq!(graph.top_host_harmonic_centrality(100), expected);
+        assert!(graph.top_host_harmonic_centrality(0).is_empty());
+    }
+
+    #[test]
+    fn www_subdomain_ignored() {
+        let mut graph = WebgraphBuilder::new_memory()
+            .with_full_graph()
+            .with_host_graph()
+            .open();
+
+        graph.insert(Node::from("B.com"), Node::from("A.com"), String::new());
+        graph.insert(Node::from("B.com"), Node::from("www.A.com"), String::new());
+
+        graph.flush();
+
+        let centrality = graph.host_harmonic_centrality();
+        assert_eq!(centrality.get(&Node::from("A.com")), Some(&1.0));
+        assert_eq!(centrality.get(&Node::from("www.A.com")), None);
+    }
+
+    #[test]
+    fn configured_subdomains_collapsed() {
+        let mut graph = WebgraphBuilder::new_memory()
+            .with_host_graph()
+            .collapse_subdomains(vec!["www".to_string(), "m".to_string(), "amp".to_string()])
+            .open();
+
+        graph.insert(Node::from("B.com"), Node::from("m.A.com"), String::new());
+        graph.insert(Node::from("C.com"), Node::from("amp.A.com"), String::new());
+        graph.insert(Node::from("D.com"), Node::from("blog.A.com"), String::new());
+
+        graph.flush();
+
+        assert_eq!(graph.host_in_degree(Node::from("A.com")), 2);
+        assert_eq!(graph.host_in_degree(Node::from("m.A.com")), 0);
+        assert_eq!(graph.host_in_degree(Node::from("blog.A.com")), 1);
+
+        let mut default_graph = WebgraphBuilder::new_memory().with_host_graph().open();
+
+        default_graph.insert(Node::from("B.com"), Node::from("m.A.com"), String::new());
+        default_graph.flush();
+
+        assert_eq!(default_graph.host_in_degree(Node::from("A.com")), 0);
+        assert_eq!(default_graph.host_in_degree(Node::from("m.A.com")), 1);
+    }
+
+    #[test]
+    fn nodes() {
+        let graph = test_graph();
+
+        let nodes: HashSet<_> = graph.nodes().collect();
+        let expected: HashSet<_> = ["A", "B", "C", "D"].into_iter().map(Node::from).collect();
+        assert_eq!(nodes, expected);
+
+        let host_nodes: HashSet<_> = graph.host_nodes().collect();
+        assert_eq!(host_nodes, expected);
+
+        let host_only = WebgraphBuilder::new_memory().with_host_graph().open();
+        assert_eq!(host_only.nodes().count(), 0);
+    }
+
+    #[test]
+    fn merge() {
+        let mut graph1 = WebgraphBuilder::new_memory()
+            .with_full_graph()
+            .with_host_graph()
+            .open();
+
+        graph1.insert(Node::from("A"), Node::from("B"), String::new());
+
+        let mut graph2 = WebgraphBuilder::new_memory()
+            .with_full_graph()
+            .with_host_graph()
+            .open();
+        graph2.insert(Node::from("B"), Node::from("C"), String::new());
+
+        graph1.merge(graph2);
+
+        assert_eq!(
+            graph1.distances(Node::from("A")).get(&Node::from("C")),
+            Some(&2)
+        )
+    }
+
+    #[test]
+    fn merge_shared_node() {
+        let mut graph1 = WebgraphBuilder::new_memory()
+            .with_full_graph()
+            .with_host_graph()
+            .open();
+
+        graph1.insert(Node::from("A"), Node::from("B"), String::new());
+        graph1.insert(Node::from("B"), Node::from("D"), String::new());
+
+        let mut graph2 = WebgraphBuilder::new_memory()
+            .with_full_graph()
+            .with_host_graph()
+            .open();
+
+        graph2.insert(Node::from("C"), Node::from("B"), String::new());
+        graph2.insert(Node::from("B"), Node::from("E"), String::new());
+        graph2.insert(Node::from("A"), Node::from("B"), String::new());
+
+        graph1.merge(graph2);
+
+        assert_eq!(graph1.in_degree(Node::from("B")), 2);
+        assert_eq!(graph1.out_degree(Node::from("B")), 2);
+        assert_eq!(graph1.host_in_degree(Node::from("B")), 2);
+        assert_eq!(graph1.host_out_degree(Node::from("B")), 2);
+
+        let stats = graph1.stats();
+        assert_eq!(stats.num_nodes, 5);
+        assert_eq!(stats.num_edges, 4);
+        assert_eq!(stats.num_host_nodes, 5);
+    }
+
+    #[test]
+    fn remove_edge() {
+        let mut graph = test_graph();
+
+        graph.remove_edge(Node::from("C"), Node::from("A"));
+        graph.flush();
+
+        let distances = graph.distances(Node::from("D"));
+
+        assert_eq!(distances.get(&Node::from("C")), Some(&1));
+        assert_eq!(distances.get(&Node::from("A")), None);
+        assert_eq!(distances.get(&Node::from("B")), None);
+
+        let distances = graph.host_distances(Node::from("D"));
+        assert_eq!(distances.get(&Node::from("A")), None);
+    }
+
+    #[test]
+    fn remove_node() {
+        let mut graph = test_graph();
+
+        graph.remove_node(Node::from("C"));
+        graph.flush();
+
+        let distances = graph.distances(Node::from("D"));
+        assert_eq!(distances.get(&Node::from("C")), None);
+        assert_eq!(distances.get(&Node::from("A")), None);
+
+        let distances = graph.distances(Node::from("A"));
+        assert_eq!(distances.get(&Node::from("B")), Some(&1));
+        assert_eq!(distances.get(&Node::from("C")), None);
+
+        assert!(graph.ingoing_edges(Node::from("A")).is_empty());
+
+        let centrality = graph.harmonic_centrality();
+        assert_eq!(centrality.get(&Node::from("C")), None);
+        assert!(centrality.contains_key(&Node::from("B")));
+    }
+
+    #[test]
+    fn compact() {
+        let mut graph = test_graph();
+
+        graph.remove_node(Node::from("B"));
+        graph.flush();
+
+        let nodes = ["A", "C", "D"].map(Node::from);
+        let distances = |graph: &Webgraph| {
+            nodes
+                .iter()
+                .map(|node| {
+                    (
+                        graph.distances(node.clone()),
+                        graph.reversed_distances(node.clone()),
+                        graph.host_distances(node.clone()),
+                        graph.host_reversed_distances(node.clone()),
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+        let max_id = |graph: &Webgraph| graph.full_graph().unwrap().nodes().max();
+
+        let distances_before = distances(&graph);
+        let max_id_before = max_id(&graph).unwrap();
+
+        graph.compact().unwrap();
+
+        assert!(max_id(&graph).unwrap() < max_id_before);
+        assert_eq!(distances(&graph), distances_before);
+        assert_eq!(
+            graph.distances(Node::from("D")).get(&Node::from("A")),
+            Some(&2)
+        );
+
+        graph.insert(Node::from("D"), Node::from("E"), String::new());
+        graph.flush();
+        assert_eq!(
+            graph.distances(Node::from("D")).get(&Node::from("E")),
+            Some(&1)
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "opened read-only")]
+    fn read_only_insert() {
+        let path = crate::gen_temp_path();
+
+        let mut graph = WebgraphBuilder::new(&path).with_full_graph().open();
+        graph.insert(Node::from("A"), Node::from("B"), String::new());
+        graph.flush();
+        drop(graph);
+
+        let mut graph = WebgraphBuilder::new(&path)
+            .with_full_graph()
+            .read_only(true)
+            .open();
+
+        assert!(matches!(
+            graph.compact(),
+            Err(crate::Error::ReadOnlyWebgraph)
+        ));
+
+        graph.insert(Node::from("B"), Node::from("C"), String::new());
+    }
+
+    #[test]
+    fn lazily_opened_stores() {
+        let path = crate::gen_temp_path();
+
+        let mut graph = WebgraphBuilder::new(&path)
+            .with_full_graph()
+            .with_host_graph()
+            .open();
+        graph.insert(Node::from("A"), Node::from("B"), String::new());
+        graph.insert(Node::from("B"), Node::from("C"), String::new());
+        graph.flush();
+        drop(graph);
+
+        std::fs::remove_dir_all(path.join("full")).unwrap();
+
+        let graph = WebgraphBuilder::new(&path)
+            .with_full_graph()
+            .with_host_graph()
+            .open();
+
+        assert_eq!(
+            graph.host_distances(Node::from("A")).get(&Node::from("C")),
+            Some(&2)
+        );
+        assert!(graph
+            .host_harmonic_centrality()
+            .contains_key(&Node::from("C")));
+        graph.flush();
+
+        assert!(graph.full_graph.as_ref().unwrap().get_if_open().is_none());
+        assert!(!path.join("full").exists());
+
+        assert!(graph.distances(Node::from("A")).is_empty());
+        assert!(graph.full_graph.as_ref().unwrap().get_if_open().is_some());
+    }
+
+    #[test]
+    fn self_loops() {
+        let mut graph = test_graph();
+        assert!(graph.self_loops().is_empty());
+
+        graph.insert(Node::from("B"), Node::from("B"), String::new());
+        graph.insert(Node::from("A"), Node::from("A"), String::new());
+        graph.flush();
+
+        assert_eq!(graph.self_loops(), vec![Node::from("A"), Node::from("B")]);
+        assert_eq!(graph.outgoing_edges(Node::from("B")).len(), 2);
+
+        let mut graph = WebgraphBuilder::new_memory()
+            .with_full_graph()
+            .with_host_graph()
+            .drop_self_loops(true)
+            .open();
+
+        graph.insert(Node::from("B"), Node::from("B"), String::new());
+        graph.insert(Node::from("B"), Node::from("C"), String::new());
+        graph.insert_batch(vec![
+            (Node::from("A"), Node::from("A"), String::new()),
+            (Node::from("A"), Node::from("B"), String::new()),
+        ]);
+        graph.flush();
+
+        assert!(graph.self_loops().is_empty());
+        assert_eq!(graph.outgoing_edges(Node::from("B")).len(), 1);
+        assert_eq!(graph.outgoing_edges(Node::from("A")).len(), 1);
+        assert_eq!(graph.host_graph().unwrap().edges().count(), 2);
+    }
+
+    #[test]
+    fn label_histogram() {
+        let mut graph = WebgraphBuilder::new_memory().with_full_graph().open();
+
+        graph.insert(Node::from("A"), Node::from("B"), "nav".to_string());
+        graph.insert(Node::from("A"), Node::from("C"), "nav".to_string());
+        graph.insert(Node::from("B"), Node::from("C"), "content".to_string());
+        graph.insert(Node::from("C"), Node::from("A"), "nav".to_string());
+        graph.insert(Node::from("C"), Node::from("B"), String::new());
+        graph.flush();
 
-        Self {
-            root,
-            has_full,
-            has_host,
+        let histogram = graph.label_histogram();
+
+        assert_eq!(histogram.len(), 3);
+        assert_eq!(histogram.get("nav"), Some(&3));
+        assert_eq!(histogram.get("content"), Some(&1));
+        assert_eq!(histogram.get(""), Some(&1));
+    }
+
+    #[test]
+    fn nodes_in_distance_range() {
+        let graph = test_graph();
+
+        assert_eq!(
+            graph.nodes_in_distance_range(Node::from("D"), 2, 2),
+            vec![Node::from("A")]
+        );
+        assert_eq!(
+            graph.nodes_in_distance_range(Node::from("D"), 2, 3),
+            vec![Node::from("A"), Node::from("B")]
+        );
+        assert_eq!(
+            graph.nodes_in_distance_range(Node::from("D"), 0, 1),
+            vec![Node::from("D"), Node::from("C")]
+        );
+        assert!(graph
+            .nodes_in_distance_range(Node::from("D"), 3, 2)
+            .is_empty());
+        assert!(graph
+            .nodes_in_distance_range(Node::from("D"), 4, 10)
+            .is_empty());
+    }
+
+    #[test]
+    fn reversed_view() {
+        let graph = test_graph();
+        let reversed = graph.reversed();
+
+        for node in ["A", "B", "C", "D"].map(Node::from) {
+            assert_eq!(
+                reversed.distances(node.clone()),
+                graph.reversed_distances(node.clone())
+            );
+            assert_eq!(
+                reversed.host_distances(node.clone()),
+                graph.host_reversed_distances(node.clone())
+            );
+            assert_eq!(
+                reversed.reversed_distances(node.clone()),
+                graph.distances(node)
+            );
         }
+
+        assert_eq!(
+            reversed.distances(Node::from("B")).get(&Node::from("D")),
+            Some(&3)
+        );
+        assert_eq!(
+            reversed.distances(Node::from("B")).get(&Node::from("C")),
+            Some(&2)
+        );
+
+        assert_eq!(
+            reversed.outgoing_edges(Node::from("B")),
+            vec![FullEdge {
+                from: Node::from("B"),
+                to: Node::from("A"),
+                label: String::new(),
+            }]
+        );
     }
-}
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct FrozenWebgraph {
-    pub root: DirEntry,
-    has_full: bool,
-    has_host: bool,
-}
+    #[test]
+    fn distances_from_set() {
+        let graph = test_graph();
 
-#[cfg(test)]
-mod test {
-    use super::*;
+        let distances = graph.distances_from_set(&[Node::from("A"), Node::from("D")]);
 
-    fn test_graph() -> Webgraph {
-        //     ┌────┐
-        //     │    │
-        // ┌───A◄─┐ │
-        // │      │ │
-        // ▼      │ │
-        // B─────►C◄┘
-        //        ▲
-        //        │
-        //        │
-        //        D
+        assert_eq!(distances.get(&Node::from("A")), Some(&0));
+        assert_eq!(distances.get(&Node::from("D")), Some(&0));
+        assert_eq!(distances.get(&Node::from("B")), Some(&1));
+        assert_eq!(distances.get(&Node::from("C")), Some(&1));
+
+        let distances = graph.distances_from_set(&[Node::from("D"), Node::from("unknown")]);
+        assert_eq!(distances, graph.distances(Node::from("D")));
+
+        assert!(graph.distances_from_set(&[]).is_empty());
+    }
+
+    #[test]
+    fn rocksdb_tuning() {
+        let mut graph = WebgraphBuilder::new_memory()
+            .with_full_graph()
+            .with_host_graph()
+            .with_rocksdb_options(RocksDbTuning {
+                block_cache_size: Some(1024 * 1024),
+                write_buffer_size: Some(1024 * 1024),
+                compression: Some(rocksdb::DBCompressionType::Lz4),
+                bulk_load: true,
+            })
+            .open();
+
+        graph.insert(Node::from("A"), Node::from("B"), String::new());
+        graph.insert(Node::from("B"), Node::from("C"), String::new());
+        graph.insert(Node::from("A"), Node::from("C"), String::new());
+        graph.insert(Node::from("C"), Node::from("A"), String::new());
+        graph.insert(Node::from("D"), Node::from("C"), String::new());
+
+        graph.finish_bulk_load();
+
+        let expected = test_graph();
+
+        for node in ["A", "B", "C", "D"] {
+            assert_eq!(
+                graph.distances(Node::from(node)),
+                expected.distances(Node::from(node))
+            );
+        }
+
+        let centrality = graph.harmonic_centrality();
+        let expected_centrality = expected.harmonic_centrality();
+        assert_eq!(centrality.len(), expected_centrality.len());
+        for (node, score) in expected_centrality {
+            assert!((centrality[&node] - score).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn empty_graph() {
+        let graph = WebgraphBuilder::new_memory()
+            .with_full_graph()
+            .with_host_graph()
+            .open();
+        let node = Node::from("A");
+
+        assert!(graph.distances(node.clone()).is_empty());
+        assert!(graph.distances_from_set(&[node.clone()]).is_empty());
+        assert!(graph.distances_sorted(node.clone()).is_empty());
+        assert!(graph.reversed_distances(node.clone()).is_empty());
+        assert!(graph.host_distances(node.clone()).is_empty());
+        assert!(graph.host_reversed_distances(node.clone()).is_empty());
+        assert!(graph.neighborhood(node.clone(), 2, false).is_empty());
+        assert!(graph.nodes_in_distance_range(node.clone(), 0, 2).is_empty());
+        assert_eq!(graph.shortest_path(node.clone(), Node::from("B")), None);
+
+        assert!(graph.harmonic_centrality().is_empty());
+        assert!(graph.host_harmonic_centrality().is_empty());
+        assert!(graph.host_harmonic_centrality_cutoff(2).is_empty());
+        assert!(graph.host_weighted_harmonic_centrality().is_empty());
+        assert!(graph.host_closeness_centrality().is_empty());
+        assert!(graph.top_host_harmonic_centrality(10).is_empty());
+        assert!(graph.host_pagerank(0.85, 10).is_empty());
+        assert!(graph.full_pagerank(0.85, 10).is_empty());
+
+        let mut cached = HashMap::new();
+        graph.update_centrality(&mut cached, &[node.clone()], 2);
+        assert!(cached.is_empty());
+
+        assert!(graph.weakly_connected_components().is_empty());
+        assert_eq!(graph.num_components(), 0);
+        assert!(graph.self_loops().is_empty());
+        assert!(graph.label_histogram().is_empty());
+        assert_eq!(graph.nodes().count(), 0);
+        assert_eq!(graph.in_degree(node.clone()), 0);
+        assert!(graph.outgoing_edges(node).is_empty());
+
+        let stats = graph.stats();
+        assert_eq!(stats.num_nodes, 0);
+        assert_eq!(stats.num_host_edges, 0);
+        assert_eq!(stats.avg_out_degree, 0.0);
+    }
+
+    #[test]
+    fn single_node_graph() {
+        let mut graph = WebgraphBuilder::new_memory()
+            .with_full_graph()
+            .with_host_graph()
+            .open();
+        graph.insert(Node::from("A"), Node::from("A"), String::new());
+        graph.flush();
+
+        assert!(graph.harmonic_centrality().is_empty());
+        assert!(graph.host_harmonic_centrality().is_empty());
+        assert!(graph.host_weighted_harmonic_centrality().is_empty());
+        assert_eq!(graph.host_closeness_centrality()[&Node::from("A")], 0.0);
+    }
+
+    #[test]
+    fn id_mapping() {
+        let graph = test_graph();
+        let path = crate::gen_temp_path();
+
+        graph.save_id_mapping(&path).unwrap();
+        let mapping = Webgraph::load_id_mapping(&path).unwrap();
+
+        let full_graph = graph.full_graph().unwrap();
+        assert_eq!(mapping.len(), 4);
+
+        for node in graph.nodes() {
+            let id = mapping.node2id(&node).unwrap();
+
+            assert_eq!(full_graph.node2id(&node), Some(id));
+            assert_eq!(full_graph.id2node(&id), Some(node.clone()));
+            assert_eq!(mapping.id2node(&id), Some(&node));
+        }
+
+        assert_eq!(mapping.node2id(&Node::from("E")), None);
+
+        let mut bytes = fs::read(&path).unwrap();
+        bytes[..4].copy_from_slice(&(ID_MAPPING_VERSION + 1).to_le_bytes());
+        fs::write(&path, bytes).unwrap();
+
+        let expected = ID_MAPPING_VERSION + 1;
+        assert!(matches!(
+            Webgraph::load_id_mapping(&path),
+            Err(crate::Error::UnsupportedIdMappingVersion(version)) if version == expected
+        ));
+    }
+
+    #[test]
+    fn weakly_connected_components() {
+        let mut graph = test_graph();
+
+        let components = graph.weakly_connected_components();
+        assert_eq!(components.len(), 4);
+        assert!(components.values().all(|component| *component == 0));
+        assert_eq!(graph.num_components(), 1);
+
+        graph.insert(Node::from("E"), Node::from("F"), String::new());
+        graph.flush();
+
+        let components = graph.weakly_connected_components();
+        assert_eq!(graph.num_components(), 2);
+        assert_eq!(components.get(&Node::from("A")), Some(&0));
+        assert_eq!(components.get(&Node::from("E")), Some(&1));
+        assert_eq!(components.get(&Node::from("F")), Some(&1));
+    }
+
+    #[test]
+    fn weakly_connected_components_unknown_node() {
+        let mut graph = test_graph();
+
+        let a = graph
+            .full_graph()
+            .unwrap()
+            .node2id(&Node::from("A"))
+            .unwrap();
+        if let Some(full_graph) = &mut graph.full_graph {
+            full_graph
+                .get_mut()
+                .insert_raw_edge(a, 1_000, String::new());
+        }
+
+        let components = graph.weakly_connected_components();
+        assert_eq!(components.len(), 4);
+        assert_eq!(graph.num_components(), 1);
+    }
+
+    #[test]
+    fn subgraph() {
+        let graph = test_graph();
+
+        let nodes: HashSet<_> = [Node::from("A"), Node::from("C")].into_iter().collect();
+        let subgraph = graph.subgraph(&nodes);
+
+        let mut edges: Vec<_> = subgraph
+            .outgoing_edges(Node::from("A"))
+            .into_iter()
+            .chain(subgraph.outgoing_edges(Node::from("C")))
+            .map(|edge| (edge.from.name, edge.to.name))
+            .collect();
+        edges.sort();
+
+        assert_eq!(
+            edges,
+            vec![
+                ("A".to_string(), "C".to_string()),
+                ("C".to_string(), "A".to_string())
+            ]
+        );
+
+        assert_eq!(subgraph.stats().num_nodes, 2);
+        assert_eq!(subgraph.stats().num_edges, 2);
+        assert!(subgraph.distances(Node::from("B")).is_empty());
+        assert_eq!(
+            subgraph.distances(Node::from("A")).get(&Node::from("C")),
+            Some(&1)
+        );
+        assert_eq!(
+            subgraph.host_harmonic_centrality().get(&Node::from("C")),
+            Some(&1.0)
+        );
+
+        fs::remove_dir_all(subgraph.path).unwrap();
+    }
+
+    #[test]
+    fn edge_iterator_multiple_blocks() {
+        let mut graph = WebgraphBuilder::new_memory().with_full_graph().open();
+
+        let num_nodes = 2_500;
+        for i in 0..num_nodes {
+            graph.insert(
+                Node::from(i.to_string()),
+                Node::from(((i + 1) % num_nodes).to_string()),
+                String::new(),
+            );
+        }
+        graph.flush();
+
+        let graph_store = graph.full_graph().unwrap();
+        let mut edges: Vec<_> = graph_store
+            .edges()
+            .map(|edge| (edge.from, edge.to))
+            .collect();
+
+        assert_eq!(edges.len(), num_nodes);
+
+        edges.sort_unstable();
+        edges.dedup();
+        assert_eq!(edges.len(), num_nodes);
+    }
+
+    #[test]
+    fn stats() {
+        let graph = test_graph();
+
+        assert_eq!(
+            graph.stats(),
+            GraphStats {
+                num_nodes: 4,
+                num_edges: 5,
+                num_host_nodes: 4,
+                num_host_edges: 5,
+                avg_out_degree: 1.25,
+            }
+        );
+    }
+
+    #[test]
+    fn export_edge_list() {
+        let mut graph = test_graph();
+        graph.insert(Node::from("D"), Node::from("A"), "some\tlabel".to_string());
+        graph.flush();
+
+        let path = crate::gen_temp_path();
+        graph.export_edge_list(&path).unwrap();
+
+        let contents = fs::read_to_string(&path).unwrap();
+        let mut lines: Vec<_> = contents.lines().collect();
+        lines.sort_unstable();
+
+        assert_eq!(
+            lines,
+            vec![
+                "A\tB\t",
+                "A\tC\t",
+                "B\tC\t",
+                "C\tA\t",
+                "D\tA\tsome label",
+                "D\tC\t",
+            ]
+        );
+
+        let host_path = crate::gen_temp_path();
+        graph.host_export_edge_list(&host_path).unwrap();
+        assert_eq!(fs::read_to_string(&host_path).unwrap().lines().count(), 6);
+
+        fs::remove_file(path).unwrap();
+        fs::remove_file(host_path).unwrap();
+    }
+
+    #[test]
+    fn import_edge_list() {
+        let path = crate::gen_temp_path();
+        fs::write(
+            &path,
+            "A\tB\t\nB\tC\tlabel\nA\tC\nmalformed\nC\tA\t\nD\tC\t\n",
+        )
+        .unwrap();
+
+        let graph = WebgraphBuilder::new_memory()
+            .with_full_graph()
+            .with_host_graph()
+            .from_edge_list(&path)
+            .unwrap();
+
+        let distances = graph.distances(Node::from("D"));
+
+        assert_eq!(distances.get(&Node::from("C")), Some(&1));
+        assert_eq!(distances.get(&Node::from("A")), Some(&2));
+        assert_eq!(distances.get(&Node::from("B")), Some(&3));
+        assert_eq!(graph.stats().num_edges, 5);
+        assert_eq!(graph.stats().num_host_edges, 5);
+
+        assert_eq!(
+            graph.ingoing_edges(Node::from("C")).len(),
+            graph.in_degree(Node::from("C"))
+        );
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn export_import_roundtrip() {
+        let graph = test_graph();
+
+        let path = crate::gen_temp_path();
+        graph.export_edge_list(&path).unwrap();
+
+        let imported = WebgraphBuilder::new_memory()
+            .with_full_graph()
+            .from_edge_list(&path)
+            .unwrap();
+
+        for node in ["A", "B", "C", "D"] {
+            assert_eq!(
+                graph.distances(Node::from(node)),
+                imported.distances(Node::from(node))
+            );
+        }
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn serialize_deserialize_bincode() {
+        let graph = test_graph();
+        let path = graph.path.clone();
+        let mut frozen: FrozenWebgraph = graph.into();
+        frozen.root = directory::relocate(frozen.root, &relative_temp_path());
+        let bytes = bincode::serialize(&frozen).unwrap();
+
+        std::fs::remove_dir_all(path).unwrap();
+
+        let deserialized_frozen: FrozenWebgraph = bincode::deserialize(&bytes).unwrap();
+        let graph: Webgraph = deserialized_frozen.into();
+
+        let distances = graph.distances(Node::from("D"));
+
+        assert_eq!(distances.get(&Node::from("C")), Some(&1));
+        assert_eq!(distances.get(&Node::from("A")), Some(&2));
+        assert_eq!(distances.get(&Node::from("B")), Some(&3));
+
+        let path = graph.path.clone();
+        drop(graph);
+        std::fs::remove_dir_all(path).unwrap();
+    }
+
+    #[test]
+    fn available_graphs() {
+        let graph = test_graph();
+        assert!(graph.has_full_graph());
+        assert!(graph.has_host_graph());
+
+        let mut graph = WebgraphBuilder::new_memory().with_host_graph().open();
+        graph.insert(Node::from("A"), Node::from("B"), String::new());
+        assert!(!graph.has_full_graph());
+        assert!(graph.has_host_graph());
+
+        let path = graph.path.clone();
+        let mut frozen: FrozenWebgraph = graph.into();
+        frozen.root = directory::relocate(frozen.root, &relative_temp_path());
+        std::fs::remove_dir_all(path).unwrap();
+
+        let graph: Webgraph = frozen.into();
+        assert!(!graph.has_full_graph());
+        assert!(graph.has_host_graph());
+
+        let path = graph.path.clone();
+        drop(graph);
+        std::fs::remove_dir_all(path).unwrap();
+    }
+
+    #[test]
+    fn frozen_file_root() {
+        let frozen = FrozenWebgraph {
+            root: DirEntry::File {
+                name: "graph".to_string(),
+                content: Vec::new(),
+            },
+            has_full: true,
+            has_host: true,
+        };
+        let bytes = bincode::serialize(&frozen).unwrap();
+
+        let frozen: FrozenWebgraph = bincode::deserialize(&bytes).unwrap();
+        assert!(matches!(
+            Webgraph::try_from_frozen(frozen),
+            Err(FrozenError::FileRoot)
+        ));
+    }
+
+    #[test]
+    fn frozen_unsafe_paths() {
+        let frozen = |root: &str, child: &str| FrozenWebgraph {
+            root: DirEntry::Folder {
+                name: root.to_string(),
+                entries: vec![DirEntry::File {
+                    name: child.to_string(),
+                    content: Vec::new(),
+                }],
+            },
+            has_full: true,
+            has_host: true,
+        };
+
+        assert!(matches!(
+            Webgraph::try_from_frozen(frozen("/tmp/graph", "/tmp/graph/file")),
+            Err(FrozenError::UnsafePath(_))
+        ));
+        assert!(matches!(
+            Webgraph::try_from_frozen(frozen("graph", "graph/../file")),
+            Err(FrozenError::UnsafePath(_))
+        ));
+        assert!(matches!(
+            Webgraph::try_from_frozen(frozen("graph", "other/file")),
+            Err(FrozenError::UnsafePath(_))
+        ));
+        assert!(matches!(
+            Webgraph::try_from_frozen(frozen("../graph", "../graph/file")),
+            Err(FrozenError::UnsafePath(_))
+        ));
+        assert!(matches!(
+            Webgraph::try_from_frozen(frozen("", "file")),
+            Err(FrozenError::UnsafePath(_))
+        ));
+        assert!(matches!(
+            Webgraph::try_from_frozen(frozen(".", "./file")),
+            Err(FrozenError::UnsafePath(_))
+        ));
+        assert!(Path::new("src/lib.rs").exists());
+    }
+
+    #[test]
+    fn frozen_existing_folder() {
+        let frozen = FrozenWebgraph {
+            root: DirEntry::Folder {
+                name: "src".to_string(),
+                entries: vec![DirEntry::File {
+                    name: "src/file".to_string(),
+                    content: Vec::new(),
+                }],
+            },
+            has_full: true,
+            has_host: true,
+        };
+
+        assert!(matches!(
+            Webgraph::try_from_frozen(frozen),
+            Err(FrozenError::ExistingFolder(_))
+        ));
+        assert!(Path::new("src/lib.rs").exists());
+        assert!(!Path::new("src/file").exists());
+    }
+
+    #[test]
+    fn host_harmonic_centrality_for() {
+        let graph = test_graph();
+
+        let full = graph.host_harmonic_centrality();
+        let centrality = graph.host_harmonic_centrality_for(&[Node::from("C")]);
+
+        assert_eq!(centrality.len(), 1);
+        assert!((centrality[&Node::from("C")] - full[&Node::from("C")]).abs() < 1e-9);
+
+        assert!(graph
+            .host_harmonic_centrality_for(&[Node::from("E")])
+            .is_empty());
+    }
 
+    #[test]
+    fn validate() {
+        let mut graph = test_graph();
+        assert!(graph.validate().is_empty());
+        assert!(graph.host_validate().is_empty());
+
+        let a = graph
+            .full_graph()
+            .unwrap()
+            .node2id(&Node::from("A"))
+            .unwrap();
+        if let Some(full_graph) = &mut graph.full_graph {
+            full_graph
+                .get_mut()
+                .insert_raw_edge(a, 1_000, String::new());
+        }
+
+        assert_eq!(
+            graph.validate(),
+            vec![ValidationError::UnknownNode {
+                edge: Edge {
+                    from: a,
+                    to: 1_000,
+                    label: String::new(),
+                },
+                id: 1_000,
+            }]
+        );
+
+        graph.remove_edge(Node::from("D"), Node::from("C"));
+        assert!(graph
+            .validate()
+            .contains(&ValidationError::IsolatedNode(Node::from("D"))));
+    }
+
+    #[test]
+    fn memory_store() {
         let mut graph = WebgraphBuilder::new_memory()
             .with_full_graph()
             .with_host_graph()
-            .open();
+            .open_with::<MemoryStore>();
 
         graph.insert(Node::from("A"), Node::from("B"), String::new());
         graph.insert(Node::from("B"), Node::from("C"), String::new());
@@ -580,157 +4621,591 @@ mod test {
         graph.insert(Node::from("C"), Node::from("A"), String::new());
         graph.insert(Node::from("D"), Node::from("C"), String::new());
 
-        graph.flush();
+        let distances = graph.distances(Node::from("D"));
 
-        graph
+        assert_eq!(distances.get(&Node::from("C")), Some(&1));
+        assert_eq!(distances.get(&Node::from("A")), Some(&2));
+        assert_eq!(distances.get(&Node::from("B")), Some(&3));
+        assert!(!Path::new(&graph.path).exists());
     }
 
     #[test]
-    fn distance_calculation() {
+    fn edges() {
         let graph = test_graph();
 
-        let distances = graph.distances(Node::from("D"));
+        let mut edges: Vec<_> = graph
+            .edges()
+            .map(|edge| (edge.from.name, edge.to.name, edge.label))
+            .collect();
+        edges.sort();
 
-        assert_eq!(distances.get(&Node::from("C")), Some(&1));
-        assert_eq!(distances.get(&Node::from("A")), Some(&2));
-        assert_eq!(distances.get(&Node::from("B")), Some(&3));
+        let expected: Vec<_> = [("A", "B"), ("A", "C"), ("B", "C"), ("C", "A"), ("D", "C")]
+            .into_iter()
+            .map(|(from, to)| (from.to_string(), to.to_string(), String::new()))
+            .collect();
+
+        assert_eq!(edges, expected);
+        assert_eq!(graph.host_edges().count(), expected.len());
+
+        let host_only = WebgraphBuilder::new_memory().with_host_graph().open();
+        assert_eq!(host_only.edges().count(), 0);
     }
 
     #[test]
-    fn nonexisting_node() {
+    fn host_harmonic_centrality_min_degree() {
         let graph = test_graph();
-        assert_eq!(graph.distances(Node::from("E")).len(), 0);
-        assert_eq!(graph.reversed_distances(Node::from("E")).len(), 0);
+
+        let full = graph.host_harmonic_centrality();
+        let centrality = graph.host_harmonic_centrality_min_degree(2);
+
+        // B only has a link from A
+        assert!(!centrality.contains_key(&Node::from("B")));
+        assert_eq!(centrality.len(), 1);
+        assert!((centrality[&Node::from("C")] - full[&Node::from("C")]).abs() < 1e-9);
+
+        assert_eq!(
+            graph.host_harmonic_centrality_min_degree(0).len(),
+            full.len()
+        );
     }
 
     #[test]
-    fn reversed_distance_calculation() {
+    fn canonical_node() {
+        let a = Url::from("HTTP://Example.com:80/".to_string());
+        let b = Url::from("http://example.com".to_string());
+
+        assert_ne!(Node::from(&a), Node::from(&b));
+        assert_eq!(Node::canonical(&a), Node::canonical(&b));
+        assert_eq!(Node::canonical(&a), Node::from("http://example.com"));
+
+        let mut graph = WebgraphBuilder::new_memory().with_full_graph().open();
+        graph.insert(Node::canonical(&a), Node::from("B"), String::new());
+        graph.insert(Node::canonical(&b), Node::from("C"), String::new());
+        assert_eq!(graph.out_degree(Node::canonical(&a)), 2);
+    }
+
+    #[test]
+    fn harmonic_centrality_chunked() {
         let graph = test_graph();
+        let path = crate::gen_temp_path();
 
-        let distances = graph.reversed_distances(Node::from("D"));
+        let read_centralities = || -> HashMap<String, f64> {
+            fs::read_to_string(&path)
+                .unwrap()
+                .lines()
+                .map(|line| {
+                    let (node, centrality) = line.split_once('\t').unwrap();
+                    (node.to_string(), centrality.parse().unwrap())
+                })
+                .collect()
+        };
 
-        assert_eq!(distances.get(&Node::from("C")), None);
-        assert_eq!(distances.get(&Node::from("A")), None);
-        assert_eq!(distances.get(&Node::from("B")), None);
+        let expected = graph.harmonic_centrality();
+        let assert_matches = |centralities: HashMap<String, f64>| {
+            assert_eq!(centralities.len(), expected.len());
+            for (node, centrality) in &expected {
+                assert!((centralities[&node.name] - centrality).abs() < 1e-9);
+            }
+        };
 
-        let distances = graph.reversed_distances(Node::from("A"));
+        let mut tokens = Vec::new();
+        graph
+            .harmonic_centrality_chunked(&path, 1, None, |token| tokens.push(token))
+            .unwrap();
+        assert_eq!(tokens.len(), 4);
+        assert_matches(read_centralities());
 
-        assert_eq!(distances.get(&Node::from("C")), Some(&1));
-        assert_eq!(distances.get(&Node::from("D")), Some(&2));
-        assert_eq!(distances.get(&Node::from("B")), Some(&2));
+        // resuming after the second chunk discards everything written after it,
+        // such as a partially written line
+        let mut file = fs::OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"partial li").unwrap();
+        drop(file);
+
+        let mut resumed_tokens = Vec::new();
+        graph
+            .harmonic_centrality_chunked(&path, 1, Some(tokens[1]), |token| {
+                resumed_tokens.push(token)
+            })
+            .unwrap();
+        assert_eq!(resumed_tokens, tokens[2..]);
+        assert_matches(read_centralities());
+
+        fs::remove_file(path).unwrap();
     }
 
     #[test]
-    fn harmonic_centrality() {
-        let graph = test_graph();
+    fn bulk_load_matches_single_inserts() {
+        let edges: Vec<_> = (0..3000)
+            .map(|i| {
+                (
+                    Node::from(format!("https://www.{}.com/{}", i % 7, i)),
+                    Node::from(format!("https://{}.com/{}", i % 11, i * 31 % 3000)),
+                    format!("{}", i % 3),
+                )
+            })
+            .collect();
 
-        let centrality = graph.harmonic_centrality();
+        let mut single = WebgraphBuilder::new_memory()
+            .with_full_graph()
+            .with_host_graph()
+            .open();
+
+        for (from, to, label) in edges.clone() {
+            single.insert(from, to, label);
+        }
+        single.flush();
+
+        let mut bulk = WebgraphBuilder::new_memory()
+            .with_full_graph()
+            .with_host_graph()
+            .open();
+
+        // the budget is small enough that the edges are spilled to disk several times
+        let mut loader = bulk.bulk_loader().with_memory_budget(50_000);
+        for (from, to, label) in edges {
+            loader.insert(from, to, label).unwrap();
+        }
+        loader.finish();
+
+        let snapshot = |store: &GraphStore<RocksDbStore>| {
+            let mut nodes: Vec<_> = store.nodes().collect();
+            nodes.sort_unstable();
+
+            nodes
+                .into_iter()
+                .map(|id| {
+                    (
+                        store.id2node(&id),
+                        store.outgoing_edges(id),
+                        store.ingoing_edges(id),
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
 
-        assert_eq!(centrality.get(&Node::from("C")).unwrap(), &1.0);
-        assert_eq!(centrality.get(&Node::from("D")), None);
         assert_eq!(
-            (*centrality.get(&Node::from("A")).unwrap() * 100.0).round() / 100.0,
-            0.67
+            snapshot(single.full_graph().unwrap()),
+            snapshot(bulk.full_graph().unwrap())
         );
         assert_eq!(
-            (*centrality.get(&Node::from("B")).unwrap() * 100.0).round() / 100.0,
-            0.61
+            snapshot(single.host_graph().unwrap()),
+            snapshot(bulk.host_graph().unwrap())
         );
     }
 
     #[test]
-    fn host_harmonic_centrality() {
+    fn host_edge_evidence() {
         let mut graph = WebgraphBuilder::new_memory()
             .with_full_graph()
             .with_host_graph()
+            .with_host_edge_evidence(2)
             .open();
 
-        graph.insert(Node::from("A.com/1"), Node::from("A.com/2"), String::new());
-        graph.insert(Node::from("A.com/1"), Node::from("A.com/3"), String::new());
-        graph.insert(Node::from("A.com/1"), Node::from("A.com/4"), String::new());
-        graph.insert(Node::from("A.com/2"), Node::from("A.com/1"), String::new());
-        graph.insert(Node::from("A.com/2"), Node::from("A.com/3"), String::new());
-        graph.insert(Node::from("A.com/2"), Node::from("A.com/4"), String::new());
-        graph.insert(Node::from("A.com/3"), Node::from("A.com/1"), String::new());
-        graph.insert(Node::from("A.com/3"), Node::from("A.com/2"), String::new());
-        graph.insert(Node::from("A.com/3"), Node::from("A.com/4"), String::new());
-        graph.insert(Node::from("A.com/4"), Node::from("A.com/1"), String::new());
-        graph.insert(Node::from("A.com/4"), Node::from("A.com/2"), String::new());
-        graph.insert(Node::from("A.com/4"), Node::from("A.com/3"), String::new());
-        graph.insert(Node::from("C.com"), Node::from("B.com"), String::new());
-        graph.insert(Node::from("D.com"), Node::from("B.com"), String::new());
+        graph.insert(
+            Node::from("https://a.com/1"),
+            Node::from("https://b.com/1"),
+            String::new(),
+        );
+        graph.insert(
+            Node::from("https://a.com/1"),
+            Node::from("https://b.com/2"),
+            String::new(),
+        );
+        graph.insert_batch(vec![
+            (
+                Node::from("https://a.com/2"),
+                Node::from("https://b.com/1"),
+                String::new(),
+            ),
+            (
+                Node::from("https://www.a.com/3"),
+                Node::from("https://b.com/3"),
+                String::new(),
+            ),
+        ]);
 
-        graph.flush();
+        let evidence =
+            graph.host_edge_evidence(Node::from("https://a.com"), Node::from("https://b.com"));
+        assert_eq!(evidence.count, 4);
+        assert_eq!(
+            evidence.sample_urls,
+            vec!["https://a.com/1".to_string(), "https://a.com/2".to_string()]
+        );
+        assert_eq!(
+            graph
+                .host_edge_evidence(Node::from("https://b.com"), Node::from("https://a.com"))
+                .count,
+            0
+        );
 
-        let centrality = graph.harmonic_centrality();
-        assert!(
-            centrality.get(&Node::from("A.com/1")).unwrap()
-                > centrality.get(&Node::from("B.com")).unwrap()
+        // links that are already in the full graph are not counted again
+        graph.insert(
+            Node::from("https://a.com/2"),
+            Node::from("https://b.com/1"),
+            String::new(),
+        );
+        assert_eq!(
+            graph
+                .host_edge_evidence(Node::from("https://a.com"), Node::from("https://b.com"))
+                .count,
+            4
         );
 
-        let host_centrality = graph.host_harmonic_centrality();
-        assert!(
-            host_centrality.get(&Node::from("B.com")).unwrap()
-                > host_centrality.get(&Node::from("A.com")).unwrap_or(&0.0)
+        // the page still links to the host, so it stays in the samples
+        graph.remove_edge(Node::from("https://a.com/1"), Node::from("https://b.com/1"));
+        assert_eq!(
+            graph.host_edge_evidence(Node::from("https://a.com"), Node::from("https://b.com")),
+            EdgeEvidence {
+                count: 3,
+                sample_urls: vec!["https://a.com/1".to_string(), "https://a.com/2".to_string()],
+            }
+        );
+
+        graph.remove_edge(Node::from("https://a.com/1"), Node::from("https://b.com/2"));
+        assert_eq!(
+            graph.host_edge_evidence(Node::from("https://a.com"), Node::from("https://b.com")),
+            EdgeEvidence {
+                count: 2,
+                sample_urls: vec!["https://a.com/2".to_string()],
+            }
+        );
+
+        let mut graph = WebgraphBuilder::new_memory().with_host_graph().open();
+        graph.insert(
+            Node::from("https://a.com/1"),
+            Node::from("https://b.com/1"),
+            String::new(),
+        );
+        assert_eq!(
+            graph.host_edge_evidence(Node::from("https://a.com"), Node::from("https://b.com")),
+            EdgeEvidence {
+                count: 1,
+                sample_urls: Vec::new(),
+            }
         );
     }
 
     #[test]
-    fn www_subdomain_ignored() {
+    fn sample_urls_kept_while_page_links_host() {
         let mut graph = WebgraphBuilder::new_memory()
             .with_full_graph()
             .with_host_graph()
+            .with_host_edge_evidence(2)
             .open();
 
-        graph.insert(Node::from("B.com"), Node::from("A.com"), String::new());
-        graph.insert(Node::from("B.com"), Node::from("www.A.com"), String::new());
+        for to in ["https://b.com/1", "https://b.com/2", "https://b.com/3"] {
+            graph.insert(Node::from("https://a.com/1"), Node::from(to), String::new());
+        }
+        graph.insert(
+            Node::from("https://a.com/2"),
+            Node::from("https://b.com/1"),
+            String::new(),
+        );
+
+        let samples = |graph: &Webgraph| {
+            graph
+                .host_edge_evidence(Node::from("https://a.com"), Node::from("https://b.com"))
+                .sample_urls
+        };
+        assert_eq!(
+            samples(&graph),
+            vec!["https://a.com/1".to_string(), "https://a.com/2".to_string()]
+        );
+
+        // a.com/1 still links to b.com/2 and b.com/3, a.com/2 lost its only link to b.com
+        graph.remove_node(Node::from("https://b.com/1"));
+        assert_eq!(samples(&graph), vec!["https://a.com/1".to_string()]);
+
+        graph.remove_edge(Node::from("https://a.com/1"), Node::from("https://b.com/2"));
+        assert_eq!(samples(&graph), vec!["https://a.com/1".to_string()]);
+
+        graph.remove_edge(Node::from("https://a.com/1"), Node::from("https://b.com/3"));
+        assert!(samples(&graph).is_empty());
+    }
+
+    #[test]
+    fn shared_host_edge_removal() {
+        let mut graph = WebgraphBuilder::new_memory()
+            .with_full_graph()
+            .with_host_graph()
+            .open();
 
+        graph.insert(
+            Node::from("https://a.com/1"),
+            Node::from("https://b.com/1"),
+            String::new(),
+        );
+        graph.insert(
+            Node::from("https://a.com/2"),
+            Node::from("https://b.com/1"),
+            String::new(),
+        );
+        graph.insert(
+            Node::from("https://b.com/2"),
+            Node::from("https://a.com/1"),
+            String::new(),
+        );
         graph.flush();
 
-        let centrality = graph.host_harmonic_centrality();
-        assert_eq!(centrality.get(&Node::from("A.com")), Some(&1.0));
-        assert_eq!(centrality.get(&Node::from("www.A.com")), None);
+        let host_edges = |graph: &Webgraph| {
+            let mut edges: Vec<_> = graph
+                .host_edges()
+                .map(|edge| (edge.from.name, edge.to.name))
+                .collect();
+            edges.sort();
+            edges
+        };
+        let both = vec![
+            ("a.com".to_string(), "b.com".to_string()),
+            ("b.com".to_string(), "a.com".to_string()),
+        ];
+
+        // a.com/2 still links from a.com to b.com
+        graph.remove_edge(Node::from("https://a.com/1"), Node::from("https://b.com/1"));
+        graph.flush();
+        assert_eq!(host_edges(&graph), both);
+
+        graph.insert(
+            Node::from("https://a.com/1"),
+            Node::from("https://b.com/1"),
+            String::new(),
+        );
+
+        // a.com/2 keeps the host a.com and its edge to b.com alive
+        graph.remove_node(Node::from("https://a.com/1"));
+        graph.flush();
+        assert_eq!(
+            host_edges(&graph),
+            vec![("a.com".to_string(), "b.com".to_string())]
+        );
+        assert_eq!(graph.host_in_degree(Node::from("b.com")), 1);
+
+        graph.remove_node(Node::from("https://a.com/2"));
+        graph.flush();
+        assert!(host_edges(&graph).is_empty());
+        assert_eq!(
+            graph.host_nodes().collect::<Vec<_>>(),
+            vec![Node::from("b.com")]
+        );
     }
 
     #[test]
-    fn merge() {
-        let mut graph1 = WebgraphBuilder::new_memory()
+    fn batch_insert_matches_single_inserts() {
+        let edges: Vec<_> = (0..3000)
+            .map(|i| {
+                (
+                    Node::from(format!("https://www.{}.com/{}", i % 7, i)),
+                    Node::from(format!("https://{}.com/{}", i % 11, i * 31 % 3000)),
+                    format!("{}", i % 3),
+                )
+            })
+            .collect();
+
+        let mut single = WebgraphBuilder::new_memory()
             .with_full_graph()
             .with_host_graph()
             .open();
 
-        graph1.insert(Node::from("A"), Node::from("B"), String::new());
+        for (from, to, label) in edges.clone() {
+            single.insert(from, to, label);
+        }
+        single.flush();
 
-        let mut graph2 = WebgraphBuilder::new_memory()
+        let mut batch = WebgraphBuilder::new_memory()
             .with_full_graph()
             .with_host_graph()
             .open();
-        graph2.insert(Node::from("B"), Node::from("C"), String::new());
 
-        graph1.merge(graph2);
+        batch.insert_batch(edges);
+
+        let snapshot = |store: &GraphStore<RocksDbStore>| {
+            let mut nodes: Vec<_> = store.nodes().collect();
+            nodes.sort_unstable();
+
+            nodes
+                .into_iter()
+                .map(|id| {
+                    (
+                        store.id2node(&id),
+                        store.outgoing_edges(id),
+                        store.ingoing_edges(id),
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
 
         assert_eq!(
-            graph1.distances(Node::from("A")).get(&Node::from("C")),
-            Some(&2)
-        )
+            snapshot(single.full_graph().unwrap()),
+            snapshot(batch.full_graph().unwrap())
+        );
+        assert_eq!(
+            snapshot(single.host_graph().unwrap()),
+            snapshot(batch.host_graph().unwrap())
+        );
     }
 
     #[test]
-    fn serialize_deserialize_bincode() {
+    fn save_load_single_file() {
         let graph = test_graph();
-        let path = graph.path.clone();
-        let frozen: FrozenWebgraph = graph.into();
-        let bytes = bincode::serialize(&frozen).unwrap();
+        let graph_path = graph.path.clone();
 
-        std::fs::remove_dir_all(path).unwrap();
+        let path = crate::gen_temp_path();
+        graph.save_to(&path).unwrap();
 
-        let deserialized_frozen: FrozenWebgraph = bincode::deserialize(&bytes).unwrap();
-        let graph: Webgraph = deserialized_frozen.into();
+        std::fs::remove_dir_all(graph_path).unwrap();
+
+        let moved_path = crate::gen_temp_path();
+        std::fs::rename(&path, &moved_path).unwrap();
+
+        let graph = Webgraph::load_from(&moved_path).unwrap();
 
         let distances = graph.distances(Node::from("D"));
 
         assert_eq!(distances.get(&Node::from("C")), Some(&1));
         assert_eq!(distances.get(&Node::from("A")), Some(&2));
         assert_eq!(distances.get(&Node::from("B")), Some(&3));
+
+        let distances = graph.host_distances(Node::from("D"));
+        assert_eq!(distances.get(&Node::from("B")), Some(&3));
+
+        std::fs::remove_dir_all(&graph.path).unwrap();
+        std::fs::remove_file(moved_path).unwrap();
+    }
+
+    #[test]
+    fn orphan_node_id_skipped() {
+        let graph = test_graph();
+        let store = graph.full_graph().unwrap();
+
+        // E has an id and outgoing edges, but the id can't be translated back to a node
+        let orphan_id = 5_000;
+        let a_id = store.node2id(&Node::from("A")).unwrap();
+        store
+            .node2id
+            .lock()
+            .unwrap()
+            .store
+            .insert(Node::from("E"), orphan_id);
+
+        let block_id = orphan_id / store.adjacency.lock().unwrap().tree.block_size;
+        let mut block = HashMap::new();
+        block.insert(
+            orphan_id,
+            vec![StoredEdge {
+                other: a_id,
+                label: String::new(),
+            }],
+        );
+        store
+            .adjacency
+            .lock()
+            .unwrap()
+            .tree
+            .inner
+            .store
+            .insert(block_id, block);
+
+        let centrality = graph.harmonic_centrality();
+        let mut nodes: Vec<_> = centrality.into_keys().collect();
+        nodes.sort();
+        assert_eq!(
+            nodes,
+            vec![Node::from("A"), Node::from("B"), Node::from("C")]
+        );
+
+        let distances = graph.distances(Node::from("E"));
+        assert_eq!(distances.len(), 3);
+        assert_eq!(distances.get(&Node::from("A")), Some(&1));
+        assert_eq!(distances.get(&Node::from("B")), Some(&2));
+    }
+
+    #[test]
+    fn dot_export() {
+        let mut graph = test_graph();
+        graph.insert(Node::from("D"), Node::from("A"), "say \"hi\"".to_string());
+        graph.flush();
+
+        let dot = graph.to_dot(100);
+
+        assert!(dot.starts_with("digraph {\n"));
+        assert!(dot.ends_with("}\n"));
+        assert!(dot.contains("    \"A\" -> \"B\";\n"));
+        assert!(dot.contains("    \"B\" -> \"C\";\n"));
+        assert!(dot.contains("    \"A\" -> \"C\";\n"));
+        assert!(dot.contains("    \"C\" -> \"A\";\n"));
+        assert!(dot.contains("    \"D\" -> \"C\";\n"));
+        assert!(dot.contains("    \"D\" -> \"A\" [label=\"say \\\"hi\\\"\"];\n"));
+
+        assert_eq!(graph.to_dot(2).matches(" -> ").count(), 2);
+        assert_eq!(graph.to_dot(0), "digraph {\n}\n");
+    }
+
+    #[test]
+    fn dot_export_unknown_node() {
+        let mut graph = test_graph();
+
+        let a = graph
+            .full_graph()
+            .unwrap()
+            .node2id(&Node::from("A"))
+            .unwrap();
+        if let Some(full_graph) = &mut graph.full_graph {
+            full_graph
+                .get_mut()
+                .insert_raw_edge(a, 1_000, String::new());
+        }
+
+        assert_eq!(graph.to_dot(100).matches(" -> ").count(), 5);
+    }
+
+    #[test]
+    fn incremental_centrality() {
+        let mut graph = test_graph();
+        let mut centrality = graph.host_harmonic_centrality();
+        assert_eq!(centrality.get(&Node::from("D")), None);
+
+        graph.insert(Node::from("B"), Node::from("D"), String::new());
+        graph.update_centrality(&mut centrality, &[Node::from("B"), Node::from("D")], 1);
+
+        let expected = graph.host_harmonic_centrality();
+
+        for node in ["B", "C", "D"] {
+            let node = Node::from(node);
+            assert!((centrality[&node] - expected[&node]).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn label_filtered_traversal() {
+        let mut graph = WebgraphBuilder::new_memory().with_full_graph().open();
+
+        graph.insert(Node::from("A"), Node::from("B"), "content".to_string());
+        graph.insert(Node::from("B"), Node::from("C"), "nav".to_string());
+        graph.insert(Node::from("A"), Node::from("D"), "content".to_string());
+        graph.insert(Node::from("D"), Node::from("C"), "ad".to_string());
+        graph.insert(Node::from("A"), Node::from("C"), "ad".to_string());
+
+        graph.flush();
+
+        let content: HashSet<_> = ["content".to_string()].into_iter().collect();
+        let distances = graph.distances_with_labels(Node::from("A"), &content);
+        assert_eq!(distances.get(&Node::from("B")), Some(&1));
+        assert_eq!(distances.get(&Node::from("D")), Some(&1));
+        assert_eq!(distances.get(&Node::from("C")), None);
+
+        let content_and_nav: HashSet<_> = ["content".to_string(), "nav".to_string()]
+            .into_iter()
+            .collect();
+        let distances = graph.distances_with_labels(Node::from("A"), &content_and_nav);
+        assert_eq!(distances.get(&Node::from("C")), Some(&2));
+
+        assert_eq!(
+            graph.distances(Node::from("A")).get(&Node::from("C")),
+            Some(&1)
+        );
+
+        let centrality = graph.harmonic_centrality_with_labels(&content);
+        assert_eq!(centrality.get(&Node::from("C")), None);
+        assert!(centrality.get(&Node::from("B")).is_some());
     }
 }
@@ -27,12 +27,15 @@ use tantivy::tokenizer::Tokenizer;
 use uuid::Uuid;
 
 mod just_text;
+mod public_suffix;
 pub mod region;
+mod robots_txt;
 mod url;
 
 use crate::schema::{Field, ALL_FIELDS, CENTRALITY_SCALING};
 
-pub use self::url::Url;
+pub use self::robots_txt::RobotsTxt;
+pub use self::url::{DownloadError, DownloadOptions, Url, DEFAULT_DOWNLOAD_TIMEOUT};
 use self::{just_text::JustText, region::Region};
 
 static URL_REGEX: once_cell::sync::Lazy<Regex> = once_cell::sync::Lazy::new(|| {
@@ -0,0 +1,109 @@
+// Cuely is an open source web search engine.
+// Copyright (C) 2022 Cuely ApS
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Lookup of registrable domains using the rules of the Public Suffix List
+//! (<https://publicsuffix.org/list/>).
+
+use std::collections::HashSet;
+
+static PUBLIC_SUFFIXES: once_cell::sync::Lazy<PublicSuffixList> =
+    once_cell::sync::Lazy::new(|| {
+        PublicSuffixList::parse(include_str!("../../public_suffix_list.dat"))
+    });
+
+struct PublicSuffixList {
+    rules: HashSet<String>,
+    /// Parent of the wildcard rules, e.g. `bd` for `*.bd`.
+    wildcards: HashSet<String>,
+    exceptions: HashSet<String>,
+}
+
+impl PublicSuffixList {
+    fn parse(list: &str) -> Self {
+        let mut rules = HashSet::new();
+        let mut wildcards = HashSet::new();
+        let mut exceptions = HashSet::new();
+
+        // each rule is the first word of a line
+        for rule in list
+            .lines()
+            .filter_map(|line| line.split_whitespace().next())
+        {
+            if rule.starts_with("//") {
+                continue;
+            }
+
+            let rule = rule.to_lowercase();
+
+            if let Some(exception) = rule.strip_prefix('!') {
+                exceptions.insert(exception.to_string());
+            } else if let Some(parent) = rule.strip_prefix("*.") {
+                wildcards.insert(parent.to_string());
+            } else {
+                rules.insert(rule);
+            }
+        }
+
+        Self {
+            rules,
+            wildcards,
+            exceptions,
+        }
+    }
+
+    /// Byte offset in the lowercase `host` where its public suffix starts.
+    /// The longest matching rule wins, and hosts that don't match any rule
+    /// have their TLD as public suffix.
+    fn suffix_start(&self, host: &str) -> usize {
+        let starts = std::iter::once(0).chain(host.match_indices('.').map(|(idx, _)| idx + 1));
+
+        for start in starts {
+            let suffix = &host[start..];
+
+            if self.exceptions.contains(suffix) {
+                // the public suffix of an exception is the rule without its leftmost label
+                return start + suffix.find('.').map(|idx| idx + 1).unwrap_or(suffix.len());
+            }
+
+            if self.rules.contains(suffix) {
+                return start;
+            }
+
+            if let Some((_, parent)) = suffix.split_once('.') {
+                if self.wildcards.contains(parent) {
+                    return start;
+                }
+            }
+        }
+
+        host.rfind('.').map(|idx| idx + 1).unwrap_or(0)
+    }
+}
+
+/// The registrable domain of `host`, i.e. its public suffix and the label before it.
+/// If the host is a public suffix itself, the entire host is returned.
+pub fn registrable_domain(host: &str) -> &str {
+    let suffix_start = PUBLIC_SUFFIXES.suffix_start(&host.to_ascii_lowercase());
+
+    if suffix_start == 0 {
+        return host;
+    }
+
+    match host[..suffix_start - 1].rfind('.') {
+        Some(idx) => &host[idx + 1..],
+        None => host,
+    }
+}
@@ -0,0 +1,241 @@
+// Cuely is an open source web search engine.
+// Copyright (C) 2022 Cuely ApS
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use super::{DownloadError, DownloadOptions, Url};
+
+#[derive(Debug, Clone)]
+struct Rule {
+    allow: bool,
+    pattern: String,
+}
+
+impl Rule {
+    /// Whether the path matches the pattern, where `*` matches any sequence
+    /// of characters and a trailing `$` matches the end of the path.
+    fn matches(&self, path: &str) -> bool {
+        let (pattern, anchored) = match self.pattern.strip_suffix('$') {
+            Some(pattern) => (pattern, true),
+            None => (self.pattern.as_str(), false),
+        };
+
+        let mut parts = pattern.split('*');
+        let first = parts.next().unwrap_or_default();
+
+        if !path.starts_with(first) {
+            return false;
+        }
+
+        let mut pos = first.len();
+        let parts: Vec<_> = parts.collect();
+
+        for (i, part) in parts.iter().enumerate() {
+            if anchored && i == parts.len() - 1 {
+                return path.len() >= pos + part.len() && path.ends_with(part);
+            }
+
+            match path[pos..].find(part) {
+                Some(idx) => pos += idx + part.len(),
+                None => return false,
+            }
+        }
+
+        !anchored || pos == path.len()
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct Group {
+    /// Lowercase user agents the group applies to.
+    agents: Vec<String>,
+    rules: Vec<Rule>,
+}
+
+/// The rules of a robots.txt file.
+#[derive(Debug, Clone, Default)]
+pub struct RobotsTxt {
+    groups: Vec<Group>,
+}
+
+impl RobotsTxt {
+    /// Parses the `User-agent`, `Allow` and `Disallow` directives. Other directives are ignored.
+    pub fn parse(contents: &str) -> Self {
+        let mut groups: Vec<Group> = Vec::new();
+        // whether the last group has seen a rule line, including an empty `Disallow`
+        let mut group_has_rules = false;
+
+        for line in contents.lines() {
+            let line = match line.split_once('#') {
+                Some((line, _)) => line,
+                None => line,
+            };
+
+            let (directive, value) = match line.split_once(':') {
+                Some((directive, value)) => (directive.trim().to_lowercase(), value.trim()),
+                None => continue,
+            };
+
+            match directive.as_str() {
+                "user-agent" => {
+                    // consecutive user agents share the same group
+                    match groups.last_mut() {
+                        Some(group) if !group_has_rules => group.agents.push(value.to_lowercase()),
+                        _ => {
+                            groups.push(Group {
+                                agents: vec![value.to_lowercase()],
+                                rules: Vec::new(),
+                            });
+                            group_has_rules = false;
+                        }
+                    }
+                }
+                "allow" | "disallow" => {
+                    group_has_rules = !groups.is_empty();
+
+                    // an empty disallow allows everything
+                    if value.is_empty() {
+                        continue;
+                    }
+
+                    if let Some(group) = groups.last_mut() {
+                        group.rules.push(Rule {
+                            allow: directive == "allow",
+                            pattern: value.to_string(),
+                        });
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Self { groups }
+    }
+
+    /// Downloads and parses the robots.txt of the site of `url`. A missing robots.txt
+    /// (any 4xx response) allows everything.
+    pub async fn fetch(url: &Url, options: &DownloadOptions) -> Result<Self, DownloadError> {
+        match url.robots_txt_url().download_bytes(options).await {
+            Ok(bytes) => Ok(Self::parse(&String::from_utf8_lossy(&bytes))),
+            Err(DownloadError::Status(status)) if status.is_client_error() => Ok(Self::default()),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// The group with the most specific user agent matching `user_agent`, falling back to `*`.
+    fn group(&self, user_agent: &str) -> Option<&Group> {
+        let user_agent = user_agent.to_lowercase();
+
+        self.groups
+            .iter()
+            .flat_map(|group| group.agents.iter().map(move |agent| (agent, group)))
+            .filter(|(agent, _)| agent.as_str() != "*" && user_agent.contains(agent.as_str()))
+            .max_by_key(|(agent, _)| agent.len())
+            .map(|(_, group)| group)
+            .or_else(|| {
+                self.groups
+                    .iter()
+                    .find(|group| group.agents.iter().any(|agent| agent == "*"))
+            })
+    }
+
+    /// Whether `user_agent` may crawl `path`. The longest matching rule decides,
+    /// and `Allow` wins if an `Allow` and a `Disallow` rule are equally long.
+    pub fn is_allowed(&self, path: &str, user_agent: &str) -> bool {
+        if path == "/robots.txt" {
+            return true;
+        }
+
+        match self.group(user_agent) {
+            Some(group) => group
+                .rules
+                .iter()
+                .filter(|rule| rule.matches(path))
+                .max_by_key(|rule| (rule.pattern.len(), rule.allow))
+                .map(|rule| rule.allow)
+                .unwrap_or(true),
+            None => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::webpage::url::tests::{http_response, spawn_http_server};
+
+    const ROBOTS_TXT: &str = r#"
+# comment
+User-agent: *
+Disallow: /private/
+Allow: /private/public.html
+Disallow: /*.pdf$
+
+User-agent: CuelyBot
+User-agent: OtherBot
+Disallow: /cuely-only # trailing comment
+Disallow:
+
+Sitemap: https://example.com/sitemap.xml
+"#;
+
+    #[test]
+    fn allow_disallow() {
+        let robots_txt = RobotsTxt::parse(ROBOTS_TXT);
+
+        assert!(robots_txt.is_allowed("/", "SomeBot"));
+        assert!(robots_txt.is_allowed("/robots.txt", "SomeBot"));
+        assert!(!robots_txt.is_allowed("/private/secret.html", "SomeBot"));
+        assert!(robots_txt.is_allowed("/private/public.html", "SomeBot"));
+        assert!(!robots_txt.is_allowed("/files/report.pdf", "SomeBot"));
+        assert!(robots_txt.is_allowed("/files/report.pdf.html", "SomeBot"));
+    }
+
+    #[test]
+    fn specific_user_agent() {
+        let robots_txt = RobotsTxt::parse(ROBOTS_TXT);
+
+        assert!(!robots_txt.is_allowed("/cuely-only", "CuelyBot/1.0"));
+        assert!(robots_txt.is_allowed("/private/secret.html", "CuelyBot/1.0"));
+        assert!(!robots_txt.is_allowed("/cuely-only/page", "otherbot"));
+        assert!(robots_txt.is_allowed("/cuely-only", "SomeBot"));
+    }
+
+    #[test]
+    fn empty_disallow_ends_group() {
+        let robots_txt =
+            RobotsTxt::parse("User-agent: *\nDisallow:\n\nUser-agent: BadBot\nDisallow: /");
+
+        assert!(robots_txt.is_allowed("/page", "CuelyBot"));
+        assert!(!robots_txt.is_allowed("/page", "BadBot"));
+    }
+
+    #[test]
+    fn empty() {
+        let robots_txt = RobotsTxt::parse("");
+        assert!(robots_txt.is_allowed("/anything", "CuelyBot"));
+    }
+
+    #[tokio::test]
+    async fn missing_robots_txt() {
+        let addr = spawn_http_server(|_| http_response("404 Not Found", "", ""));
+
+        let url: Url = format!("http://{}/some/page", addr).into();
+        let robots_txt = RobotsTxt::fetch(&url, &DownloadOptions::default())
+            .await
+            .unwrap();
+
+        assert!(robots_txt.is_allowed("/some/page", "CuelyBot"));
+    }
+}